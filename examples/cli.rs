@@ -1,8 +1,26 @@
-use bank_system::{BalanceManager, Deposit, Name, Storage, Transaction, Transfer};
+use bank_system::{
+    BalanceManager, Deposit, LoadMode, Money, Name, Storage, Transaction, Transfer, report,
+};
 use std::env;
 
+/// Сумма в основных единицах ("12.50") в валюте счёта `name`
+fn parse_amount(storage: &Storage, name: &Name, value: &str) -> Money {
+    let currency = storage
+        .accounts
+        .get(name)
+        .map(|b| b.currency)
+        .unwrap_or_default();
+    Money::parse_major(value, currency).expect("Сумма должна быть числом")
+}
+
 fn main() {
-    let mut storage = Storage::load_data("balance.csv");
+    let mut storage = match Storage::load_data("balance.csv", LoadMode::Strict) {
+        Ok((storage, _)) => storage,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
 
     let args: Vec<String> = env::args().collect();
 
@@ -22,7 +40,7 @@ fn main() {
                 return;
             }
             let name: Name = args[2].clone();
-            let amount: u64 = args[3].parse().expect("Сумма должна быть числом");
+            let amount = parse_amount(&storage, &name, &args[3]);
 
             let tx = Deposit {
                 account: name.clone(),
@@ -34,7 +52,7 @@ fn main() {
                     println!("Транзакция: депозит {} на {}", name, amount);
                     storage.save("balance.csv");
                 }
-                Err(e) => println!("Ошибка транзакции: {:?}", e),
+                Err(e) => println!("Ошибка транзакции: {}", report(&e)),
             }
         }
         "withdraw" => {
@@ -43,7 +61,7 @@ fn main() {
                 return;
             }
             let name: Name = args[2].clone();
-            let amount: u64 = args[3].parse().expect("Сумма должна быть числом");
+            let amount = parse_amount(&storage, &name, &args[3]);
 
             match storage.withdraw(&name, amount) {
                 Ok(_) => {
@@ -60,7 +78,7 @@ fn main() {
             }
             let from: Name = args[2].clone();
             let to: Name = args[3].clone();
-            let amount: u64 = args[4].parse().expect("Сумма должна быть числом");
+            let amount = parse_amount(&storage, &from, &args[4]);
 
             let tx = Transfer {
                 from: from.clone(),
@@ -73,7 +91,7 @@ fn main() {
                     println!("Транзакция: перевод {} от {} к {}", amount, from, to);
                     storage.save("balance.csv");
                 }
-                Err(e) => println!("Ошибка транзакции: {:?}", e),
+                Err(e) => println!("Ошибка транзакции: {}", report(&e)),
             }
         }
         "balance" => {
@@ -84,7 +102,7 @@ fn main() {
             let name: Name = args[2].clone();
 
             match storage.get_balance(&name) {
-                Some(b) => println!("Баланс {}: {}", name, b.result),
+                Some(b) => println!("Баланс {}: {}", name, b.money()),
                 None => println!("Пользователь {} не найден", name),
             }
         }
@@ -29,51 +29,105 @@ pub fn transaction_derive(input: TokenStream) -> TokenStream {
                     kind = "withdraw";
                 } else if val == "transfer" {
                     kind = "transfer";
+                } else if val == "exchange" {
+                    kind = "exchange";
+                } else if val == "hold" {
+                    kind = "hold";
+                } else if val == "capture" {
+                    kind = "capture";
+                } else if val == "release" {
+                    kind = "release";
                 }
             }
         }
     }
 
+    // Все виды транзакций проводятся через методы Storage (credit, debit, transfer, exchange,
+    // place_hold, capture_hold, release_hold),
+    // поэтому операции попадают в историю так же, как через BalanceManager
     let body = match kind {
         "deposit" => quote! {
-            let balance = storage.accounts.entry(self.account.clone()).or_default();
-            balance.result += self.amount;
+            storage.ensure_account(&self.account, self.amount.currency)?;
+            storage.credit(&self.account, self.amount)?;
         },
         "withdraw" => quote! {
-            let balance = storage.accounts.entry(self.account.clone()).or_default();
-            if balance.result < self.amount {
-                return Err(TxError::InsufficientFunds);
-            }
-            balance.result -= self.amount;
+            storage.debit(&self.account, self.amount)?;
         },
         "transfer" => quote! {
-            let from_balance = storage
-                .accounts
-                .get(&self.from)
-                .map(|b| b.result)
-                .unwrap_or(0);
-
-            if from_balance < self.amount {
-                return Err(TxError::InsufficientFunds);
-            }
-
-            if let Some(balance) = storage.accounts.get_mut(&self.from) {
-                balance.result -= self.amount;
-            } else {
-                return Err(TxError::InvalidAccount);
-            }
-
-            let to_balance = storage.accounts.entry(self.to.clone()).or_default();
-            to_balance.result += self.amount;
+            storage.transfer(&self.from, &self.to, self.amount)?;
+        },
+        "exchange" => quote! {
+            storage.exchange(&self.account, self.amount, self.to)?;
+        },
+        "hold" => quote! {
+            storage.place_hold(&self.account, self.hold, self.amount, self.expires)?;
+        },
+        "capture" => quote! {
+            storage.capture_hold(&self.account, self.hold, self.amount)?;
+        },
+        "release" => quote! {
+            storage.release_hold(&self.account, self.hold)?;
         },
         _ => panic!("Unknown transaction kind"),
     };
 
+    // Описание — вид транзакции и значения всех полей в порядке объявления через пробел.
+    // Строки (имена счетов) кодируются так же, как реквизиты операций, и пробелов
+    // не содержат, иначе "a b" + "c" и "a" + "b c" дали бы одно описание
+    let fields: Vec<_> = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|f| {
+                    let ident = f.ident.clone();
+                    let is_string = match &f.ty {
+                        syn::Type::Path(ty) => ty
+                            .path
+                            .segments
+                            .last()
+                            .is_some_and(|s| s.ident == "String" || s.ident == "Name"),
+                        _ => false,
+                    };
+                    if is_string {
+                        quote! { crate::operations::escape(&self.#ident) }
+                    } else {
+                        quote! { self.#ident.to_string() }
+                    }
+                })
+                .collect(),
+            _ => panic!("Transaction can only be derived for structs with named fields"),
+        },
+        _ => panic!("Transaction can only be derived for structs"),
+    };
+
+    // Счета, которые транзакция может изменить: по ним SharedStorage решает, что блокировать
+    let accounts = match kind {
+        "transfer" => quote! { vec![&self.from, &self.to] },
+        _ => quote! { vec![&self.account] },
+    };
+
     let expanded = quote! {
         impl Transaction for #name {
-            fn apply(&self, storage: &mut Storage) -> Result<(), TxError> {
-                #body
-                Ok(())
+            fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+                // Каждая транзакция атомарна и попадает в журнал одной записью
+                storage.atomic(|storage| {
+                    #body
+                    Ok(())
+                })
+            }
+
+            fn fingerprint(&self) -> String {
+                let mut fingerprint = String::from(#kind);
+                #(
+                    fingerprint.push(' ');
+                    fingerprint.push_str(&#fields);
+                )*
+                fingerprint
+            }
+
+            fn accounts(&self) -> Vec<&Name> {
+                #accounts
             }
         }
     };
@@ -1,49 +1,226 @@
-use crate::operations::OpKind;
+use crate::Name;
+use crate::errors::BankError;
+use crate::money::{Amount, Currency, SignedAmount};
+use crate::operations::{OpKind, Operation};
 use crate::storage::Storage;
+use std::collections::BTreeMap;
 
-pub fn find_best(storage: &Storage) -> Option<(&str, f32)> {
-    if storage.accounts.is_empty() {
-        return None;
-    }
-    let mut best_factor = f32::MIN;
-    let mut best_name = "";
-    for (name, balance) in &storage.accounts {
-        let mut all_positive = 0;
-        for op in &balance.last_ops {
-            if let OpKind::Deposit(value) = op {
-                all_positive += *value as u64
+/// Обороты счёта или группы счетов в одной валюте. Зачисления — пополнения, входящие
+/// переводы и проценты, списания — снятия и исходящие переводы. Обмен валюты
+/// деньги не приносит и не уносит, поэтому в обороты не входит, только в счётчики.
+/// Суммы в минимальных единицах; итоги, не влезающие в [`Amount`], упираются в максимум.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowStats {
+    pub currency: Currency,
+    /// Сумма зачислений
+    pub inflow: Amount,
+    /// Сумма списаний
+    pub outflow: Amount,
+    /// Зачисления минус списания
+    pub net: SignedAmount,
+    /// Сколько было зачислений
+    pub inflows: u64,
+    /// Сколько было списаний
+    pub outflows: u64,
+    /// Средний размер зачисления или списания с округлением вниз; `None`, если их не было
+    pub average: Option<Amount>,
+    /// Медиана размера зачисления или списания; при чётном числе — среднее двух
+    /// серединных с округлением вниз; `None`, если их не было
+    pub median: Option<Amount>,
+    /// Число операций по видам ([`OpKind::name`]), включая обмены и закрытие счёта
+    pub counts: BTreeMap<&'static str, u64>,
+}
+
+impl FlowStats {
+    /// Обороты по операциям `ops` в основной валюте `currency`
+    pub fn from_ops<'a>(currency: Currency, ops: impl IntoIterator<Item = &'a Operation>) -> Self {
+        let mut flows = Flows::default();
+        flows.extend(ops);
+        flows.finish(currency)
+    }
+
+    /// Сколько всего операций
+    pub fn operations(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Во сколько раз зачисления больше списаний; `None`, если списаний не было
+    pub fn ratio(&self) -> Option<f64> {
+        if self.outflow.is_zero() {
+            return None;
+        }
+        Some(self.inflow.value() as f64 / self.outflow.value() as f64)
+    }
+}
+
+/// Оборот одной операции в основной валюте счёта
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Flow {
+    In(Amount),
+    Out(Amount),
+}
+
+/// Зачисление или списание, которым считается операция; обмен и закрытие счёта — `None`
+pub(crate) fn flow(kind: &OpKind) -> Option<Flow> {
+    match *kind {
+        OpKind::Deposit(value) | OpKind::TransferIn(value) | OpKind::Interest(value) => {
+            Some(Flow::In(value))
+        }
+        OpKind::Withdraw(value) | OpKind::TransferOut(value) => Some(Flow::Out(value)),
+        OpKind::Exchange { .. } | OpKind::CloseAccount => None,
+    }
+}
+
+/// Размеры зачислений и списаний, из которых потом считаются [`FlowStats`]
+#[derive(Default)]
+struct Flows {
+    inflows: Vec<u64>,
+    outflows: Vec<u64>,
+    counts: BTreeMap<&'static str, u64>,
+}
+
+impl Flows {
+    fn extend<'a>(&mut self, ops: impl IntoIterator<Item = &'a Operation>) {
+        for op in ops {
+            match flow(&op.kind) {
+                Some(Flow::In(value)) => self.inflows.push(value.value()),
+                Some(Flow::Out(value)) => self.outflows.push(value.value()),
+                None => {}
+            }
+            *self.counts.entry(op.kind.name()).or_default() += 1;
+        }
+    }
+
+    fn finish(self, currency: Currency) -> FlowStats {
+        let total = |sizes: &[u64]| sizes.iter().map(|&v| u128::from(v)).sum::<u128>();
+        let clamp = |sum: u128| u64::try_from(sum).map_or(Amount::MAX, Amount::new);
+        let (inflow, outflow) = (total(&self.inflows), total(&self.outflows));
+        let net = (inflow as i128 - outflow as i128).clamp(-(u64::MAX as i128), u64::MAX as i128);
+
+        let mut sizes: Vec<u64> = self.inflows.iter().chain(&self.outflows).copied().collect();
+        sizes.sort_unstable();
+        let count = sizes.len() as u128;
+        let average = (count > 0).then(|| Amount::new(((inflow + outflow) / count) as u64));
+        let median = match sizes.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(Amount::new(sizes[len / 2])),
+            len => {
+                let (low, high) = (u128::from(sizes[len / 2 - 1]), u128::from(sizes[len / 2]));
+                Some(Amount::new(((low + high) / 2) as u64))
+            }
+        };
+
+        FlowStats {
+            currency,
+            inflow: clamp(inflow),
+            outflow: clamp(outflow),
+            net: SignedAmount::new(net).expect("ограничено пределами SignedAmount"),
+            inflows: self.inflows.len() as u64,
+            outflows: self.outflows.len() as u64,
+            average,
+            median,
+            counts: self.counts,
+        }
+    }
+}
+
+/// По чему сравнивать счета в [`Storage::top_accounts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBy {
+    /// Учётный баланс в основной валюте
+    Balance,
+    /// Сумма зачислений
+    Inflow,
+    /// Сумма списаний
+    Outflow,
+    /// Зачисления минус списания
+    Net,
+}
+
+impl Storage {
+    /// Обороты счёта `name` за всю историю, вместе с архивной
+    pub fn account_stats(&self, name: &Name) -> Result<FlowStats, BankError> {
+        let history = self.history(name, 0, usize::MAX)?;
+        Ok(FlowStats::from_ops(self.accounts[name].currency, &history))
+    }
+
+    /// Обороты всего банка по валютам: каждый счёт входит в итог своей основной
+    /// валюты. Перевод между клиентами считается дважды — списанием у одного
+    /// и зачислением у другого.
+    pub fn bank_stats(&self) -> Result<BTreeMap<Currency, FlowStats>, BankError> {
+        let mut flows: BTreeMap<Currency, Flows> = BTreeMap::new();
+        for (name, history) in self.histories()? {
+            let currency = self.accounts[name].currency;
+            flows.entry(currency).or_default().extend(&history);
+        }
+        Ok(flows
+            .into_iter()
+            .map(|(currency, flows)| (currency, flows.finish(currency)))
+            .collect())
+    }
+
+    /// Первые `n` счетов в валюте `currency` по убыванию `by`; при равенстве раньше
+    /// идёт меньшее имя. Счета в других валютах не сравниваются.
+    pub fn top_accounts(
+        &self,
+        currency: Currency,
+        by: RankBy,
+        n: usize,
+    ) -> Result<Vec<(Name, SignedAmount)>, BankError> {
+        let mut ranked = Vec::new();
+        let histories = match by {
+            RankBy::Balance => BTreeMap::new(),
+            _ => self.histories()?,
+        };
+        for (name, balance) in &self.accounts {
+            if balance.currency != currency {
+                continue;
             }
+            let stats = || FlowStats::from_ops(currency, &histories[name]);
+            let value = match by {
+                RankBy::Balance => balance.result,
+                RankBy::Inflow => stats().inflow.into(),
+                RankBy::Outflow => stats().outflow.into(),
+                RankBy::Net => stats().net,
+            };
+            ranked.push((name.clone(), value));
         }
-        // почти то же самое на итераторах!
-        let all_negative: u64 = balance
-            .last_ops
-            .iter()
-            .filter_map(|op| match op {
-                OpKind::Withdraw(value) => Some(*value as u64),
-                _ => None,
-            })
-            .sum();
-        let factor = all_positive as f32 / all_negative as f32;
-        if factor > best_factor {
-            best_factor = factor;
-            best_name = name;
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(n);
+        Ok(ranked)
+    }
+}
+
+/// Счёт с самым большим отношением зачислений к списаниям по истории в памяти.
+/// Счета без списаний не участвуют: отношение у них не определено. При равенстве
+/// побеждает меньшее имя; `None`, если сравнивать некого.
+pub fn find_best(storage: &Storage) -> Option<(&str, f32)> {
+    let mut names: Vec<&Name> = storage.accounts.keys().collect();
+    names.sort();
+    let mut best: Option<(&str, f64)> = None;
+    for name in names {
+        let balance = &storage.accounts[name];
+        let Some(ratio) = FlowStats::from_ops(balance.currency, &balance.last_ops).ratio() else {
+            continue;
+        };
+        if best.is_none_or(|(_, best)| ratio > best) {
+            best = Some((name, ratio));
         }
     }
-    Some((best_name, best_factor))
+    best.map(|(name, ratio)| (name, ratio as f32))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::money::Amount;
     use crate::operations::{Balance, OpKind};
     use crate::storage::Storage;
     use std::collections::HashMap;
 
     #[test]
     fn find_best_empty_storage() {
-        let storage = Storage {
-            accounts: HashMap::new(),
-        };
+        let storage = Storage::new();
         assert_eq!(find_best(&storage), None);
     }
 
@@ -51,10 +228,14 @@ mod tests {
     fn find_best_single_account() {
         let mut accounts = HashMap::new();
         let mut balance = Balance::new();
-        balance.last_ops = vec![OpKind::Deposit(1000), OpKind::Withdraw(500)];
+        balance.last_ops = vec![
+            OpKind::Deposit(Amount::new(1000)).into(),
+            OpKind::Withdraw(Amount::new(500)).into(),
+        ];
         accounts.insert("Alice".to_string(), balance);
 
-        let storage = Storage { accounts };
+        let mut storage = Storage::new();
+        storage.accounts = accounts;
         let result = find_best(&storage);
 
         assert!(result.is_some());
@@ -68,28 +249,32 @@ mod tests {
         let mut accounts = HashMap::new();
 
         let mut dad_balance = Balance::new();
-        dad_balance.last_ops = vec![OpKind::Deposit(200000), OpKind::Withdraw(100000)];
+        dad_balance.last_ops = vec![
+            OpKind::Deposit(Amount::new(200000)).into(),
+            OpKind::Withdraw(Amount::new(100000)).into(),
+        ];
 
         let mut mom_balance = Balance::new();
         mom_balance.last_ops = vec![
-            OpKind::Deposit(120000),
-            OpKind::Withdraw(50000),
-            OpKind::Withdraw(20000),
+            OpKind::Deposit(Amount::new(120000)).into(),
+            OpKind::Withdraw(Amount::new(50000)).into(),
+            OpKind::Withdraw(Amount::new(20000)).into(),
         ];
 
         let mut son_balance = Balance::new();
         son_balance.last_ops = vec![
-            OpKind::Deposit(5000),
-            OpKind::Withdraw(500),
-            OpKind::Withdraw(1000),
-            OpKind::Withdraw(700),
+            OpKind::Deposit(Amount::new(5000)).into(),
+            OpKind::Withdraw(Amount::new(500)).into(),
+            OpKind::Withdraw(Amount::new(1000)).into(),
+            OpKind::Withdraw(Amount::new(700)).into(),
         ];
 
         accounts.insert("Dad".to_string(), dad_balance);
         accounts.insert("Mom".to_string(), mom_balance);
         accounts.insert("Son".to_string(), son_balance);
 
-        let storage = Storage { accounts };
+        let mut storage = Storage::new();
+        storage.accounts = accounts;
         let result = find_best(&storage);
 
         assert!(result.is_some());
@@ -102,4 +287,172 @@ mod tests {
         assert_eq!(name, "Son");
         assert!((factor - 2.27).abs() < 0.01);
     }
+
+    #[test]
+    fn find_best_skips_accounts_without_outflow_and_breaks_ties_by_name() {
+        let account = |ops: Vec<OpKind>| {
+            let mut balance = Balance::new();
+            balance.last_ops = ops.into_iter().map(Operation::from).collect();
+            balance
+        };
+        let mut storage = Storage::new();
+        storage.accounts = HashMap::from([
+            (
+                "Saver".to_string(),
+                account(vec![OpKind::Deposit(Amount::new(100))]),
+            ),
+            ("Empty".to_string(), account(Vec::new())),
+        ]);
+        assert_eq!(find_best(&storage), None);
+
+        for name in ["Zoe", "Bob", "Max"] {
+            storage.accounts.insert(
+                name.to_string(),
+                account(vec![
+                    OpKind::Deposit(Amount::new(300)),
+                    OpKind::Withdraw(Amount::new(100)),
+                ]),
+            );
+        }
+        assert_eq!(find_best(&storage), Some(("Bob", 3.0)));
+    }
+
+    fn ops(kinds: &[&str]) -> Vec<Operation> {
+        kinds.iter().map(|op| op.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn flow_stats_of_history() {
+        let history = ops(&[
+            "D:1000",
+            "T+:300",
+            "I:5",
+            "W:200",
+            "T-:100",
+            "X:50:RUB>1:USD@0.02",
+        ]);
+        let stats = FlowStats::from_ops(Currency::RUB, &history);
+        assert_eq!(stats.inflow, Amount::new(1305));
+        assert_eq!(stats.outflow, Amount::new(300));
+        assert_eq!(stats.net, 1005);
+        assert_eq!((stats.inflows, stats.outflows), (3, 2));
+        // размеры 5, 100, 200, 300, 1000
+        assert_eq!(stats.average, Some(Amount::new(321)));
+        assert_eq!(stats.median, Some(Amount::new(200)));
+        assert_eq!(stats.operations(), 6);
+        assert_eq!(stats.counts["exchange"], 1);
+        assert_eq!(stats.counts["interest"], 1);
+        assert_eq!(stats.ratio(), Some(4.35));
+
+        let even = FlowStats::from_ops(Currency::RUB, &ops(&["D:1", "D:4", "W:2", "W:7"]));
+        assert_eq!(even.median, Some(Amount::new(3)));
+        assert_eq!(even.net, -4);
+    }
+
+    #[test]
+    fn flow_stats_of_nothing() {
+        let empty = FlowStats::from_ops(Currency::USD, &[]);
+        assert_eq!(empty.inflow, Amount::ZERO);
+        assert_eq!(empty.net, SignedAmount::ZERO);
+        assert_eq!((empty.average, empty.median), (None, None));
+        assert_eq!(empty.ratio(), None);
+        assert_eq!(empty.operations(), 0);
+
+        // только обмены и закрытие: операции есть, оборотов нет
+        let closed = FlowStats::from_ops(Currency::RUB, &ops(&["X:50:RUB>1:USD@0.02", "C"]));
+        assert_eq!(closed.operations(), 2);
+        assert_eq!(closed.average, None);
+    }
+
+    #[test]
+    fn flow_totals_saturate() {
+        let max = format!("D:{}", u64::MAX);
+        let stats = FlowStats::from_ops(Currency::RUB, &ops(&[&max, &max, "W:1"]));
+        assert_eq!(stats.inflow, Amount::MAX);
+        assert_eq!(stats.net.value(), u64::MAX as i128);
+        assert_eq!(stats.average, Some(Amount::new(u64::MAX / 3 * 2)));
+        assert_eq!(stats.median, Some(Amount::MAX));
+    }
+
+    fn bank() -> Storage {
+        let mut storage = Storage::new();
+        let account = |currency, result: i128, kinds: &[&str]| {
+            let mut balance = Balance::with_currency(currency);
+            balance.result = SignedAmount::new(result).unwrap();
+            balance.last_ops = ops(kinds);
+            balance
+        };
+        storage.accounts = HashMap::from([
+            (
+                "Alice".to_string(),
+                account(Currency::RUB, 700, &["D:1000", "T-:300"]),
+            ),
+            (
+                "Bob".to_string(),
+                account(Currency::RUB, 700, &["D:400", "T+:300"]),
+            ),
+            ("Carol".to_string(), account(Currency::RUB, 0, &[])),
+            ("Dave".to_string(), account(Currency::USD, 50, &["D:50"])),
+        ]);
+        storage
+    }
+
+    #[test]
+    fn bank_stats_by_currency() {
+        let storage = bank();
+        let stats = storage.bank_stats().unwrap();
+        assert_eq!(
+            stats.keys().copied().collect::<Vec<_>>(),
+            vec![Currency::RUB, Currency::USD]
+        );
+        let rub = &stats[&Currency::RUB];
+        // перевод Алисы Бобу — и списание, и зачисление
+        assert_eq!(rub.inflow, Amount::new(1700));
+        assert_eq!(rub.outflow, Amount::new(300));
+        assert_eq!(rub.median, Some(Amount::new(350)));
+        assert_eq!(stats[&Currency::USD].inflow, Amount::new(50));
+
+        let alice = storage.account_stats(&"Alice".to_string()).unwrap();
+        assert_eq!(alice.net, 700);
+        assert!(matches!(
+            storage.account_stats(&"Nobody".to_string()),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert!(Storage::new().bank_stats().unwrap().is_empty());
+    }
+
+    #[test]
+    fn top_accounts_are_deterministic() {
+        let storage = bank();
+        let top = |by, n| {
+            storage
+                .top_accounts(Currency::RUB, by, n)
+                .unwrap()
+                .into_iter()
+                .map(|(name, value)| (name, value.value()))
+                .collect::<Vec<_>>()
+        };
+        let pair = |name: &str, value| (name.to_string(), value);
+        // у Алисы и Боба одинаковый баланс — первой идёт Алиса
+        assert_eq!(
+            top(RankBy::Balance, 10),
+            vec![pair("Alice", 700), pair("Bob", 700), pair("Carol", 0)]
+        );
+        assert_eq!(top(RankBy::Inflow, 1), vec![pair("Alice", 1000)]);
+        assert_eq!(
+            top(RankBy::Outflow, 2),
+            vec![pair("Alice", 300), pair("Bob", 0)]
+        );
+        assert_eq!(
+            top(RankBy::Net, 3),
+            vec![pair("Alice", 700), pair("Bob", 700), pair("Carol", 0)]
+        );
+        assert!(top(RankBy::Balance, 0).is_empty());
+        assert!(
+            storage
+                .top_accounts(Currency::EUR, RankBy::Balance, 5)
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,684 @@
+use crate::Name;
+use crate::clock::Date;
+use crate::errors::BankError;
+use crate::journal::{Change, JournalEntry};
+use crate::ledger::LedgerAccount;
+use crate::money::Currency;
+use crate::operations::{Balance, Operation};
+use crate::storage::Storage;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Сколько истории счёта держать в памяти. Более старые операции
+/// [`Storage::archive_history`] переносит в архив на диске.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Retention {
+    /// Вся история в памяти, архив не ведётся
+    #[default]
+    Unlimited,
+    /// Последние N операций счёта
+    LastOps(usize),
+    /// Операции за сегодня и предыдущие N дней; операции без времени — старше любых
+    LastDays(u32),
+}
+
+/// "all", "ops:1000" или "days:90"
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Retention::Unlimited => write!(f, "all"),
+            Retention::LastOps(count) => write!(f, "ops:{}", count),
+            Retention::LastDays(days) => write!(f, "days:{}", days),
+        }
+    }
+}
+
+impl FromStr for Retention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || {
+            format!(
+                "неверное окно хранения '{}': нужно all, ops:N или days:N",
+                s
+            )
+        };
+        match s.split_once(':') {
+            None if s == "all" => Ok(Retention::Unlimited),
+            Some(("ops", count)) => count.parse().map(Retention::LastOps).map_err(|_| bad()),
+            Some(("days", days)) => days.parse().map(Retention::LastDays).map_err(|_| bad()),
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl Retention {
+    /// Сколько первых операций истории `ops` уходит в архив на день `today`.
+    /// Последняя операция остаётся в памяти всегда: по ней видно, закрыт ли счёт.
+    pub fn excess(self, ops: &[Operation], today: Date) -> usize {
+        let movable = ops.len().saturating_sub(1);
+        match self {
+            Retention::Unlimited => 0,
+            Retention::LastOps(keep) => ops.len().saturating_sub(keep).min(movable),
+            Retention::LastDays(days) => {
+                let since = today.add_days(-i64::from(days));
+                ops[..movable]
+                    .iter()
+                    .take_while(|op| op.meta.at.is_none_or(|at| at.date() < since))
+                    .count()
+            }
+        }
+    }
+}
+
+/// Что счёт помнит об операциях, перенесённых в архив: сколько их и итог их проводок.
+/// Этого хватает, чтобы сводить книги, не читая архив. Номера транзакций архив
+/// не хранит: последняя операция счёта, а с ней и самый большой номер, остаётся в памяти.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Archived {
+    /// Сколько операций в архиве; первая операция `last_ops` — следующая по номеру
+    pub count: u64,
+    /// Сальдо проводок архивных операций по внешним счетам книги в минимальных единицах.
+    /// Проводки по самому счёту клиента — те же суммы с обратным знаком.
+    pub postings: BTreeMap<(LedgerAccount, Currency), i128>,
+}
+
+impl Archived {
+    /// Итог архивных проводок по счёту клиента в валюте `currency`
+    pub fn customer(&self, currency: Currency) -> i128 {
+        -self
+            .postings
+            .iter()
+            .filter(|((_, c), _)| *c == currency)
+            .map(|(_, amount)| amount)
+            .sum::<i128>()
+    }
+
+    /// Итог архивных проводок по внешнему счёту `account` в валюте `currency`
+    pub fn external(&self, account: &LedgerAccount, currency: Currency) -> i128 {
+        self.postings
+            .get(&(account.clone(), currency))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Пусто, если архива нет, иначе "3 @cash:RUB:-10000 @clearing:RUB:3000":
+/// число операций и сальдо внешних счетов
+impl fmt::Display for Archived {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Archived::default() {
+            return Ok(());
+        }
+        write!(f, "{}", self.count)?;
+        for ((account, currency), amount) in &self.postings {
+            write!(f, " {}:{}:{}", account, currency, amount)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Archived {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("неверная сводка архива '{}'", s);
+        let mut parts = s.split_whitespace();
+        let Some(head) = parts.next() else {
+            return Ok(Archived::default());
+        };
+        let mut archived = Archived {
+            count: head.parse().map_err(|_| bad())?,
+            postings: BTreeMap::new(),
+        };
+        for part in parts {
+            let [account, currency, amount] = part.splitn(3, ':').collect::<Vec<_>>()[..] else {
+                return Err(bad());
+            };
+            let account: LedgerAccount = account.parse().map_err(|_| bad())?;
+            if matches!(account, LedgerAccount::Customer(_)) {
+                return Err(bad());
+            }
+            let key = (account, currency.parse().map_err(|_| bad())?);
+            let amount = amount.parse().map_err(|_| bad())?;
+            if archived.postings.insert(key, amount).is_some() {
+                return Err(bad());
+            }
+        }
+        Ok(archived)
+    }
+}
+
+impl Balance {
+    /// Сколько операций в полной истории счёта, вместе с архивными
+    pub fn history_len(&self) -> u64 {
+        self.archived.count + self.last_ops.len() as u64
+    }
+
+    /// Убирает из памяти первые `count` операций истории счёта `name`, учитывая их
+    /// в сводке [`Balance::archived`], и возвращает их
+    pub(crate) fn move_to_archive(&mut self, name: &Name, count: usize) -> Vec<Operation> {
+        let count = count.min(self.last_ops.len());
+        let moved: Vec<Operation> = self.last_ops.drain(..count).collect();
+        for op in &moved {
+            for posting in op.kind.postings(name, self.currency) {
+                if matches!(posting.account, LedgerAccount::Customer(_)) {
+                    continue;
+                }
+                *self
+                    .archived
+                    .postings
+                    .entry((posting.account, posting.amount.currency))
+                    .or_default() += posting.amount.amount.value();
+            }
+        }
+        self.archived.postings.retain(|_, amount| *amount != 0);
+        self.archived.count += moved.len() as u64;
+        moved
+    }
+}
+
+/// Архив истории: файл `<снимок>.archive` со строками "Name,Seq,Op", где Seq —
+/// номер операции в полной истории счёта, считая с нуля. Строки только дописываются.
+/// Если номер встречается несколько раз, верна последняя строка: повторы остаются
+/// от переноса, прерванного до записи в журнал, и от счёта, открытого заново
+/// под тем же именем.
+#[derive(Debug, Clone)]
+pub struct Archive {
+    path: PathBuf,
+}
+
+impl Archive {
+    /// Путь к архиву для файла-снимка
+    pub fn path_for(snapshot: &str) -> PathBuf {
+        PathBuf::from(format!("{}.archive", snapshot))
+    }
+
+    /// Открывает архив, отрезая строку, которую не успели дописать до падения.
+    /// Файл создаётся при первом переносе.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Archive> {
+        let path = path.as_ref().to_path_buf();
+        match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(mut file) => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                let valid_len = data
+                    .iter()
+                    .rposition(|b| *b == b'\n')
+                    .map_or(0, |pos| pos + 1);
+                if valid_len < data.len() {
+                    file.set_len(valid_len as u64)?;
+                    file.sync_all()?;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Archive { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Дописывает операции `ops` счёта `name`, первая из которых имеет номер `first`,
+    /// и дожидается, пока они окажутся на диске. При ошибке дописанное отрезается.
+    pub fn append(&self, name: &Name, first: u64, ops: &[Operation]) -> io::Result<()> {
+        let mut data = String::new();
+        for (seq, op) in (first..).zip(ops) {
+            data.push_str(&format!("{},{},{}\n", name, seq, op));
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        let len = file.metadata()?.len();
+        let written = file
+            .write_all(data.as_bytes())
+            .and_then(|_| file.sync_data());
+        if written.is_err() {
+            let _ = file.set_len(len);
+        }
+        written
+    }
+
+    /// Архивные операции счёта `name` с номерами из `range`, по порядку
+    pub fn read(&self, name: &Name, range: Range<u64>) -> io::Result<Vec<Operation>> {
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut found = self.scan(|owner, seq| owner == name && range.contains(&seq))?;
+        complete(name, range, found.remove(name).unwrap_or_default())
+    }
+
+    /// Архивные операции всех счетов за один проход по файлу: у каждого счёта из
+    /// `counts` — все его операции с номерами `0..count`, по порядку
+    pub fn read_all(
+        &self,
+        counts: &BTreeMap<&str, u64>,
+    ) -> io::Result<BTreeMap<Name, Vec<Operation>>> {
+        if counts.values().all(|count| *count == 0) {
+            return Ok(BTreeMap::new());
+        }
+        let mut found =
+            self.scan(|owner, seq| counts.get(owner).is_some_and(|count| seq < *count))?;
+        counts
+            .iter()
+            .map(|(name, count)| {
+                let ops = found.remove(*name).unwrap_or_default();
+                Ok((name.to_string(), complete(name, 0..*count, ops)?))
+            })
+            .collect()
+    }
+
+    /// Читает архив целиком и собирает операции, для которых `wanted(счёт, номер)`
+    fn scan(
+        &self,
+        wanted: impl Fn(&str, u64) -> bool,
+    ) -> io::Result<BTreeMap<Name, BTreeMap<u64, Operation>>> {
+        let bad = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("неверная строка архива '{}'", line),
+            )
+        };
+        let mut found: BTreeMap<Name, BTreeMap<u64, Operation>> = BTreeMap::new();
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            let Some((owner, rest)) = line.split_once(',') else {
+                return Err(bad(&line));
+            };
+            let (seq, op) = rest.split_once(',').ok_or_else(|| bad(&line))?;
+            let seq: u64 = seq.parse().map_err(|_| bad(&line))?;
+            if wanted(owner, seq) {
+                let op = op.parse::<Operation>().map_err(|_| bad(&line))?;
+                found.entry(owner.to_string()).or_default().insert(seq, op);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Операции `found` счёта `name`, если среди них есть все номера из `range`
+fn complete(
+    name: &str,
+    range: Range<u64>,
+    found: BTreeMap<u64, Operation>,
+) -> io::Result<Vec<Operation>> {
+    if let Some(missing) = range.clone().find(|seq| !found.contains_key(seq)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("в архиве нет операции №{} счёта {}", missing, name),
+        ));
+    }
+    Ok(found.into_values().collect())
+}
+
+/// Дописывает в архив операции счетов, вышедшие за окно `retention` на день `today`.
+/// Возвращает запись журнала о переносе и число перенесённых операций;
+/// сами счета не меняет — это делает применение записи.
+pub(crate) fn roll<'a>(
+    archive: &Archive,
+    accounts: impl Iterator<Item = (&'a Name, &'a Balance)>,
+    retention: Retention,
+    today: Date,
+) -> Result<(JournalEntry, usize), BankError> {
+    let mut accounts: Vec<_> = accounts.collect();
+    accounts.sort_by(|a, b| a.0.cmp(b.0));
+    let mut entry = JournalEntry::default();
+    let mut moved = 0;
+    for (name, balance) in accounts {
+        let count = retention.excess(&balance.last_ops, today);
+        if count == 0 {
+            continue;
+        }
+        archive
+            .append(name, balance.archived.count, &balance.last_ops[..count])
+            .map_err(BankError::Archive)?;
+        entry.changes.push(Change::Archive {
+            name: name.clone(),
+            count,
+        });
+        moved += count;
+    }
+    Ok((entry, moved))
+}
+
+/// Операции полной истории счёта с номерами `offset..offset + limit`: архивные
+/// читаются из `archive`, остальные берутся из памяти
+pub(crate) fn page(
+    archive: Option<&Archive>,
+    name: &Name,
+    balance: &Balance,
+    offset: u64,
+    limit: usize,
+) -> Result<Vec<Operation>, BankError> {
+    let end = offset
+        .saturating_add(limit as u64)
+        .min(balance.history_len());
+    let start = offset.min(end);
+    let archived = balance.archived.count;
+
+    let mut ops = Vec::new();
+    if start < archived {
+        let archive = archive.ok_or_else(|| {
+            BankError::Archive(io::Error::new(
+                io::ErrorKind::NotFound,
+                "архив истории не подключён",
+            ))
+        })?;
+        ops = archive
+            .read(name, start..end.min(archived))
+            .map_err(BankError::Archive)?;
+    }
+    let in_memory =
+        (start.max(archived) - archived) as usize..(end.max(archived) - archived) as usize;
+    ops.extend_from_slice(&balance.last_ops[in_memory]);
+    Ok(ops)
+}
+
+impl Storage {
+    /// Переносит в архив операции, вышедшие за окно [`Storage::retention`] по часам
+    /// хранилища, и возвращает, сколько их перенесено. Перенос пишется в журнал,
+    /// так что после падения история не теряется и не задваивается.
+    /// Хранилище без архива — не загруженное из файла — ничего не переносит.
+    pub fn archive_history(&mut self) -> Result<usize, BankError> {
+        let Some(archive) = &self.archive else {
+            return Ok(0);
+        };
+        let (entry, moved) = roll(
+            archive,
+            self.accounts.iter(),
+            self.retention,
+            self.clock.today(),
+        )?;
+        if let Some(journal) = self.journal.as_mut()
+            && !entry.is_empty()
+        {
+            journal.append(&entry)?;
+        }
+        entry.apply(&mut self.accounts, &mut self.orders, &mut self.keys);
+        Ok(moved)
+    }
+
+    /// Страница полной истории счёта: операции с номерами `offset..offset + limit`
+    /// от самой старой, из архива и из памяти вместе. За концом истории страница
+    /// короче или пуста; всего операций — [`Balance::history_len`].
+    pub fn history(
+        &self,
+        name: &Name,
+        offset: u64,
+        limit: usize,
+    ) -> Result<Vec<Operation>, BankError> {
+        let balance = self
+            .accounts
+            .get(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        page(self.archive.as_ref(), name, balance, offset, limit)
+    }
+
+    /// Полная история всех счетов, как [`Storage::history`], но с одним чтением архива:
+    /// отчётам по всему банку не нужно перечитывать архив ради каждого счёта
+    pub fn histories(&self) -> Result<BTreeMap<&Name, Vec<Operation>>, BankError> {
+        let counts: BTreeMap<&str, u64> = self
+            .accounts
+            .iter()
+            .map(|(name, balance)| (name.as_str(), balance.archived.count))
+            .collect();
+        let mut archived = if counts.values().all(|count| *count == 0) {
+            BTreeMap::new()
+        } else {
+            self.archive
+                .as_ref()
+                .ok_or_else(|| {
+                    BankError::Archive(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "архив истории не подключён",
+                    ))
+                })?
+                .read_all(&counts)
+                .map_err(BankError::Archive)?
+        };
+        Ok(self
+            .accounts
+            .iter()
+            .map(|(name, balance)| {
+                let mut ops = archived.remove(name).unwrap_or_default();
+                ops.extend_from_slice(&balance.last_ops);
+                (name, ops)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::RankBy;
+    use crate::clock::ManualClock;
+    use crate::errors::LoadMode;
+    use crate::money::Amount;
+    use crate::operations::OpKind;
+    use crate::storage::{AccountPolicy, BalanceManager};
+    use crate::testing::{date, rub};
+    use crate::transaction::{Transaction, Transfer};
+    use std::fs;
+    use std::sync::Arc;
+
+    fn cleanup(file: &str) {
+        for path in [
+            PathBuf::from(file),
+            crate::journal::Journal::path_for(file),
+            Archive::path_for(file),
+        ] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Хранилище из файла `file`: у Алисы по пополнению в день с 1 по 5 января
+    /// и перевод Бобу 5-го
+    fn five_days(file: &str) -> (Storage, Arc<ManualClock>) {
+        cleanup(file);
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let clock = Arc::new(ManualClock::new(date("2026-01-01")));
+        storage.clock = clock.clone();
+        let alice = "Alice".to_string();
+        for day in 0..5 {
+            storage.deposit(&alice, rub(100 + day)).unwrap();
+            clock.advance(1);
+        }
+        clock.advance(-1);
+        Transfer {
+            from: alice,
+            to: "Bob".to_string(),
+            amount: rub(50),
+        }
+        .apply(&mut storage)
+        .unwrap();
+        (storage, clock)
+    }
+
+    #[test]
+    fn retention_windows() {
+        let ops: Vec<Operation> = [
+            "D:1^2026-01-01T00:00:00Z",
+            "D:2",
+            "D:3^2026-01-03T10:00:00Z",
+        ]
+        .iter()
+        .map(|op| op.parse().unwrap())
+        .collect();
+        let today = date("2026-01-04");
+        assert_eq!(Retention::Unlimited.excess(&ops, today), 0);
+        assert_eq!(Retention::LastOps(2).excess(&ops, today), 1);
+        assert_eq!(Retention::LastOps(5).excess(&ops, today), 0);
+        // последняя операция остаётся в памяти даже при нулевом окне
+        assert_eq!(Retention::LastOps(0).excess(&ops, today), 2);
+        assert_eq!(Retention::LastDays(2).excess(&ops, today), 2);
+        assert_eq!(Retention::LastDays(3).excess(&ops, today), 0);
+        assert_eq!(Retention::LastDays(0).excess(&ops, date("2027-01-01")), 2);
+        assert_eq!(Retention::LastOps(3).excess(&[], today), 0);
+
+        for retention in [
+            Retention::Unlimited,
+            Retention::LastOps(1000),
+            Retention::LastDays(90),
+        ] {
+            assert_eq!(retention.to_string().parse(), Ok(retention));
+        }
+        for bad in ["", "ops", "ops:-1", "days:x", "weeks:2"] {
+            assert!(bad.parse::<Retention>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn summary_roundtrip() {
+        let mut balance = Balance::new();
+        balance.last_ops = ["D:10000#1", "T-:3000#2&Bob", "W:500#3"]
+            .iter()
+            .map(|op| op.parse().unwrap())
+            .collect();
+        let moved = balance.move_to_archive(&"Alice".to_string(), 2);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(balance.last_ops.len(), 1);
+        assert_eq!(balance.history_len(), 3);
+        assert_eq!(balance.archived.customer(Currency::RUB), 7_000);
+
+        let text = balance.archived.to_string();
+        assert_eq!(text, "2 @cash:RUB:-10000 @clearing:RUB:3000");
+        assert_eq!(text.parse::<Archived>().unwrap(), balance.archived);
+        assert_eq!("".parse::<Archived>().unwrap(), Archived::default());
+        assert_eq!(Archived::default().to_string(), "");
+        for bad in [
+            "x",
+            "2#2",
+            "2 @cash:RUB",
+            "2 Alice:RUB:5",
+            "2 @cash:RUB:1 @cash:RUB:2",
+        ] {
+            assert!(bad.parse::<Archived>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn archive_pages_through_disk_and_memory() {
+        let file = "test_archive_pages.csv";
+        let (mut storage, _clock) = five_days(file);
+        let alice = "Alice".to_string();
+        let full = storage.history(&alice, 0, usize::MAX).unwrap();
+        assert_eq!(full.len(), 6);
+        let stats = storage.bank_stats().unwrap();
+        let top = storage
+            .top_accounts(Currency::RUB, RankBy::Inflow, 2)
+            .unwrap();
+
+        storage.retention = Retention::LastOps(2);
+        assert_eq!(storage.archive_history().unwrap(), 4);
+        assert_eq!(storage.accounts["Alice"].last_ops.len(), 2);
+        assert_eq!(storage.accounts["Bob"].last_ops.len(), 1);
+        // второй прогон ничего не переносит
+        assert_eq!(storage.archive_history().unwrap(), 0);
+
+        assert_eq!(storage.history(&alice, 0, usize::MAX).unwrap(), full);
+        assert_eq!(storage.history(&alice, 3, 2).unwrap(), full[3..5]);
+        assert_eq!(storage.history(&alice, 5, 10).unwrap(), full[5..]);
+        assert!(storage.history(&alice, 6, 10).unwrap().is_empty());
+        // отчёты по всему банку читают архив один раз и видят ту же историю
+        let histories = storage.histories().unwrap();
+        assert_eq!(histories[&alice], full);
+        assert_eq!(histories[&"Bob".to_string()].len(), 1);
+        assert_eq!(storage.bank_stats().unwrap(), stats);
+        assert_eq!(
+            storage
+                .top_accounts(Currency::RUB, RankBy::Inflow, 2)
+                .unwrap(),
+            top
+        );
+        assert!(matches!(
+            storage.history(&"Nobody".to_string(), 0, 1),
+            Err(BankError::UserNotFound(_))
+        ));
+
+        // книги сходятся, а после загрузки номера транзакций продолжаются
+        assert!(storage.trial_balance().is_balanced());
+        storage.save(file);
+        let (mut loaded, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts, storage.accounts);
+        assert_eq!(loaded.history(&alice, 0, usize::MAX).unwrap(), full);
+        assert!(loaded.trial_balance().is_balanced());
+        loaded.deposit(&alice, rub(1)).unwrap();
+        let last = loaded.accounts["Alice"].last_ops.last().unwrap();
+        assert_eq!(last.meta.tx, Some(7));
+
+        cleanup(file);
+    }
+
+    #[test]
+    fn archiving_survives_restart_through_journal() {
+        let file = "test_archive_journal.csv";
+        let (mut storage, clock) = five_days(file);
+        storage.save(file);
+        let alice = "Alice".to_string();
+        let full = storage.history(&alice, 0, usize::MAX).unwrap();
+
+        // окно в два дня: 1 и 2 января уходят в архив
+        storage.retention = Retention::LastDays(2);
+        assert_eq!(storage.archive_history().unwrap(), 2);
+        clock.advance(1);
+        storage.withdraw(&alice, rub(10)).unwrap();
+
+        // снимок не сохраняли: перенос и снятие восстанавливаются из журнала
+        let (loaded, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts, storage.accounts);
+        assert_eq!(loaded.accounts["Alice"].archived.count, 2);
+        let history = loaded.history(&alice, 0, usize::MAX).unwrap();
+        assert_eq!(history[..6], full[..]);
+        assert_eq!(history[6].kind, OpKind::Withdraw(Amount::new(10)));
+
+        // выписка видит и архивные операции
+        let statement = loaded
+            .statement(&alice, date("2026-01-01"), date("2026-01-01"))
+            .unwrap();
+        assert_eq!(statement.opening, 0);
+        assert_eq!(statement.closing, 100);
+
+        cleanup(file);
+    }
+
+    #[test]
+    fn archive_repairs_torn_tail_and_prefers_latest() {
+        let path = "test_archive_torn.csv.archive";
+        let alice = "Alice".to_string();
+        fs::write(path, "Alice,0,D:1\nAlice,1,D:2\nAlice,0,D:5\nAlice,1,D").unwrap();
+        let archive = Archive::open(path).unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "Alice,0,D:1\nAlice,1,D:2\nAlice,0,D:5\n"
+        );
+        let ops = archive.read(&alice, 0..2).unwrap();
+        assert_eq!(ops[0].kind, OpKind::Deposit(Amount::new(5)));
+        assert_eq!(ops[1].kind, OpKind::Deposit(Amount::new(2)));
+        assert!(archive.read(&alice, 0..3).is_err());
+        assert!(archive.read(&"Bob".to_string(), 0..0).unwrap().is_empty());
+
+        // без подключённого архива архивную часть истории не прочитать
+        let mut balance = Balance::new();
+        balance.archived.count = 1;
+        balance
+            .last_ops
+            .push(OpKind::Deposit(Amount::new(1)).into());
+        assert!(matches!(
+            page(None, &alice, &balance, 0, 2),
+            Err(BankError::Archive(_))
+        ));
+        assert_eq!(page(None, &alice, &balance, 1, 2).unwrap().len(), 1);
+
+        let _ = fs::remove_file(path);
+    }
+}
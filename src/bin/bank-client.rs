@@ -0,0 +1,54 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+fn main() {
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:7878".to_string());
+
+    let stream = match TcpStream::connect(&addr) {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("Не удалось подключиться к {}: {}", addr, e);
+            return;
+        }
+    };
+    let mut writer = stream.try_clone().expect("Не удалось открыть соединение");
+    let mut responses = BufReader::new(stream).lines();
+
+    println!("=== Bank client, сервер {} ===", addr);
+    println!("Команды те же, что у utils; exit — выйти");
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    loop {
+        print!("> ");
+        stdout.flush().unwrap(); // показываем приглашение
+
+        let mut input = String::new();
+        if stdin.lock().read_line(&mut input).unwrap() == 0 {
+            break; // EOF
+        }
+        let command = input.trim();
+        if command.is_empty() {
+            continue;
+        }
+
+        if writeln!(writer, "{}", command).is_err() {
+            eprintln!("Сервер закрыл соединение");
+            break;
+        }
+        match responses.next() {
+            Some(Ok(response)) => println!("{}", response),
+            _ => {
+                eprintln!("Сервер закрыл соединение");
+                break;
+            }
+        }
+        if command == "exit" {
+            break;
+        }
+    }
+}
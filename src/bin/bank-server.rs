@@ -0,0 +1,24 @@
+use bank_system::Retention;
+use bank_system::protocol::handle_client;
+use bank_system::shared::serve;
+use std::env;
+
+fn main() {
+    // Адрес можно передать первым аргументом, по умолчанию слушаем только локальный хост
+    let addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    // Вторым аргументом — сколько истории держать в памяти: all, ops:N или days:N
+    let retention: Retention = match env::args().nth(2).map(|arg| arg.parse()) {
+        None => Retention::default(),
+        Some(Ok(retention)) => retention,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = serve(&addr, retention, "Bank server", handle_client) {
+        eprintln!("{}", e);
+    }
+}
@@ -1,6 +1,7 @@
 use bank_system::Storage;
+use bank_system::report;
 use bank_system::tx_chain;
-use bank_system::{Deposit, Transaction, Transfer, Withdraw};
+use bank_system::{Currency, Deposit, Money, Transaction, Transfer, Withdraw};
 use my_macros::{FromSql, ToSql, say_hello};
 
 #[derive(Debug, ToSql, FromSql)]
@@ -18,16 +19,16 @@ fn main() {
     let tx = tx_chain!(
         Deposit {
             account: "Alice".into(),
-            amount: 500
+            amount: Money::new(500, Currency::RUB)
         },
         Transfer {
             from: "Alice".into(),
             to: "Bob".into(),
-            amount: 50
+            amount: Money::new(50, Currency::RUB)
         },
         Withdraw {
             account: "Alice".into(),
-            amount: 100
+            amount: Money::new(100, Currency::RUB)
         }
     );
 
@@ -47,7 +48,7 @@ fn main() {
     println!("Выполняем транзакции через макрос...");
     match tx.apply(&mut storage) {
         Ok(_) => println!("Успешно"),
-        Err(e) => println!("Ошибка: {:?}", e),
+        Err(e) => println!("Ошибка: {}", report(&e)),
     }
 
     println!("Итоговые балансы:");
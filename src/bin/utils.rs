@@ -1,20 +1,107 @@
 use bank_system::transaction::Withdraw;
-use bank_system::{BalanceManager, Deposit, Name, Storage, Transaction, Transfer};
+use bank_system::{
+    Amount, Bucket, Clock, Currency, Date, DayCount, Deposit, Exchange, FlowStats, InterestEngine,
+    InterestMode, LoadMode, Metric, Money, Name, OpKind, RankBy, RateTable, Retention, RetryPolicy,
+    Schedule, SignedAmount, SignedMoney, Storage, SystemClock, Transaction, Transfer, report,
+};
 use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// Разбирает сумму в основных единицах ("12.50") в валюте счёта `name`;
+/// для ещё не открытого счёта — в базовой валюте
+fn parse_amount(storage: &Storage, name: &str, value: &str) -> Option<Money> {
+    let currency = storage
+        .accounts
+        .get(name)
+        .map(|b| b.currency)
+        .unwrap_or_default();
+    Money::parse_major(value, currency).ok()
+}
+
+/// Печатает обороты: суммы, число операций, средний и медианный размер
+fn print_stats(stats: &FlowStats) {
+    let money = |amount| Money {
+        amount,
+        currency: stats.currency,
+    };
+    let size = |amount: Option<Amount>| amount.map_or("—".to_string(), |a| money(a).to_string());
+    println!("Обороты в {}:", stats.currency);
+    println!("  Зачисления: {} ({})", money(stats.inflow), stats.inflows);
+    println!(
+        "  Списания:   {} ({})",
+        money(stats.outflow),
+        stats.outflows
+    );
+    println!(
+        "  Итог:       {}",
+        SignedMoney {
+            amount: stats.net,
+            currency: stats.currency
+        }
+    );
+    println!(
+        "  Средняя операция: {}, медиана: {}",
+        size(stats.average),
+        size(stats.median)
+    );
+    let counts: Vec<String> = stats
+        .counts
+        .iter()
+        .map(|(kind, count)| format!("{}: {}", kind, count))
+        .collect();
+    println!("  Операций: {} ({})", stats.operations(), counts.join(", "));
+}
 
 fn main() {
-    let mut storage = Storage::load_data("balance.csv");
+    let mut storage = match Storage::load_data("balance.csv", LoadMode::Strict) {
+        Ok((storage, _)) => storage,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    // Курсы для обмена берём из rates.csv рядом с балансами, если он есть
+    if Path::new("rates.csv").exists() {
+        match RateTable::load("rates.csv") {
+            Ok(rates) => storage.rates = rates,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+    }
 
     println!("=== Bank CLI Utils ===");
     println!("Команды:");
-    println!("  add <name> <balance>         - добавить пользователя");
-    println!("  remove <name>                - удалить пользователя");
+    println!("  add <name> <balance> [валюта] - добавить пользователя");
+    println!("  remove <name>                - удалить пользователя без следа");
+    println!("  close <name> [получатель]    - закрыть счёт, переведя остаток получателю");
     println!("  deposit <name> <amount>      - пополнить баланс (транзакция)");
     println!("  withdraw <name> <amount>     - снять со счёта");
     println!("  transfer <from> <to> <amount> - перевод между счетами");
+    println!("  exchange <name> <amount> <из> <в> - обмен валюты по курсам из rates.csv");
+    println!("  limit <name> <amount>        - кредитный лимит счёта");
+    println!("  interest <name> <ставка> [simple|compound] [act/365|act/360|30/360]");
+    println!("                               - годовые проценты по счёту");
+    println!("  accrue                       - начислить проценты по сегодняшний день");
+    println!("  order <from> <to> <amount> <once|every:N|monthly:D> [начало] [повторы:интервал]");
+    println!("                               - платёжное поручение");
+    println!("  cancel <номер>               - отменить платёжное поручение");
+    println!("  due                          - исполнить наступившие поручения");
     println!("  + deposit <name> <amount> transfer <from> <to> <amount>");
     println!("                               - комбинированная транзакция");
     println!("  balance <name>               - показать баланс");
+    println!("  statement <name> <с> <по> [text|csv|json]");
+    println!("                               - выписка по счёту за период");
+    println!("  history <name> [с] [сколько] - история счёта вместе с архивной");
+    println!("  archive <all|ops:N|days:N>   - перенести старую историю в архив");
+    println!("  stats [name]                 - обороты счёта или всего банка");
+    println!("  top <balance|inflow|outflow|net> [N] [валюта]");
+    println!("                               - счета с наибольшим балансом или оборотом");
+    println!("  series <name|all> <day|week|month> <с> <по> [показатель] [окно]");
+    println!("                               - временной ряд остатка и оборотов в CSV");
+    println!("  books                        - оборотно-сальдовая ведомость");
     println!("  exit                         - выйти");
 
     let stdin = io::stdin();
@@ -36,24 +123,32 @@ fn main() {
 
         match args[0] {
             "add" => {
-                if args.len() != 3 {
-                    println!("Пример: add John 100");
+                if args.len() != 3 && args.len() != 4 {
+                    println!("Пример: add John 100 или add John 100 USD");
                     continue;
                 }
                 let name: Name = args[1].to_string();
-                let balance: u64 = match args[2].parse() {
+                let currency: Currency = match args.get(3).map(|c| c.parse()) {
+                    None => Currency::default(),
+                    Some(Ok(c)) => c,
+                    Some(Err(e)) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                let balance = match Money::parse_major(args[2], currency) {
                     Ok(b) => b,
                     Err(_) => {
                         println!("Сумма должна быть числом");
                         continue;
                     }
                 };
-                if storage.add_user(name.clone()).is_some() {
-                    let _ = storage.deposit(&name, balance);
-                    println!("Пользователь {} добавлен с балансом {}", name, balance);
-                    storage.save("balance.csv");
-                } else {
-                    println!("Пользователь {} уже существует", name);
+                match storage.create_account(&name, currency, Some(balance)) {
+                    Ok(()) => {
+                        println!("Пользователь {} добавлен с балансом {}", name, balance);
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
                 }
             }
             "remove" => {
@@ -62,11 +157,33 @@ fn main() {
                     continue;
                 }
                 let name = args[1];
-                if storage.remove_user(&name.to_string()).is_some() {
-                    println!("Пользователь {} удалён", name);
-                    storage.save("balance.csv");
-                } else {
-                    println!("Пользователь {} не найден", name);
+                match storage.remove_user(&name.to_string()) {
+                    Ok(_) => {
+                        println!("Пользователь {} удалён", name);
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "close" => {
+                if !(2..=3).contains(&args.len()) {
+                    println!("Пример: close John или close John Alice");
+                    continue;
+                }
+                let name = args[1].to_string();
+                let payout = args.get(2).map(|payout| payout.to_string());
+                match storage.close_account(&name, payout.as_ref()) {
+                    Ok(paid) => {
+                        match payout {
+                            Some(payout) if !paid.amount.is_zero() => println!(
+                                "Счёт {} закрыт, остаток {} переведён {}",
+                                name, paid, payout
+                            ),
+                            _ => println!("Счёт {} закрыт", name),
+                        }
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("{}", report(&e)),
                 }
             }
             "deposit" => {
@@ -75,9 +192,9 @@ fn main() {
                     continue;
                 }
                 let name = args[1].to_string();
-                let amount: u64 = match args[2].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
+                let amount = match parse_amount(&storage, args[1], args[2]) {
+                    Some(a) => a,
+                    None => {
                         println!("Сумма должна быть числом");
                         continue;
                     }
@@ -93,7 +210,7 @@ fn main() {
                         println!("Транзакция: депозит {} на {}", name, amount);
                         storage.save("balance.csv");
                     }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
+                    Err(e) => println!("Ошибка транзакции: {}", report(&e)),
                 }
             }
             "withdraw" => {
@@ -102,9 +219,9 @@ fn main() {
                     continue;
                 }
                 let name = args[1].to_string();
-                let amount: u64 = match args[2].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
+                let amount = match parse_amount(&storage, args[1], args[2]) {
+                    Some(a) => a,
+                    None => {
                         println!("Сумма должна быть числом");
                         continue;
                     }
@@ -123,7 +240,7 @@ fn main() {
                         );
                         storage.save("balance.csv");
                     }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
+                    Err(e) => println!("Ошибка транзакции: {}", report(&e)),
                 }
             }
             "transfer" => {
@@ -133,9 +250,9 @@ fn main() {
                 }
                 let from = args[1].to_string();
                 let to = args[2].to_string();
-                let amount: u64 = match args[3].parse() {
-                    Ok(a) => a,
-                    Err(_) => {
+                let amount = match parse_amount(&storage, args[1], args[3]) {
+                    Some(a) => a,
+                    None => {
                         println!("Сумма должна быть числом");
                         continue;
                     }
@@ -152,9 +269,189 @@ fn main() {
                         println!("Транзакция: перевод {} -> {} на {}", from, to, amount);
                         storage.save("balance.csv");
                     }
-                    Err(e) => println!("Ошибка транзакции: {:?}", e),
+                    Err(e) => println!("Ошибка транзакции: {}", report(&e)),
+                }
+            }
+            "exchange" => {
+                if args.len() != 5 {
+                    println!("Пример: exchange John 100 RUB USD");
+                    continue;
+                }
+                let name = args[1].to_string();
+                let (Ok(from), Ok(to)) = (args[3].parse::<Currency>(), args[4].parse::<Currency>())
+                else {
+                    println!("Валюта — три заглавные буквы, например USD");
+                    continue;
+                };
+                let amount = match Money::parse_major(args[2], from) {
+                    Ok(a) => a,
+                    Err(_) => {
+                        println!("Сумма должна быть числом");
+                        continue;
+                    }
+                };
+
+                let tx = Exchange {
+                    account: name.clone(),
+                    amount,
+                    to,
+                };
+
+                match tx.apply(&mut storage) {
+                    Ok(_) => {
+                        if let Some(OpKind::Exchange { sold, bought, rate }) =
+                            storage.accounts[&name].last_ops.last().map(|op| &op.kind)
+                        {
+                            println!(
+                                "Транзакция: {} обменял {} на {} по курсу {}",
+                                name, sold, bought, rate
+                            );
+                        }
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("Ошибка транзакции: {}", report(&e)),
                 }
             }
+            "limit" => {
+                if args.len() != 3 {
+                    println!("Пример: limit John 500");
+                    continue;
+                }
+                let name: Name = args[1].to_string();
+                let Some(limit) = parse_amount(&storage, &name, args[2]) else {
+                    println!("Сумма должна быть числом");
+                    continue;
+                };
+                match storage.set_credit_limit(&name, limit.amount) {
+                    Ok(_) => {
+                        println!("Кредитный лимит {}: {}", name, limit);
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("{}", report(&e)),
+                }
+            }
+            "interest" => {
+                if !(3..=5).contains(&args.len()) {
+                    println!("Пример: interest John 0.05 compound act/365");
+                    continue;
+                }
+                let name: Name = args[1].to_string();
+                let rate = match args[2].parse() {
+                    Ok(rate) => rate,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                let mode = args
+                    .get(3)
+                    .map_or(Ok(InterestMode::Compound), |m| m.parse());
+                let day_count = args.get(4).map_or(Ok(DayCount::Actual365), |d| d.parse());
+                let (mode, day_count) = match (mode, day_count) {
+                    (Ok(mode), Ok(day_count)) => (mode, day_count),
+                    (Err(e), _) | (_, Err(e)) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+
+                let engine = InterestEngine::new(SystemClock);
+                let terms = engine.terms(rate, mode, day_count);
+                // то, что набежало по старым условиям, начисляем по ним
+                let result = engine
+                    .run(&mut storage)
+                    .and_then(|_| storage.set_interest(&name, Some(terms)));
+                match result {
+                    Ok(_) => {
+                        println!(
+                            "Проценты {}: {} годовых, {}, {}",
+                            name, terms.rate, terms.mode, terms.day_count
+                        );
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("{}", report(&e)),
+                }
+            }
+            "accrue" => match InterestEngine::new(SystemClock).run(&mut storage) {
+                Ok(accrued) => {
+                    if accrued.is_empty() {
+                        println!("Начислять нечего");
+                    }
+                    for (name, money) in accrued {
+                        println!("Начислены проценты {}: {}", name, money);
+                    }
+                    storage.save("balance.csv");
+                }
+                Err(e) => println!("{}", report(&e)),
+            },
+            "order" => {
+                if !(5..=7).contains(&args.len()) {
+                    println!("Пример: order Alice Bob 100 monthly:1 2026-01-01 3:1");
+                    continue;
+                }
+                let Some(amount) = parse_amount(&storage, args[1], args[3]) else {
+                    println!("Сумма должна быть числом");
+                    continue;
+                };
+                let schedule = args[4].parse::<Schedule>();
+                let start = args.get(5).map_or(Ok(SystemClock.today()), |d| d.parse());
+                let retry = args
+                    .get(6)
+                    .map_or(Ok(RetryPolicy::default()), |r| r.parse());
+                let (schedule, start, retry) = match (schedule, start, retry) {
+                    (Ok(schedule), Ok(start), Ok(retry)) => (schedule, start, retry),
+                    (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                let transfer = Transfer {
+                    from: args[1].to_string(),
+                    to: args[2].to_string(),
+                    amount,
+                };
+                match storage.add_order(transfer, schedule, start, retry) {
+                    Ok(id) => {
+                        println!(
+                            "Поручение №{}: {}, первый перевод {}",
+                            id, schedule, storage.orders[&id].due
+                        );
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("{}", report(&e)),
+                }
+            }
+            "cancel" => {
+                let Some(Ok(id)) = args.get(1).map(|id| id.parse()) else {
+                    println!("Пример: cancel 1");
+                    continue;
+                };
+                match storage.cancel_order(id) {
+                    Ok(_) => {
+                        println!("Поручение №{} отменено", id);
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("{}", report(&e)),
+                }
+            }
+            "due" => {
+                let runs = storage.run_due(SystemClock.today());
+                if runs.is_empty() {
+                    println!("Исполнять нечего");
+                }
+                for run in runs {
+                    match run.result {
+                        Ok(_) => println!("Поручение №{} за {} исполнено", run.order, run.date),
+                        Err(e) => println!(
+                            "Поручение №{} за {} не исполнено: {}",
+                            run.order,
+                            run.date,
+                            report(&e)
+                        ),
+                    }
+                }
+                storage.save("balance.csv");
+            }
             "+" => {
                 if args.len() != 8 {
                     println!(
@@ -164,15 +461,23 @@ fn main() {
                     continue;
                 }
 
+                let (Some(deposit_amount), Some(transfer_amount)) = (
+                    parse_amount(&storage, args[2], args[3]),
+                    parse_amount(&storage, args[5], args[7]),
+                ) else {
+                    println!("Сумма должна быть числом");
+                    continue;
+                };
+
                 let deposit = Deposit {
                     account: args[2].to_string(),
-                    amount: args[3].parse().unwrap_or(0),
+                    amount: deposit_amount,
                 };
 
                 let transfer = Transfer {
                     from: args[5].to_string(),
                     to: args[6].to_string(),
-                    amount: args[7].parse().unwrap_or(0),
+                    amount: transfer_amount,
                 };
 
                 let combined_tx = deposit + transfer;
@@ -182,7 +487,7 @@ fn main() {
                         println!("Комбинированная транзакция выполнена!");
                         storage.save("balance.csv");
                     }
-                    Err(e) => println!("Ошибка при выполнении: {:?}", e),
+                    Err(e) => println!("Ошибка при выполнении: {}", report(&e)),
                 }
             }
             "balance" => {
@@ -192,10 +497,214 @@ fn main() {
                 }
                 let name: Name = args[1].to_string();
                 match storage.get_balance(&name) {
-                    Some(b) => println!("Баланс {}: {}", name, b.result),
+                    Some(b) => {
+                        let all: Vec<String> =
+                            b.all_money().iter().map(|m| m.to_string()).collect();
+                        print!("Баланс {}: {}", name, all.join(", "));
+                        if !b.credit_limit.is_zero() {
+                            print!(
+                                " (кредитный лимит {})",
+                                Money {
+                                    amount: b.credit_limit,
+                                    currency: b.currency,
+                                }
+                            );
+                        }
+                        if b.is_closed() {
+                            print!(" (счёт закрыт)");
+                        }
+                        println!();
+                    }
                     None => println!("Пользователь {} не найден", name),
                 }
             }
+            "statement" => {
+                if args.len() != 4 && args.len() != 5 {
+                    println!("Пример: statement John 2026-01-01 2026-01-31 csv");
+                    continue;
+                }
+                let (from, to) = match (args[2].parse::<Date>(), args[3].parse::<Date>()) {
+                    (Ok(from), Ok(to)) => (from, to),
+                    (Err(e), _) | (_, Err(e)) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                match storage.statement(&args[1].to_string(), from, to) {
+                    Ok(statement) => match args.get(4).copied().unwrap_or("text") {
+                        "text" => println!("{}", statement),
+                        "csv" => print!("{}", statement.to_csv()),
+                        "json" => println!("{}", statement.to_json()),
+                        other => println!("Неизвестный формат '{}': text, csv или json", other),
+                    },
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "history" => {
+                if !(2..=4).contains(&args.len()) {
+                    println!("Пример: history John 0 20");
+                    continue;
+                }
+                let offset = args.get(2).map_or(Ok(0), |offset| offset.parse::<u64>());
+                let limit = args.get(3).map_or(Ok(20), |limit| limit.parse::<usize>());
+                let (Ok(offset), Ok(limit)) = (offset, limit) else {
+                    println!("Номер и число операций — целые неотрицательные числа");
+                    continue;
+                };
+                match storage.history(&args[1].to_string(), offset, limit) {
+                    Ok(ops) => {
+                        for (seq, op) in (offset..).zip(&ops) {
+                            println!("{:>6}  {}", seq, op);
+                        }
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "archive" => {
+                if args.len() != 2 {
+                    println!("Пример: archive days:90");
+                    continue;
+                }
+                storage.retention = match args[1].parse::<Retention>() {
+                    Ok(retention) => retention,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                };
+                match storage.archive_history() {
+                    Ok(moved) => {
+                        println!("В архив перенесено операций: {}", moved);
+                        storage.save("balance.csv");
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "stats" => {
+                let stats = match args.as_slice() {
+                    [_] => storage
+                        .bank_stats()
+                        .map(|stats| stats.into_values().collect()),
+                    [_, name] => storage
+                        .account_stats(&name.to_string())
+                        .map(|stats| vec![stats]),
+                    _ => {
+                        println!("Пример: stats John");
+                        continue;
+                    }
+                };
+                match stats {
+                    Ok(stats) => {
+                        for stats in stats {
+                            print_stats(&stats);
+                        }
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "top" => {
+                if !(2..=4).contains(&args.len()) {
+                    println!("Пример: top balance 5 RUB");
+                    continue;
+                }
+                let by = match args[1] {
+                    "balance" => RankBy::Balance,
+                    "inflow" => RankBy::Inflow,
+                    "outflow" => RankBy::Outflow,
+                    "net" => RankBy::Net,
+                    other => {
+                        println!("Неизвестный показатель '{}'", other);
+                        continue;
+                    }
+                };
+                let n = args.get(2).map_or(Ok(10), |n| n.parse::<usize>());
+                let currency = args
+                    .get(3)
+                    .map_or(Ok(Currency::default()), |c| c.parse::<Currency>());
+                let (Ok(n), Ok(currency)) = (n, currency) else {
+                    println!("Пример: top balance 5 RUB");
+                    continue;
+                };
+                match storage.top_accounts(currency, by, n) {
+                    Ok(top) => {
+                        for (place, (name, amount)) in top.iter().enumerate() {
+                            println!(
+                                "{:>3}. {:<16} {}",
+                                place + 1,
+                                name,
+                                SignedMoney {
+                                    amount: *amount,
+                                    currency
+                                }
+                            );
+                        }
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "series" => {
+                if !(5..=7).contains(&args.len()) {
+                    println!("Пример: series John week 2026-01-01 2026-03-31 balance 4");
+                    continue;
+                }
+                let bucket = args[2].parse::<Bucket>();
+                let dates = (args[3].parse::<Date>(), args[4].parse::<Date>());
+                let metric = args
+                    .get(5)
+                    .map_or(Ok(Metric::Balance), |m| m.parse::<Metric>());
+                let (Ok(bucket), (Ok(from), Ok(to)), Ok(metric)) = (bucket, dates, metric) else {
+                    println!("Пример: series John week 2026-01-01 2026-03-31 balance 4");
+                    continue;
+                };
+                let Ok(window) = args.get(6).map_or(Ok(3), |w| w.parse::<usize>()) else {
+                    println!("Окно — целое неотрицательное число периодов");
+                    continue;
+                };
+                let series = match args[1] {
+                    "all" => storage.series(bucket, from, to),
+                    name => storage
+                        .account_series(&name.to_string(), bucket, from, to)
+                        .map(|series| [(series.currency, series)].into()),
+                };
+                match series {
+                    Ok(series) => {
+                        for (currency, series) in &series {
+                            if args[1] == "all" {
+                                println!("# {}", currency);
+                            }
+                            print!("{}", series.to_csv(metric, window));
+                        }
+                    }
+                    Err(e) => println!("Ошибка: {}", report(&e)),
+                }
+            }
+            "books" => {
+                let trial = storage.trial_balance();
+                for ((account, currency), amount) in &trial.balances {
+                    match SignedAmount::new(*amount) {
+                        Some(amount) => println!(
+                            "{:<16} {}",
+                            account,
+                            SignedMoney {
+                                amount,
+                                currency: *currency
+                            }
+                        ),
+                        None => println!("{:<16} {} {}", account, amount, currency),
+                    }
+                }
+                for mismatch in &trial.mismatches {
+                    println!(
+                        "Остаток {} в {} не сходится с историей: {} вместо {}",
+                        mismatch.account, mismatch.currency, mismatch.stored, mismatch.derived
+                    );
+                }
+                if trial.is_balanced() {
+                    println!("Книги сведены");
+                } else {
+                    println!("Книги не сходятся");
+                }
+            }
             "exit" => break,
             _ => println!("Неизвестная команда"),
         }
@@ -0,0 +1,336 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Календарная дата без времени и часового пояса. Хранится числом дней от 1970-01-01,
+/// поэтому даты сравниваются и вычитаются как числа.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(i64);
+
+impl Date {
+    /// `None`, если такого дня нет в календаре
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        // Алгоритм Говарда Хиннанта: год считается с марта, чтобы февраль был последним
+        let year = i64::from(year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Some(Date(era * 146_097 + day_of_era - 719_468))
+    }
+
+    /// Год, месяц и день
+    pub fn ymd(self) -> (i32, u32, u32) {
+        let days = self.0 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        (year as i32, month, day)
+    }
+
+    pub fn add_days(self, days: i64) -> Date {
+        Date(self.0 + days)
+    }
+
+    /// Сколько дней от `self` до `later`; отрицательно, если `later` раньше
+    pub fn days_until(self, later: Date) -> i64 {
+        later.0 - self.0
+    }
+
+    /// День недели: 0 — понедельник, 6 — воскресенье
+    pub fn weekday(self) -> u32 {
+        // 1970-01-01 был четвергом
+        (self.0 + 3).rem_euclid(7) as u32
+    }
+
+    /// Первое число следующего месяца
+    pub fn next_month(self) -> Date {
+        let (year, month, _) = self.ymd();
+        let (year, month) = if month == 12 {
+            (year + 1, 1)
+        } else {
+            (year, month + 1)
+        };
+        Date::from_ymd(year, month, 1).expect("первое число есть в любом месяце")
+    }
+
+    /// День `day` того же месяца; если месяц короче — его последний день
+    pub fn in_month(self, day: u32) -> Date {
+        let (year, month, _) = self.ymd();
+        let day = day.clamp(1, days_in_month(year, month));
+        Date::from_ymd(year, month, day).expect("день ограничен длиной месяца")
+    }
+}
+
+/// Момент времени с точностью до секунды по UTC. Хранится числом секунд
+/// от 1970-01-01T00:00:00Z, как [`Date`] — числом дней.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn from_secs(secs: i64) -> Self {
+        Timestamp(secs)
+    }
+
+    pub fn secs(self) -> i64 {
+        self.0
+    }
+
+    /// Момент `hour:minute:second` дня `date`; `None`, если такого времени нет
+    pub fn at(date: Date, hour: u32, minute: u32, second: u32) -> Option<Timestamp> {
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        let seconds = i64::from(hour * 3600 + minute * 60 + second);
+        Some(Timestamp(date.0 * 86_400 + seconds))
+    }
+
+    /// День, к которому относится момент
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(86_400))
+    }
+
+    pub fn add_secs(self, secs: i64) -> Timestamp {
+        Timestamp(self.0 + secs)
+    }
+}
+
+/// Полночь в начале дня
+impl From<Date> for Timestamp {
+    fn from(date: Date) -> Self {
+        Timestamp(date.0 * 86_400)
+    }
+}
+
+/// Момент в формате ISO 8601: `2026-01-31T09:05:00Z`
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.0.rem_euclid(86_400);
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date(),
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("некорректное время '{}', нужно ГГГГ-ММ-ДДTЧЧ:ММ:ССZ", s);
+        let (date, time) = s.split_once('T').ok_or_else(bad)?;
+        let time = time.strip_suffix('Z').ok_or_else(bad)?;
+        let mut parts = time.splitn(3, ':');
+        let (Some(hour), Some(minute), Some(second)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad());
+        };
+        if [hour, minute, second].iter().any(|part| part.len() != 2) {
+            return Err(bad());
+        }
+        Timestamp::at(
+            date.parse().map_err(|_| bad())?,
+            hour.parse().map_err(|_| bad())?,
+            minute.parse().map_err(|_| bad())?,
+            second.parse().map_err(|_| bad())?,
+        )
+        .ok_or_else(bad)
+    }
+}
+
+fn is_leap(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Дата в формате ISO 8601: `2026-01-31`
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("некорректная дата '{}', нужна ГГГГ-ММ-ДД", s);
+        let mut parts = s.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(bad());
+        }
+        Date::from_ymd(
+            year.parse().map_err(|_| bad())?,
+            month.parse().map_err(|_| bad())?,
+            day.parse().map_err(|_| bad())?,
+        )
+        .ok_or_else(bad)
+    }
+}
+
+/// Источник текущего времени. Всё, что зависит от календаря, получает часы снаружи,
+/// чтобы тесты могли прокручивать время вперёд.
+pub trait Clock {
+    fn now(&self) -> Timestamp;
+
+    fn today(&self) -> Date {
+        self.now().date()
+    }
+}
+
+/// Системные часы, дата по UTC
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        Timestamp(seconds)
+    }
+}
+
+/// Часы, которые идут только вручную. Новые часы и [`ManualClock::set`]
+/// показывают полночь заданного дня.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Timestamp>,
+}
+
+impl ManualClock {
+    pub fn new(today: Date) -> Self {
+        ManualClock {
+            now: Mutex::new(today.into()),
+        }
+    }
+
+    pub fn set(&self, today: Date) {
+        self.set_time(today.into());
+    }
+
+    pub fn set_time(&self, now: Timestamp) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    pub fn advance(&self, days: i64) {
+        self.advance_secs(days * 86_400);
+    }
+
+    pub fn advance_secs(&self, secs: i64) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now = now.add_secs(secs);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_roundtrip() {
+        let epoch = Date::from_ymd(1970, 1, 1).unwrap();
+        assert_eq!(epoch, Date(0));
+        let leap = Date::from_ymd(2024, 2, 29).unwrap();
+        assert_eq!(leap.ymd(), (2024, 2, 29));
+        assert_eq!(leap.add_days(1).to_string(), "2024-03-01");
+        assert_eq!("1969-12-31".parse::<Date>().unwrap(), epoch.add_days(-1));
+        assert_eq!(leap.to_string().parse::<Date>(), Ok(leap));
+        assert_eq!(Date::from_ymd(2023, 2, 29), None);
+        assert!("2024-13-01".parse::<Date>().is_err());
+        assert!("2024-1-01".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn next_month_and_days() {
+        let date: Date = "2025-12-15".parse().unwrap();
+        assert_eq!(date.next_month().to_string(), "2026-01-01");
+        let jan: Date = "2026-01-01".parse().unwrap();
+        assert_eq!(jan.days_until(jan.next_month()), 31);
+        assert_eq!(jan.next_month().days_until(jan), -31);
+        assert_eq!(jan.next_month().in_month(31).to_string(), "2026-02-28");
+        assert_eq!(jan.in_month(31).to_string(), "2026-01-31");
+        // 2026-01-01 — четверг, 2025-12-29 — понедельник
+        assert_eq!(jan.weekday(), 3);
+        assert_eq!(jan.add_days(-3).weekday(), 0);
+        assert_eq!(jan.add_days(3).weekday(), 6);
+        assert_eq!("1969-12-28".parse::<Date>().unwrap().weekday(), 6);
+    }
+
+    #[test]
+    fn manual_clock_advances() {
+        let clock = ManualClock::new("2026-01-01".parse().unwrap());
+        clock.advance(31);
+        assert_eq!(clock.today().to_string(), "2026-02-01");
+        clock.set("2027-01-01".parse().unwrap());
+        let by_ref: &dyn Clock = &&clock;
+        assert_eq!(by_ref.today().to_string(), "2027-01-01");
+        assert!(SystemClock.today() > clock.today().add_days(-365 * 100));
+
+        clock.advance_secs(3_600 + 61);
+        assert_eq!(clock.now().to_string(), "2027-01-01T01:01:01Z");
+        assert_eq!(clock.today().to_string(), "2027-01-01");
+    }
+
+    #[test]
+    fn timestamp_roundtrip() {
+        let date: Date = "1969-12-31".parse().unwrap();
+        let evening = Timestamp::at(date, 23, 59, 30).unwrap();
+        assert_eq!(evening.to_string(), "1969-12-31T23:59:30Z");
+        assert_eq!(evening.date(), date);
+        assert_eq!(evening.add_secs(30), Timestamp::from_secs(0));
+        assert_eq!(evening.to_string().parse::<Timestamp>(), Ok(evening));
+        assert_eq!(Timestamp::from(date).to_string(), "1969-12-31T00:00:00Z");
+        assert_eq!(Timestamp::at(date, 24, 0, 0), None);
+        assert!("2026-01-31T9:05:00Z".parse::<Timestamp>().is_err());
+        assert!("2026-01-31T09:05:00".parse::<Timestamp>().is_err());
+    }
+}
@@ -1,33 +1,448 @@
 use crate::Name;
-use std::fmt;
+use crate::clock::Date;
+use crate::holds::HoldId;
+use crate::money::{Currency, Money, SignedMoney};
+use crate::schedule::OrderId;
+use crate::transaction::TxId;
+use std::{fmt, io};
 
+/// Ошибка операций со счетами — общая для `BalanceManager` и транзакций.
+/// Несёт имя счёта и суммы, чтобы сообщение можно было показать пользователю как есть.
 #[derive(Debug)]
-pub enum BalanceManagerError {
+pub enum BankError {
     UserNotFound(Name),
-    NotEnoughMoney { required: u64, available: u64 },
+    /// Имя счёта пустое или с символами, которые нельзя записать в снимок и журнал
+    BadName(String),
+    /// Счёт с таким именем уже открыт
+    AccountExists(Name),
+    /// У счёта были переводы с другими счетами, поэтому удалить его бесследно нельзя
+    HasTransfers(Name),
+    /// Не хватает денег с учётом кредитной линии: `available` — всё, что можно
+    /// потратить, `credit` — неиспользованный кредит в его составе
+    NotEnoughMoney {
+        account: Name,
+        required: Money,
+        available: Money,
+        credit: Money,
+    },
+    /// Зачисление переполнило бы баланс
+    Overflow {
+        account: Name,
+        balance: SignedMoney,
+        amount: Money,
+    },
+    /// Новый кредитный лимит меньше текущего долга по счёту
+    LimitBelowDebt {
+        account: Name,
+        limit: Money,
+        debt: Money,
+    },
+    /// Валюта операции не совпадает с валютой счёта
+    CurrencyMismatch {
+        account: Name,
+        expected: Currency,
+        found: Currency,
+    },
+    /// Перевод со счёта на него же
+    SelfTransfer(Name),
+    /// Счёт закрыт и операций больше не принимает
+    AccountClosed(Name),
+    /// На закрываемом счёте остались деньги (или долг), а выплачивать их некуда
+    AccountNotEmpty {
+        account: Name,
+        balance: SignedMoney,
+    },
+    /// Нет курса для обмена между валютами
+    NoRate {
+        from: Currency,
+        to: Currency,
+    },
+    /// После пересчёта по курсу и округления не осталось ни одной минимальной единицы
+    ExchangeTooSmall(Money),
+    /// Шаг комбинированной транзакции (нумерация с нуля) завершился ошибкой `source`
+    StepFailed {
+        step: usize,
+        source: Box<BankError>,
+    },
+    /// На счёте уже есть блокировка с таким номером
+    HoldExists {
+        account: Name,
+        hold: HoldId,
+    },
+    /// Блокировки с таким номером нет: она списана, снята или истекла
+    HoldNotFound {
+        account: Name,
+        hold: HoldId,
+    },
+    /// Блокировка истекала бы не позже сегодняшнего дня
+    HoldExpired {
+        account: Name,
+        hold: HoldId,
+        expires: Date,
+    },
+    /// Списать по блокировке больше, чем заблокировано, нельзя
+    HoldExceeded {
+        account: Name,
+        hold: HoldId,
+        held: Money,
+        requested: Money,
+    },
+    /// Нет активного платёжного поручения с таким номером
+    OrderNotFound(OrderId),
+    /// Расписание или политика повторов поручения, которые нельзя исполнять:
+    /// нулевой интервал, день месяца вне 1..=31
+    BadSchedule(String),
+    /// Ключ идемпотентности пустой, слишком длинный или с недопустимыми символами
+    BadIdempotencyKey(String),
+    /// С этим ключом уже проведена другая транзакция `tx`
+    IdempotencyConflict {
+        key: String,
+        tx: TxId,
+    },
+    /// Начало периода позже его конца
+    BadPeriod {
+        from: Date,
+        to: Date,
+    },
+    /// Транзакция изменила счета, которых нет в её [`Transaction::accounts`]:
+    /// это ошибка в самой транзакции, изменения отброшены
+    ///
+    /// [`Transaction::accounts`]: crate::transaction::Transaction::accounts
+    UndeclaredAccounts(Vec<Name>),
+    /// Изменения не удалось записать в журнал, они откачены
+    Journal(io::Error),
+    /// Архив истории не удалось прочитать или дописать
+    Archive(io::Error),
 }
 
-impl fmt::Display for BalanceManagerError {
+impl BankError {
+    /// Привязывает ошибку к шагу цепочки, сдвигая номер уже найденного шага на `offset`
+    pub(crate) fn at_step(self, offset: usize) -> BankError {
+        match self {
+            BankError::StepFailed { step, source } => BankError::StepFailed {
+                step: step + offset,
+                source,
+            },
+            other => BankError::StepFailed {
+                step: offset,
+                source: Box::new(other),
+            },
+        }
+    }
+
+    /// Исходная ошибка без обёрток шагов
+    pub fn root(&self) -> &BankError {
+        match self {
+            BankError::StepFailed { source, .. } => source.root(),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for BankError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            BalanceManagerError::UserNotFound(name) => {
+            BankError::UserNotFound(name) => {
                 write!(f, "Пользователь '{}' не найден", name)
             }
-            BalanceManagerError::NotEnoughMoney {
+            BankError::BadName(name) => write!(f, "Некорректное имя счёта {:?}", name),
+            BankError::AccountExists(name) => {
+                write!(f, "Пользователь '{}' уже существует", name)
+            }
+            BankError::HasTransfers(name) => write!(
+                f,
+                "У '{}' были переводы с другими счетами: удалить его нельзя, только закрыть",
+                name
+            ),
+            BankError::NotEnoughMoney {
+                account,
                 required,
                 available,
+                credit,
+            } => {
+                write!(
+                    f,
+                    "Недостаточно средств у '{}': требуется {}, доступно {}",
+                    account, required, available
+                )?;
+                if !credit.amount.is_zero() {
+                    write!(f, ", из них кредит {}", credit)?;
+                }
+                Ok(())
+            }
+            BankError::Overflow {
+                account,
+                balance,
+                amount,
+            } => {
+                write!(
+                    f,
+                    "Переполнение баланса '{}': {} + {} не помещается в сумму",
+                    account, balance, amount
+                )
+            }
+            BankError::LimitBelowDebt {
+                account,
+                limit,
+                debt,
+            } => {
+                write!(
+                    f,
+                    "Кредитный лимит {} для '{}' меньше текущего долга {}",
+                    limit, account, debt
+                )
+            }
+            BankError::CurrencyMismatch {
+                account,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Валюта операции {} не совпадает с валютой счёта '{}' ({})",
+                    found, account, expected
+                )
+            }
+            BankError::SelfTransfer(name) => {
+                write!(f, "Нельзя перевести деньги со счёта '{}' на него же", name)
+            }
+            BankError::AccountClosed(name) => write!(f, "Счёт '{}' закрыт", name),
+            BankError::AccountNotEmpty { account, balance } => {
+                write!(
+                    f,
+                    "Нельзя закрыть счёт '{}': на нём остаётся {}",
+                    account, balance
+                )
+            }
+            BankError::NoRate { from, to } => {
+                write!(f, "Нет курса обмена {} -> {}", from, to)
+            }
+            BankError::ExchangeTooSmall(amount) => {
+                write!(f, "Сумма {} слишком мала для обмена", amount)
+            }
+            BankError::StepFailed { step, .. } => {
+                write!(f, "Шаг {} транзакции не выполнен", step)
+            }
+            BankError::HoldExists { account, hold } => {
+                write!(f, "У '{}' уже есть блокировка №{}", account, hold)
+            }
+            BankError::HoldNotFound { account, hold } => {
+                write!(f, "У '{}' нет блокировки №{}", account, hold)
+            }
+            BankError::HoldExpired {
+                account,
+                hold,
+                expires,
             } => {
                 write!(
                     f,
-                    "Недостаточно средств: требуется {}, доступно {}",
-                    required, available
+                    "Блокировка №{} у '{}' истекла бы {}, это не позже сегодняшнего дня",
+                    hold, account, expires
+                )
+            }
+            BankError::HoldExceeded {
+                account,
+                hold,
+                held,
+                requested,
+            } => {
+                write!(
+                    f,
+                    "По блокировке №{} у '{}' заблокировано {}, а списывается {}",
+                    hold, account, held, requested
+                )
+            }
+            BankError::OrderNotFound(id) => {
+                write!(f, "Нет активного платёжного поручения №{}", id)
+            }
+            BankError::BadSchedule(schedule) => {
+                write!(f, "Некорректное расписание поручения '{}'", schedule)
+            }
+            BankError::BadIdempotencyKey(key) => {
+                write!(f, "Некорректный ключ идемпотентности '{}'", key)
+            }
+            BankError::IdempotencyConflict { key, tx } => {
+                write!(
+                    f,
+                    "Ключ '{}' уже использован другой транзакцией №{}",
+                    key, tx
                 )
             }
+            BankError::BadPeriod { from, to } => {
+                write!(f, "Начало периода {} позже его конца {}", from, to)
+            }
+            BankError::UndeclaredAccounts(names) => {
+                write!(f, "Транзакция вышла за свои счета: {}", names.join(", "))
+            }
+            BankError::Journal(_) => write!(f, "Не удалось записать журнал"),
+            BankError::Archive(_) => write!(f, "Архив истории недоступен"),
+        }
+    }
+}
+
+impl std::error::Error for BankError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BankError::StepFailed { source, .. } => Some(source.as_ref()),
+            BankError::Journal(err) | BankError::Archive(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BankError {
+    fn from(err: io::Error) -> Self {
+        BankError::Journal(err)
+    }
+}
+
+/// Сообщение об ошибке вместе со всеми причинами: "Шаг 1 транзакции не выполнен: Недостаточно средств..."
+pub fn report(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoneyError {
+    BadCurrency(String),
+    BadAmount(String),
+    CurrencyMismatch { expected: Currency, found: Currency },
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::BadCurrency(code) => write!(f, "Некорректный код валюты '{}'", code),
+            MoneyError::BadAmount(value) => write!(f, "Некорректная сумма '{}'", value),
+            MoneyError::CurrencyMismatch { expected, found } => {
+                write!(f, "Нельзя смешивать валюты {} и {}", expected, found)
+            }
+            MoneyError::Overflow => write!(f, "Сумма вне допустимого диапазона"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// Как поступать с плохими строками при загрузке снимка
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// Любая плохая строка — ошибка загрузки
+    #[default]
+    Strict,
+    /// Плохие строки пропускаются, но возвращаются вызывающему
+    Lenient,
+}
+
+/// Почему строка снимка была отвергнута
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineError {
+    FieldCount { expected: usize, found: usize },
+    EmptyName,
+    DuplicateName(Name),
+    BadBalance(String),
+    BadCurrency(String),
+    BadHoldings(String),
+    BadHolds(String),
+    BadLimit(String),
+    BadInterest(String),
+    BadOp(String),
+    BadRate(String),
+    BadOrder(String),
+    BadKey(String),
+    BadArchived(String),
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineError::FieldCount { expected, found } => {
+                write!(f, "ожидалось полей: {}, найдено {}", expected, found)
+            }
+            LineError::EmptyName => write!(f, "пустое имя пользователя"),
+            LineError::DuplicateName(name) => {
+                write!(f, "пользователь '{}' встречается повторно", name)
+            }
+            LineError::BadBalance(value) => write!(f, "некорректный баланс '{}'", value),
+            LineError::BadCurrency(value) => write!(f, "некорректная валюта '{}'", value),
+            LineError::BadHoldings(value) => {
+                write!(f, "некорректные остатки в валютах '{}'", value)
+            }
+            LineError::BadHolds(value) => write!(f, "некорректные блокировки '{}'", value),
+            LineError::BadLimit(value) => write!(f, "некорректный кредитный лимит '{}'", value),
+            LineError::BadInterest(value) => {
+                write!(f, "некорректные условия процентов '{}'", value)
+            }
+            LineError::BadOp(value) => write!(f, "некорректная операция '{}'", value),
+            LineError::BadRate(reason) => write!(f, "{}", reason),
+            LineError::BadOrder(reason) => write!(f, "{}", reason),
+            LineError::BadKey(reason) => write!(f, "{}", reason),
+            LineError::BadArchived(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Отвергнутая строка снимка (нумерация строк с единицы)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedLine {
+    pub line: usize,
+    pub reason: LineError,
+}
+
+impl fmt::Display for RejectedLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "строка {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// Строгая загрузка нашла плохие строки
+    Rejected(Vec<RejectedLine>),
+    /// Заголовок снимка неизвестной версии
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "Не удалось прочитать данные: {}", err),
+            LoadError::Rejected(lines) => {
+                write!(f, "Файл повреждён, отвергнуто строк: {}", lines.len())?;
+                for line in lines {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+            LoadError::UnsupportedFormat(header) => {
+                write!(f, "Неизвестный формат файла: '{}'", header)
+            }
         }
     }
 }
 
-impl std::error::Error for BalanceManagerError {}
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Rejected(_) | LoadError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -35,25 +450,95 @@ mod tests {
 
     #[test]
     fn user_not_found_display() {
-        let err = BalanceManagerError::UserNotFound("Alice".to_string());
+        let err = BankError::UserNotFound("Alice".to_string());
         assert_eq!(format!("{}", err), "Пользователь 'Alice' не найден");
     }
 
     #[test]
     fn not_enough_money_display() {
-        let err = BalanceManagerError::NotEnoughMoney {
-            required: 100,
-            available: 50,
+        let err = BankError::NotEnoughMoney {
+            account: "Alice".to_string(),
+            required: Money::new(10000, Currency::RUB),
+            available: Money::new(5000, Currency::RUB),
+            credit: Money::new(0, Currency::RUB),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Недостаточно средств у 'Alice': требуется 100.00 RUB, доступно 50.00 RUB"
+        );
+
+        let err = BankError::NotEnoughMoney {
+            account: "Alice".to_string(),
+            required: Money::new(10000, Currency::RUB),
+            available: Money::new(3000, Currency::RUB),
+            credit: Money::new(3000, Currency::RUB),
+        };
+        assert_eq!(
+            format!("{}", err),
+            "Недостаточно средств у 'Alice': требуется 100.00 RUB, доступно 30.00 RUB, из них кредит 30.00 RUB"
+        );
+    }
+
+    #[test]
+    fn overflow_display() {
+        let jpy: Currency = "JPY".parse().unwrap();
+        let err = BankError::Overflow {
+            account: "Bob".to_string(),
+            balance: Money::new(u64::MAX, jpy).into(),
+            amount: Money::new(1, jpy),
         };
         assert_eq!(
             format!("{}", err),
-            "Недостаточно средств: требуется 100, доступно 50"
+            format!(
+                "Переполнение баланса 'Bob': {} JPY + 1 JPY не помещается в сумму",
+                u64::MAX
+            )
+        );
+    }
+
+    #[test]
+    fn step_failed_chains_source() {
+        let err = BankError::UserNotFound("Carl".to_string())
+            .at_step(0)
+            .at_step(2);
+        assert!(matches!(err, BankError::StepFailed { step: 2, .. }));
+        assert!(matches!(err.root(), BankError::UserNotFound(_)));
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(
+            report(&err),
+            "Шаг 2 транзакции не выполнен: Пользователь 'Carl' не найден"
+        );
+
+        let err = BankError::from(io::Error::other("диск полон"));
+        assert_eq!(report(&err), "Не удалось записать журнал: диск полон");
+    }
+
+    #[test]
+    fn load_error_display_lists_lines() {
+        let err = LoadError::Rejected(vec![
+            RejectedLine {
+                line: 2,
+                reason: LineError::BadBalance("abc".to_string()),
+            },
+            RejectedLine {
+                line: 5,
+                reason: LineError::FieldCount {
+                    expected: 2,
+                    found: 1,
+                },
+            },
+        ]);
+        assert_eq!(
+            format!("{}", err),
+            "Файл повреждён, отвергнуто строк: 2\n  \
+             строка 2: некорректный баланс 'abc'\n  \
+             строка 5: ожидалось полей: 2, найдено 1"
         );
     }
 
     #[test]
     fn error_debug() {
-        let err = BalanceManagerError::UserNotFound("Bob".to_string());
+        let err = BankError::UserNotFound("Bob".to_string());
         assert!(format!("{:?}", err).contains("UserNotFound"));
     }
 }
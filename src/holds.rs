@@ -0,0 +1,404 @@
+use crate::Name;
+use crate::clock::{Clock, Date};
+use crate::errors::BankError;
+use crate::money::{Amount, Money};
+use crate::operations::Balance;
+use crate::storage::{Storage, check_available};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Номер блокировки, уникальный в пределах счёта. Его выдаёт тот, кто блокирует
+/// деньги (например, процессинг карт), и по нему же потом списывает или снимает блок.
+pub type HoldId = u64;
+
+/// Заблокированная на счёте сумма в его основной валюте. Блок уменьшает доступный
+/// остаток, но не баланс: деньги уходят со счёта только при списании блока.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Authorization {
+    pub amount: Amount,
+    /// День, с которого блок больше не действует
+    pub expires: Date,
+}
+
+impl Authorization {
+    pub fn is_active(&self, today: Date) -> bool {
+        today < self.expires
+    }
+}
+
+impl Balance {
+    /// Сколько заблокировано на счёте блоками, действующими в день `today`.
+    /// Истёкший блок уже не держит деньги, даже если [`Storage::expire_holds`]
+    /// его ещё не снял.
+    pub fn held(&self, today: Date) -> Amount {
+        self.holds
+            .values()
+            .filter(|hold| hold.is_active(today))
+            .fold(Amount::ZERO, |sum, hold| {
+                sum.checked_add(hold.amount).unwrap_or(Amount::MAX)
+            })
+    }
+
+    /// Блокировки для файлов: `7:500:2026-02-01 8:30:2026-02-03`
+    pub fn format_holds(&self) -> String {
+        let holds: Vec<String> = self
+            .holds
+            .iter()
+            .map(|(id, hold)| format!("{}:{}", id, hold))
+            .collect();
+        holds.join(" ")
+    }
+
+    pub fn parse_holds(s: &str) -> Result<BTreeMap<HoldId, Authorization>, String> {
+        s.split_whitespace()
+            .map(|item| {
+                let bad = || format!("плохая блокировка '{}'", item);
+                let (id, hold) = item.split_once(':').ok_or_else(bad)?;
+                Ok((id.parse().map_err(|_| bad())?, hold.parse()?))
+            })
+            .collect()
+    }
+}
+
+/// `500:2026-02-01`
+impl fmt::Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.amount, self.expires)
+    }
+}
+
+impl FromStr for Authorization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("плохая блокировка '{}'", s);
+        let (amount, expires) = s.split_once(':').ok_or_else(bad)?;
+        Ok(Authorization {
+            amount: amount.parse().map_err(|_| bad())?,
+            expires: expires.parse().map_err(|_| bad())?,
+        })
+    }
+}
+
+impl Storage {
+    /// Блокирует `amount` на счёте до дня `expires`, который должен быть позже
+    /// сегодняшнего. Денег с учётом кредита и уже заблокированного должно хватать
+    /// на всю сумму.
+    pub fn place_hold(
+        &mut self,
+        name: &Name,
+        id: HoldId,
+        amount: Money,
+        expires: Date,
+    ) -> Result<(), BankError> {
+        let today = self.clock.today();
+        let balance = self.account_in(name, amount.currency)?;
+        if balance.holds.contains_key(&id) {
+            return Err(BankError::HoldExists {
+                account: name.clone(),
+                hold: id,
+            });
+        }
+        if expires <= today {
+            return Err(BankError::HoldExpired {
+                account: name.clone(),
+                hold: id,
+                expires,
+            });
+        }
+        check_available(name, balance, amount, today)?;
+        balance.holds.insert(
+            id,
+            Authorization {
+                amount: amount.amount,
+                expires,
+            },
+        );
+        Ok(())
+    }
+
+    /// Списывает по блоку `amount` — всю заблокированную сумму или её часть.
+    /// Остаток блока снимается: повторно списать по тому же номеру нельзя.
+    /// По истёкшему блоку списать нельзя, даже если он ещё не снят.
+    /// Как и у `debit`, атомарность — забота вызывающего.
+    pub fn capture_hold(
+        &mut self,
+        name: &Name,
+        id: HoldId,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        let today = self.clock.today();
+        let balance = self.account_in(name, amount.currency)?;
+        let hold = take_hold(name, balance, id, today)?;
+        if amount.amount > hold.amount {
+            balance.holds.insert(id, hold);
+            return Err(BankError::HoldExceeded {
+                account: name.clone(),
+                hold: id,
+                held: Money {
+                    amount: hold.amount,
+                    currency: amount.currency,
+                },
+                requested: amount,
+            });
+        }
+        self.debit(name, amount)
+    }
+
+    /// Снимает действующий блок, ничего не списывая
+    pub fn release_hold(&mut self, name: &Name, id: HoldId) -> Result<(), BankError> {
+        let today = self.clock.today();
+        let balance = self.open_account(name)?;
+        take_hold(name, balance, id, today).map(|_| ())
+    }
+
+    /// Снимает все блоки, срок которых истёк к `today`.
+    /// Возвращает снятые блоки в порядке имён и номеров.
+    pub fn expire_holds(&mut self, today: Date) -> Result<Vec<(Name, HoldId, Money)>, BankError> {
+        self.atomic(|storage| {
+            let names: Vec<Name> = storage
+                .accounts
+                .iter()
+                .filter(|(_, balance)| balance.holds.values().any(|hold| !hold.is_active(today)))
+                .map(|(name, _)| name.clone())
+                .collect();
+            let mut expired = Vec::new();
+            for name in names {
+                let balance = storage.account_mut(&name).expect("имя взято из счетов");
+                let currency = balance.currency;
+                balance.holds.retain(|id, hold| {
+                    if hold.is_active(today) {
+                        return true;
+                    }
+                    expired.push((
+                        name.clone(),
+                        *id,
+                        Money {
+                            amount: hold.amount,
+                            currency,
+                        },
+                    ));
+                    false
+                });
+            }
+            expired.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+            Ok(expired)
+        })
+    }
+}
+
+/// Забирает со счёта блок `id`, действующий в день `today`; истёкший блок остаётся
+/// до [`Storage::expire_holds`]
+fn take_hold(
+    name: &Name,
+    balance: &mut Balance,
+    id: HoldId,
+    today: Date,
+) -> Result<Authorization, BankError> {
+    if !balance
+        .holds
+        .get(&id)
+        .is_some_and(|hold| hold.is_active(today))
+    {
+        return Err(BankError::HoldNotFound {
+            account: name.clone(),
+            hold: id,
+        });
+    }
+    Ok(balance.holds.remove(&id).expect("блок только что найден"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::errors::LoadMode;
+    use crate::journal::Journal;
+    use crate::storage::BalanceManager;
+    use crate::testing::{date, rub};
+    use crate::transaction::{Capture, Hold, Release, Transaction, Withdraw};
+    use std::fs;
+    use std::sync::Arc;
+
+    /// День, по часам хранилища которого идут тесты: блоки в них истекают 2026-02-01
+    fn today() -> Date {
+        date("2026-01-15")
+    }
+
+    fn storage_with_alice(amount: u64) -> Storage {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new(today()));
+        storage.add_user("Alice".to_string());
+        storage.credit(&"Alice".to_string(), rub(amount)).unwrap();
+        storage
+    }
+
+    fn hold(id: HoldId, amount: u64) -> Hold {
+        Hold {
+            account: "Alice".to_string(),
+            hold: id,
+            amount: rub(amount),
+            expires: date("2026-02-01"),
+        }
+    }
+
+    #[test]
+    fn hold_reduces_available_not_ledger() {
+        let mut storage = storage_with_alice(100);
+        hold(1, 70).apply(&mut storage).unwrap();
+
+        let alice = &storage.accounts["Alice"];
+        assert_eq!(alice.money(), rub(100));
+        assert_eq!(alice.held(today()), Amount::new(70));
+        assert_eq!(alice.available(today()), rub(30));
+        assert_eq!(alice.last_ops.len(), 1);
+
+        let withdraw = Withdraw {
+            account: "Alice".to_string(),
+            amount: rub(40),
+        };
+        assert!(matches!(
+            withdraw.apply(&mut storage),
+            Err(BankError::NotEnoughMoney { .. })
+        ));
+        assert!(matches!(
+            hold(2, 31).apply(&mut storage),
+            Err(BankError::NotEnoughMoney { .. })
+        ));
+        assert!(matches!(
+            hold(1, 1).apply(&mut storage),
+            Err(BankError::HoldExists { hold: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn capture_settles_part_and_releases_rest() {
+        let mut storage = storage_with_alice(100);
+        hold(1, 70).apply(&mut storage).unwrap();
+        hold(2, 30).apply(&mut storage).unwrap();
+
+        let capture = |id, amount| Capture {
+            account: "Alice".to_string(),
+            hold: id,
+            amount: rub(amount),
+        };
+        assert!(matches!(
+            capture(1, 71).apply(&mut storage),
+            Err(BankError::HoldExceeded { .. })
+        ));
+        // неудачное списание блок не снимает
+        assert_eq!(storage.accounts["Alice"].held(today()), Amount::new(100));
+
+        capture(1, 50).apply(&mut storage).unwrap();
+        let alice = &storage.accounts["Alice"];
+        assert_eq!(alice.money(), rub(50));
+        assert_eq!(alice.available(today()), rub(20));
+        assert!(matches!(
+            capture(1, 10).apply(&mut storage),
+            Err(BankError::HoldNotFound { hold: 1, .. })
+        ));
+
+        let release = Release {
+            account: "Alice".to_string(),
+            hold: 2,
+        };
+        release.apply(&mut storage).unwrap();
+        assert_eq!(storage.accounts["Alice"].available(today()), rub(50));
+        assert!(release.apply(&mut storage).is_err());
+    }
+
+    #[test]
+    fn holds_expire() {
+        let mut storage = storage_with_alice(100);
+        hold(1, 10).apply(&mut storage).unwrap();
+        Hold {
+            expires: date("2026-03-01"),
+            ..hold(2, 20)
+        }
+        .apply(&mut storage)
+        .unwrap();
+
+        assert!(storage.expire_holds(date("2026-01-31")).unwrap().is_empty());
+        let expired = storage.expire_holds(date("2026-02-01")).unwrap();
+        assert_eq!(expired, vec![("Alice".to_string(), 1, rub(10))]);
+        assert_eq!(storage.accounts["Alice"].available(today()), rub(80));
+    }
+
+    #[test]
+    fn expired_holds_do_not_hold_money() {
+        let clock = Arc::new(ManualClock::new(today()));
+        let mut storage = storage_with_alice(100);
+        storage.clock = clock.clone();
+        hold(1, 70).apply(&mut storage).unwrap();
+        assert!(matches!(
+            Hold {
+                expires: today(),
+                ..hold(2, 10)
+            }
+            .apply(&mut storage),
+            Err(BankError::HoldExpired { hold: 2, .. })
+        ));
+
+        // блок истёк, но ещё не снят `expire_holds`
+        clock.set(date("2026-02-01"));
+        assert_eq!(storage.accounts["Alice"].held(clock.today()), Amount::ZERO);
+        assert_eq!(storage.accounts["Alice"].available(clock.today()), rub(100));
+        let capture = Capture {
+            account: "Alice".to_string(),
+            hold: 1,
+            amount: rub(10),
+        };
+        assert!(matches!(
+            capture.apply(&mut storage),
+            Err(BankError::HoldNotFound { hold: 1, .. })
+        ));
+        Withdraw {
+            account: "Alice".to_string(),
+            amount: rub(100),
+        }
+        .apply(&mut storage)
+        .unwrap();
+        let expired = storage.expire_holds(clock.today()).unwrap();
+        assert_eq!(expired, vec![("Alice".to_string(), 1, rub(70))]);
+    }
+
+    #[test]
+    fn holds_survive_save_and_journal_replay() {
+        let file = "holds_roundtrip.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.clock = Arc::new(ManualClock::new(today()));
+        storage.add_user("Alice".to_string());
+        storage.deposit(&"Alice".to_string(), rub(100)).unwrap();
+        hold(7, 40).apply(&mut storage).unwrap();
+
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(
+            restored.accounts["Alice"].holds,
+            storage.accounts["Alice"].holds
+        );
+        restored.save(file);
+        assert!(
+            fs::read_to_string(file)
+                .unwrap()
+                .contains("Alice,RUB,100,0,,,7:40:2026-02-01,,D:100#1")
+        );
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts["Alice"].available(today()), rub(60));
+
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn holds_roundtrip() {
+        let mut balance = Balance::new();
+        balance.holds.insert(7, "500:2026-02-01".parse().unwrap());
+        balance.holds.insert(8, "30:2026-02-03".parse().unwrap());
+        let line = balance.format_holds();
+        assert_eq!(line, "7:500:2026-02-01 8:30:2026-02-03");
+        assert_eq!(Balance::parse_holds(&line).unwrap(), balance.holds);
+        assert!(Balance::parse_holds("7:500").is_err());
+        assert!(Balance::parse_holds("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1104 @@
+use crate::Name;
+use crate::clock::Date;
+use crate::errors::{BankError, report};
+use crate::holds::HoldId;
+use crate::json::Json;
+use crate::money::{Currency, Money};
+use crate::operations::{Balance, OpKind, Operation};
+use crate::shared::SharedStorage;
+use crate::transaction::{
+    Capture, Deposit, Exchange, Hold, Release, Transaction, Transfer, TxCombinator, Withdraw,
+};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+/// Запросы больше этого размера не читаем
+const MAX_BODY: usize = 1 << 20;
+
+/// Разобранный HTTP-запрос: нам нужны только метод, путь, тело
+/// и заголовок `Idempotency-Key`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+    pub idempotency_key: Option<String>,
+}
+
+/// Ответ API: статус и JSON-тело
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Self {
+        Response { status: 200, body }
+    }
+}
+
+/// Ошибка API, которая отдаётся клиенту как `{"error": {...}}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: u16,
+    pub code: &'static str,
+    pub message: String,
+    /// Номер упавшего шага комбинированной транзакции
+    pub step: Option<usize>,
+}
+
+impl ApiError {
+    fn new(status: u16, code: &'static str, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.to_string(),
+            step: None,
+        }
+    }
+
+    fn bad_request(message: impl ToString) -> Self {
+        ApiError::new(400, "bad_request", message)
+    }
+
+    fn into_response(self) -> Response {
+        let mut error = vec![
+            ("code".to_string(), Json::string(self.code)),
+            ("message".to_string(), Json::String(self.message)),
+        ];
+        if let Some(step) = self.step {
+            error.push(("step".to_string(), Json::Number(step.to_string())));
+        }
+        Response {
+            status: self.status,
+            body: Json::Object(vec![("error".to_string(), Json::Object(error))]),
+        }
+    }
+}
+
+impl From<BankError> for ApiError {
+    fn from(err: BankError) -> Self {
+        let (status, code) = match err.root() {
+            BankError::UserNotFound(_) => (404, "account_not_found"),
+            BankError::BadName(_) => (400, "bad_name"),
+            BankError::AccountExists(_) => (409, "account_exists"),
+            BankError::HasTransfers(_) => (409, "has_transfers"),
+            BankError::NotEnoughMoney { .. } => (409, "insufficient_funds"),
+            BankError::Overflow { .. } => (409, "overflow"),
+            BankError::CurrencyMismatch { .. } => (422, "currency_mismatch"),
+            BankError::SelfTransfer(_) => (422, "self_transfer"),
+            BankError::AccountClosed(_) => (409, "account_closed"),
+            BankError::AccountNotEmpty { .. } => (409, "account_not_empty"),
+            BankError::LimitBelowDebt { .. } => (409, "limit_below_debt"),
+            BankError::NoRate { .. } => (422, "no_rate"),
+            BankError::ExchangeTooSmall(_) => (422, "amount_too_small"),
+            BankError::HoldExists { .. } => (409, "hold_exists"),
+            BankError::HoldNotFound { .. } => (404, "hold_not_found"),
+            BankError::HoldExpired { .. } => (400, "hold_expired"),
+            BankError::HoldExceeded { .. } => (422, "hold_exceeded"),
+            BankError::OrderNotFound(_) => (404, "order_not_found"),
+            BankError::BadIdempotencyKey(_) => (400, "bad_idempotency_key"),
+            BankError::IdempotencyConflict { .. } => (409, "idempotency_conflict"),
+            BankError::BadPeriod { .. } => (400, "bad_period"),
+            BankError::BadSchedule(_) => (400, "bad_schedule"),
+            BankError::UndeclaredAccounts(_) => (500, "undeclared_accounts"),
+            BankError::Journal(_) => (500, "journal"),
+            BankError::Archive(_) => (500, "archive"),
+            BankError::StepFailed { .. } => unreachable!("root() снимает обёртки шагов"),
+        };
+        let step = match &err {
+            BankError::StepFailed { step, .. } => Some(*step),
+            _ => None,
+        };
+        ApiError {
+            step,
+            ..ApiError::new(status, code, report(&err))
+        }
+    }
+}
+
+/// Маршрутизация запросов:
+/// - `GET /accounts` — все счета;
+/// - `GET /accounts/{name}` — баланс и история счёта;
+/// - `POST /accounts` — `{"name": "...", "currency": "USD", "balance": "10.50"}`,
+///   валюта и начальный баланс необязательны;
+/// - `DELETE /accounts/{name}` — закрыть пустой счёт;
+/// - `POST /accounts/{name}/close` — `{"payout_to": "..."}`, закрыть счёт, переведя
+///   остаток на другой счёт; без получателя — как `DELETE`;
+/// - `GET /accounts/{name}/statement?from=2026-01-01&to=2026-01-31` — выписка
+///   за период, последний день включительно;
+/// - `GET /accounts/{name}/history?offset=0&limit=50` — страница полной истории
+///   счёта вместе с архивной, от самых старых операций; по умолчанию первые 50;
+/// - `PUT /accounts/{name}/credit-limit` — `{"limit": "500.00"}`, кредитная линия
+///   в валюте счёта;
+/// - `POST /transactions` — транзакция или массив транзакций, которые
+///   выполняются атомарно как [`TxCombinator`]; в ответе номер транзакции `tx`.
+///   С заголовком `Idempotency-Key` повтор того же запроса не проводит транзакцию
+///   ещё раз, а возвращает тот же ответ. Кроме переводов, это блокировки:
+///   `{"type": "hold", "account": "...", "hold": 7, "amount": "5", "expires": "2026-02-01"}`,
+///   `{"type": "capture", "account": "...", "hold": 7, "amount": "3"}` и
+///   `{"type": "release", "account": "...", "hold": 7}`.
+pub fn handle(request: &Request, storage: &SharedStorage) -> Response {
+    route(request, storage).unwrap_or_else(ApiError::into_response)
+}
+
+fn route(request: &Request, storage: &SharedStorage) -> Result<Response, ApiError> {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let method = request.method.as_str();
+
+    match segments.as_slice() {
+        ["accounts"] => match method {
+            "GET" => Ok(list_accounts(storage)),
+            "POST" => create_account(&parse_body(request)?, storage),
+            _ => Err(method_not_allowed()),
+        },
+        ["accounts", name] => {
+            let name = percent_decode(name)
+                .ok_or_else(|| ApiError::bad_request("Некорректное имя в пути"))?;
+            match method {
+                "GET" => storage
+                    .get_balance(&name)
+                    .map(|balance| Response::ok(account_json(&name, &balance, storage.today())))
+                    .ok_or_else(|| not_found(&name)),
+                "DELETE" => close_account(&name, None, storage),
+                _ => Err(method_not_allowed()),
+            }
+        }
+        ["accounts", name, "close"] => {
+            let name = percent_decode(name)
+                .ok_or_else(|| ApiError::bad_request("Некорректное имя в пути"))?;
+            match method {
+                "POST" => {
+                    let body = parse_body(request)?;
+                    let payout = match body.get("payout_to") {
+                        None | Some(Json::Null) => None,
+                        Some(value) => {
+                            Some(value.as_str().map(str::to_string).ok_or_else(|| {
+                                ApiError::bad_request("Поле 'payout_to' должно быть строкой")
+                            })?)
+                        }
+                    };
+                    close_account(&name, payout.as_ref(), storage)
+                }
+                _ => Err(method_not_allowed()),
+            }
+        }
+        ["accounts", name, "statement"] => {
+            let name = percent_decode(name)
+                .ok_or_else(|| ApiError::bad_request("Некорректное имя в пути"))?;
+            match method {
+                "GET" => {
+                    let statement = storage.statement(
+                        &name,
+                        date_param(request, "from")?,
+                        date_param(request, "to")?,
+                    )?;
+                    Ok(Response::ok(statement.to_json()))
+                }
+                _ => Err(method_not_allowed()),
+            }
+        }
+        ["accounts", name, "history"] => {
+            let name = percent_decode(name)
+                .ok_or_else(|| ApiError::bad_request("Некорректное имя в пути"))?;
+            match method {
+                "GET" => history_page(request, &name, storage),
+                _ => Err(method_not_allowed()),
+            }
+        }
+        ["accounts", name, "credit-limit"] => {
+            let name = percent_decode(name)
+                .ok_or_else(|| ApiError::bad_request("Некорректное имя в пути"))?;
+            match method {
+                "PUT" => {
+                    let balance = storage.get_balance(&name).ok_or_else(|| not_found(&name))?;
+                    let limit = money_field(&parse_body(request)?, "limit", balance.currency)?;
+                    if limit.currency != balance.currency {
+                        return Err(BankError::CurrencyMismatch {
+                            account: name,
+                            expected: balance.currency,
+                            found: limit.currency,
+                        }
+                        .into());
+                    }
+                    storage.set_credit_limit(&name, limit.amount)?;
+                    let balance = storage.get_balance(&name).unwrap_or_default();
+                    Ok(Response::ok(account_json(&name, &balance, storage.today())))
+                }
+                _ => Err(method_not_allowed()),
+            }
+        }
+        ["transactions"] => match method {
+            "POST" => {
+                let tx = parse_transaction(&parse_body(request)?, storage)?;
+                let id = storage.submit(&tx, request.idempotency_key.as_deref())?;
+                Ok(Response::ok(Json::object([
+                    ("tx", Json::Number(id.to_string())),
+                    ("steps", Json::Number(tx.steps().to_string())),
+                ])))
+            }
+            _ => Err(method_not_allowed()),
+        },
+        _ => Err(ApiError::new(404, "not_found", "Нет такого ресурса")),
+    }
+}
+
+fn close_account(
+    name: &Name,
+    payout: Option<&Name>,
+    storage: &SharedStorage,
+) -> Result<Response, ApiError> {
+    storage.close_account(name, payout)?;
+    let balance = storage.get_balance(name).ok_or_else(|| not_found(name))?;
+    Ok(Response::ok(account_json(name, &balance, storage.today())))
+}
+
+fn not_found(name: &Name) -> ApiError {
+    ApiError::from(BankError::UserNotFound(name.clone()))
+}
+
+fn method_not_allowed() -> ApiError {
+    ApiError::new(405, "method_not_allowed", "Метод не поддерживается")
+}
+
+fn parse_body(request: &Request) -> Result<Json, ApiError> {
+    Json::parse(&request.body).map_err(ApiError::bad_request)
+}
+
+fn list_accounts(storage: &SharedStorage) -> Response {
+    let mut accounts: Vec<(Name, Balance)> = storage.snapshot().accounts.into_iter().collect();
+    accounts.sort_by(|a, b| a.0.cmp(&b.0));
+    let today = storage.today();
+    Response::ok(Json::Array(
+        accounts
+            .iter()
+            .map(|(name, balance)| account_json(name, balance, today))
+            .collect(),
+    ))
+}
+
+fn create_account(body: &Json, storage: &SharedStorage) -> Result<Response, ApiError> {
+    let name = required_str(body, "name")?.to_string();
+    let currency = match body.get("currency") {
+        None => Currency::default(),
+        Some(value) => value
+            .as_str()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| ApiError::bad_request("Поле 'currency' — код валюты"))?,
+    };
+    let initial = match body.get("balance") {
+        None => None,
+        Some(_) => Some(money_field(body, "balance", currency)?),
+    };
+
+    storage.create_account(&name, currency, initial)?;
+
+    let balance = storage.get_balance(&name).unwrap_or_default();
+    Ok(Response {
+        status: 201,
+        body: account_json(&name, &balance, storage.today()),
+    })
+}
+
+/// Транзакция из JSON: объект `{"type": "deposit" | "withdraw" | "transfer" | "exchange", ...}`
+/// с необязательным назначением платежа `"memo"` или непустой массив таких объектов
+fn parse_transaction(
+    body: &Json,
+    storage: &SharedStorage,
+) -> Result<Box<dyn Transaction>, ApiError> {
+    if let Json::Array(items) = body {
+        let mut txs = items.iter().map(|item| parse_transaction(item, storage));
+        let first = txs
+            .next()
+            .ok_or_else(|| ApiError::bad_request("Пустой список транзакций"))??;
+        return txs.try_fold(first, |t1, t2| {
+            Ok(Box::new(TxCombinator { t1, t2: t2? }) as Box<dyn Transaction>)
+        });
+    }
+
+    // Сумма без валюты читается в валюте счёта, с которого идут деньги
+    let account_currency = |name: &str| {
+        storage
+            .get_balance(&name.to_string())
+            .map(|b| b.currency)
+            .unwrap_or_default()
+    };
+    let tx: Box<dyn Transaction> = match required_str(body, "type")? {
+        "deposit" => {
+            let account = required_str(body, "account")?;
+            Box::new(Deposit {
+                amount: money_field(body, "amount", account_currency(account))?,
+                account: account.to_string(),
+            })
+        }
+        "withdraw" => {
+            let account = required_str(body, "account")?;
+            Box::new(Withdraw {
+                amount: money_field(body, "amount", account_currency(account))?,
+                account: account.to_string(),
+            })
+        }
+        "transfer" => {
+            let from = required_str(body, "from")?;
+            Box::new(Transfer {
+                amount: money_field(body, "amount", account_currency(from))?,
+                from: from.to_string(),
+                to: required_str(body, "to")?.to_string(),
+            })
+        }
+        "exchange" => {
+            let account = required_str(body, "account")?;
+            let to = required_str(body, "to")?;
+            Box::new(Exchange {
+                amount: money_field(body, "amount", account_currency(account))?,
+                account: account.to_string(),
+                to: to
+                    .parse()
+                    .map_err(|_| ApiError::bad_request("Поле 'to' — код валюты"))?,
+            })
+        }
+        "hold" => {
+            let account = required_str(body, "account")?;
+            let expires = required_str(body, "expires")?;
+            Box::new(Hold {
+                amount: money_field(body, "amount", account_currency(account))?,
+                hold: hold_field(body)?,
+                expires: expires
+                    .parse()
+                    .map_err(|e| ApiError::bad_request(format!("Поле 'expires': {}", e)))?,
+                account: account.to_string(),
+            })
+        }
+        "capture" => {
+            let account = required_str(body, "account")?;
+            Box::new(Capture {
+                amount: money_field(body, "amount", account_currency(account))?,
+                hold: hold_field(body)?,
+                account: account.to_string(),
+            })
+        }
+        "release" => Box::new(Release {
+            account: required_str(body, "account")?.to_string(),
+            hold: hold_field(body)?,
+        }),
+        other => {
+            return Err(ApiError::bad_request(format!(
+                "Неизвестный тип транзакции '{}'",
+                other
+            )));
+        }
+    };
+    match body.get("memo") {
+        None => Ok(tx),
+        Some(memo) => {
+            let memo = memo
+                .as_str()
+                .ok_or_else(|| ApiError::bad_request("Поле 'memo' — строка"))?;
+            Ok(Box::new(tx.with_memo(memo)))
+        }
+    }
+}
+
+fn required_str<'a>(body: &'a Json, field: &str) -> Result<&'a str, ApiError> {
+    body.get(field)
+        .and_then(Json::as_str)
+        .ok_or_else(|| ApiError::bad_request(format!("Нужно строковое поле '{}'", field)))
+}
+
+/// Номер блокировки — целое число в поле "hold"
+fn hold_field(body: &Json) -> Result<HoldId, ApiError> {
+    body.get("hold")
+        .and_then(Json::as_u64)
+        .ok_or_else(|| ApiError::bad_request("Нужно числовое поле 'hold'"))
+}
+
+/// Сумма в основных единицах: "10.50 USD" или "10.50" в валюте `default`
+fn money_field(body: &Json, field: &str, default: Currency) -> Result<Money, ApiError> {
+    let value = required_str(body, field)?;
+    let money = if value.contains(' ') {
+        value.parse()
+    } else {
+        Money::parse_major(value, default)
+    };
+    money.map_err(|e| ApiError::bad_request(format!("Поле '{}': {}", field, e)))
+}
+
+fn account_json(name: &Name, balance: &Balance, today: Date) -> Json {
+    let holdings = balance
+        .all_money()
+        .iter()
+        .skip(1)
+        .map(Json::string)
+        .collect();
+    let ops = balance
+        .last_ops
+        .iter()
+        .map(|op| op_json(op, balance.currency))
+        .collect();
+    let holds = balance
+        .holds
+        .iter()
+        .map(|(id, hold)| {
+            Json::object([
+                ("hold", Json::Number(id.to_string())),
+                (
+                    "amount",
+                    Json::string(Money {
+                        amount: hold.amount,
+                        currency: balance.currency,
+                    }),
+                ),
+                ("expires", Json::string(hold.expires)),
+            ])
+        })
+        .collect();
+    let interest = match &balance.interest {
+        None => Json::Null,
+        Some(terms) => Json::object([
+            ("rate", Json::string(terms.rate)),
+            ("mode", Json::string(terms.mode)),
+            ("day_count", Json::string(terms.day_count)),
+            ("accrued_to", Json::string(terms.accrued_to)),
+        ]),
+    };
+    Json::object([
+        ("name", Json::string(name)),
+        ("currency", Json::string(balance.currency)),
+        ("balance", Json::string(balance.money())),
+        ("closed", Json::Bool(balance.is_closed())),
+        (
+            "credit_limit",
+            Json::string(Money {
+                amount: balance.credit_limit,
+                currency: balance.currency,
+            }),
+        ),
+        ("available", Json::string(balance.available(today))),
+        (
+            "held",
+            Json::string(Money {
+                amount: balance.held(today),
+                currency: balance.currency,
+            }),
+        ),
+        ("interest", interest),
+        ("holdings", Json::Array(holdings)),
+        ("holds", Json::Array(holds)),
+        ("last_ops", Json::Array(ops)),
+    ])
+}
+
+/// Запись истории с реквизитами; у старых записей их нет, и поля пропускаются
+fn op_json(op: &Operation, currency: Currency) -> Json {
+    let mut json = kind_json(&op.kind, currency);
+    let Json::Object(fields) = &mut json else {
+        unreachable!("операция — всегда объект");
+    };
+    let meta = &op.meta;
+    if let Some(tx) = meta.tx {
+        fields.push(("tx".to_string(), Json::Number(tx.to_string())));
+    }
+    if let Some(at) = meta.at {
+        fields.push(("at".to_string(), Json::string(at)));
+    }
+    if let Some(counterparty) = &meta.counterparty {
+        fields.push(("counterparty".to_string(), Json::string(counterparty)));
+    }
+    if let Some(memo) = &meta.memo {
+        fields.push(("memo".to_string(), Json::string(memo)));
+    }
+    json
+}
+
+fn kind_json(op: &OpKind, currency: Currency) -> Json {
+    let money = |amount| Json::string(Money { amount, currency });
+    match op {
+        OpKind::Deposit(amount) => Json::object([
+            ("kind", Json::string(op.name())),
+            ("amount", money(*amount)),
+        ]),
+        OpKind::Withdraw(amount) => Json::object([
+            ("kind", Json::string(op.name())),
+            ("amount", money(*amount)),
+        ]),
+        OpKind::TransferIn(amount) => Json::object([
+            ("kind", Json::string(op.name())),
+            ("amount", money(*amount)),
+        ]),
+        OpKind::TransferOut(amount) => Json::object([
+            ("kind", Json::string(op.name())),
+            ("amount", money(*amount)),
+        ]),
+        OpKind::Exchange { sold, bought, rate } => Json::object([
+            ("kind", Json::string(op.name())),
+            ("sold", Json::string(sold)),
+            ("bought", Json::string(bought)),
+            ("rate", Json::string(rate)),
+        ]),
+        OpKind::Interest(amount) => Json::object([
+            ("kind", Json::string(op.name())),
+            ("amount", money(*amount)),
+        ]),
+        OpKind::CloseAccount => Json::object([("kind", Json::string(op.name()))]),
+    }
+}
+
+/// Сколько операций истории отдавать, если `limit` не задан
+const HISTORY_PAGE: usize = 50;
+
+fn history_page(
+    request: &Request,
+    name: &Name,
+    storage: &SharedStorage,
+) -> Result<Response, ApiError> {
+    let offset = match query_param(request, "offset") {
+        None => 0,
+        Some(value) => value
+            .parse()
+            .map_err(|_| ApiError::bad_request("Параметр 'offset' должен быть числом"))?,
+    };
+    let limit = match query_param(request, "limit") {
+        None => HISTORY_PAGE,
+        Some(value) => value
+            .parse()
+            .map_err(|_| ApiError::bad_request("Параметр 'limit' должен быть числом"))?,
+    };
+    let balance = storage.get_balance(name).ok_or_else(|| not_found(name))?;
+    let ops = storage
+        .history(name, offset, limit)?
+        .iter()
+        .map(|op| op_json(op, balance.currency))
+        .collect();
+    Ok(Response::ok(Json::object([
+        ("name", Json::string(name)),
+        ("total", Json::Number(balance.history_len().to_string())),
+        ("offset", Json::Number(offset.to_string())),
+        ("ops", Json::Array(ops)),
+    ])))
+}
+
+/// Раскодированное значение параметра запроса `?name=value`
+fn query_param(request: &Request, name: &str) -> Option<String> {
+    let query = request.path.split_once('?').map(|(_, query)| query);
+    query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode(value))
+}
+
+/// Дата из параметра запроса `?from=2026-01-01`
+fn date_param(request: &Request, name: &str) -> Result<Date, ApiError> {
+    let value = query_param(request, name)
+        .ok_or_else(|| ApiError::bad_request(format!("Нужен параметр '{}'", name)))?;
+    value
+        .parse()
+        .map_err(|e| ApiError::bad_request(format!("Параметр '{}': {}", name, e)))
+}
+
+/// Раскодирует `%XX` в сегменте пути
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Читает запрос: строку запроса, заголовки и тело длиной `Content-Length`
+pub fn read_request(reader: &mut impl BufRead) -> io::Result<Request> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(invalid("некорректная строка запроса"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut length = 0;
+    let mut idempotency_key = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("заголовки оборваны"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((key, value)) = header.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if key.eq_ignore_ascii_case("content-length") {
+            length = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid("некорректный Content-Length"))?;
+        } else if key.eq_ignore_ascii_case("idempotency-key") {
+            idempotency_key = Some(value.trim().to_string());
+        }
+    }
+    if length > MAX_BODY {
+        return Err(invalid("слишком большое тело запроса"));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid("тело не в UTF-8"))?;
+    Ok(Request {
+        method,
+        path,
+        body,
+        idempotency_key,
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Обслуживает одно соединение: один запрос — один ответ
+pub fn handle_connection(stream: TcpStream, storage: &SharedStorage) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let response = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => handle(&request, storage),
+        Err(e) => ApiError::bad_request(e).into_response(),
+    };
+    let body = response.body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::storage::{BalanceManager, Storage};
+    use std::io::{Cursor, Read};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.to_string(),
+            idempotency_key: None,
+        }
+    }
+
+    fn error_code(response: &Response) -> Option<&str> {
+        response.body.get("error")?.get("code")?.as_str()
+    }
+
+    #[test]
+    fn accounts_crud() {
+        let storage = SharedStorage::new(Storage::new());
+        let send = |method, path, body| handle(&request(method, path, body), &storage);
+
+        let created = send("POST", "/accounts", r#"{"name":"Alice","balance":"10.50"}"#);
+        assert_eq!(created.status, 201);
+        assert_eq!(
+            created.body.get("balance").and_then(Json::as_str),
+            Some("10.50 RUB")
+        );
+        assert_eq!(
+            error_code(&send("POST", "/accounts", r#"{"name":"Alice"}"#)),
+            Some("account_exists")
+        );
+        assert_eq!(send("POST", "/accounts", "{").status, 400);
+        send(
+            "POST",
+            "/accounts",
+            r#"{"name":"Bob Smith","currency":"USD"}"#,
+        );
+
+        let list = send("GET", "/accounts", "");
+        let Json::Array(accounts) = &list.body else {
+            panic!("Ожидался массив");
+        };
+        assert_eq!(accounts.len(), 2);
+
+        let bob = send("GET", "/accounts/Bob%20Smith", "");
+        assert_eq!(bob.body.get("currency").and_then(Json::as_str), Some("USD"));
+        let closed = send("DELETE", "/accounts/Bob%20Smith", "");
+        assert_eq!(closed.status, 200);
+        assert_eq!(closed.body.get("closed"), Some(&Json::Bool(true)));
+        // закрытый счёт виден, но операций не принимает
+        assert_eq!(send("GET", "/accounts/Bob%20Smith", "").status, 200);
+        assert_eq!(
+            error_code(&send("DELETE", "/accounts/Bob%20Smith", "")),
+            Some("account_closed")
+        );
+        assert_eq!(send("DELETE", "/accounts/Carl", "").status, 404);
+        assert_eq!(
+            error_code(&send(
+                "POST",
+                "/accounts",
+                r#"{"name":"Evil,Name","balance":"1"}"#
+            )),
+            Some("bad_name")
+        );
+        assert_eq!(
+            error_code(&send("POST", "/accounts", r#"{"name":""}"#)),
+            Some("bad_name")
+        );
+        assert_eq!(send("PUT", "/accounts", "").status, 405);
+        assert_eq!(send("GET", "/nowhere", "").status, 404);
+    }
+
+    #[test]
+    fn close_route_pays_out_balance() {
+        let storage = SharedStorage::new(Storage::new());
+        let send = |method, path, body| handle(&request(method, path, body), &storage);
+        send("POST", "/accounts", r#"{"name":"Alice","balance":"10"}"#);
+        send("POST", "/accounts", r#"{"name":"Bob"}"#);
+
+        assert_eq!(
+            error_code(&send("DELETE", "/accounts/Alice", "")),
+            Some("account_not_empty")
+        );
+        let closed = send("POST", "/accounts/Alice/close", r#"{"payout_to":"Bob"}"#);
+        assert_eq!(closed.status, 200);
+        assert_eq!(
+            closed.body.get("balance").and_then(Json::as_str),
+            Some("0.00 RUB")
+        );
+        let bob = send("GET", "/accounts/Bob", "");
+        assert_eq!(
+            bob.body.get("balance").and_then(Json::as_str),
+            Some("10.00 RUB")
+        );
+        let deposit = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"deposit","account":"Alice","amount":"1"}"#,
+        );
+        assert_eq!(error_code(&deposit), Some("account_closed"));
+    }
+
+    #[test]
+    fn hold_capture_release() {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        let storage = SharedStorage::new(storage);
+        let send = |method, path, body| handle(&request(method, path, body), &storage);
+        send("POST", "/accounts", r#"{"name":"Alice","balance":"10"}"#);
+        let field = |name: &str| {
+            send("GET", "/accounts/Alice", "")
+                .body
+                .get(name)
+                .and_then(Json::as_str)
+                .map(str::to_string)
+        };
+
+        let held = send(
+            "POST",
+            "/transactions",
+            r#"[{"type":"hold","account":"Alice","hold":1,"amount":"6","expires":"2026-02-01"},
+                {"type":"hold","account":"Alice","hold":2,"amount":"3","expires":"2026-02-01"}]"#,
+        );
+        assert_eq!(held.status, 200);
+        assert_eq!(field("balance").as_deref(), Some("10.00 RUB"));
+        assert_eq!(field("available").as_deref(), Some("1.00 RUB"));
+        assert_eq!(field("held").as_deref(), Some("9.00 RUB"));
+        let expired = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"hold","account":"Alice","hold":3,"amount":"1","expires":"2026-01-31"}"#,
+        );
+        assert_eq!(error_code(&expired), Some("hold_expired"));
+
+        let capture = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"capture","account":"Alice","hold":1,"amount":"7"}"#,
+        );
+        assert_eq!(error_code(&capture), Some("hold_exceeded"));
+        let capture = send(
+            "POST",
+            "/transactions",
+            r#"[{"type":"capture","account":"Alice","hold":1,"amount":"5"},
+                {"type":"release","account":"Alice","hold":2}]"#,
+        );
+        assert_eq!(capture.status, 200);
+        assert_eq!(field("balance").as_deref(), Some("5.00 RUB"));
+        assert_eq!(field("available").as_deref(), Some("5.00 RUB"));
+        let release = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"release","account":"Alice","hold":2}"#,
+        );
+        assert_eq!(error_code(&release), Some("hold_not_found"));
+        let bad = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"release","account":"Alice","hold":"2"}"#,
+        );
+        assert_eq!(bad.status, 400);
+    }
+
+    #[test]
+    fn credit_limit_route() {
+        let storage = SharedStorage::new(Storage::new());
+        let send = |method, path, body| handle(&request(method, path, body), &storage);
+        send("POST", "/accounts", r#"{"name":"Alice","balance":"10"}"#);
+
+        let limited = send("PUT", "/accounts/Alice/credit-limit", r#"{"limit":"50"}"#);
+        assert_eq!(limited.status, 200);
+        assert_eq!(
+            limited.body.get("available").and_then(Json::as_str),
+            Some("60.00 RUB")
+        );
+        let withdrawn = send(
+            "POST",
+            "/transactions",
+            r#"{"type":"withdraw","account":"Alice","amount":"40"}"#,
+        );
+        assert_eq!(withdrawn.status, 200);
+        let alice = send("GET", "/accounts/Alice", "");
+        assert_eq!(
+            alice.body.get("balance").and_then(Json::as_str),
+            Some("-30.00 RUB")
+        );
+        assert_eq!(
+            error_code(&send(
+                "PUT",
+                "/accounts/Alice/credit-limit",
+                r#"{"limit":"10"}"#
+            )),
+            Some("limit_below_debt")
+        );
+        assert_eq!(
+            error_code(&send(
+                "PUT",
+                "/accounts/Alice/credit-limit",
+                r#"{"limit":"10 USD"}"#
+            )),
+            Some("currency_mismatch")
+        );
+        assert_eq!(
+            send("PUT", "/accounts/Nobody/credit-limit", r#"{"limit":"1"}"#).status,
+            404
+        );
+    }
+
+    #[test]
+    fn statement_for_period() {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        let mut storage = SharedStorage::new(storage);
+        storage.add_user("Alice".to_string());
+        storage
+            .deposit(&"Alice".to_string(), Money::new(500, Currency::RUB))
+            .unwrap();
+        let get = |path| handle(&request("GET", path, ""), &storage);
+
+        let ok = get("/accounts/Alice/statement?from=2026-01-01&to=2026-01-31");
+        assert_eq!(ok.status, 200);
+        assert_eq!(
+            ok.body.get("opening").and_then(Json::as_str),
+            Some("0.00 RUB")
+        );
+        assert_eq!(
+            ok.body.get("closing").and_then(Json::as_str),
+            Some("5.00 RUB")
+        );
+        assert_eq!(
+            ok.body.get("credits").and_then(Json::as_str),
+            Some("5.00 RUB")
+        );
+
+        let reversed = get("/accounts/Alice/statement?from=2026-02-01&to=2026-01-01");
+        assert_eq!(error_code(&reversed), Some("bad_period"));
+        let missing = get("/accounts/Alice/statement?from=2026-01-01");
+        assert_eq!(missing.status, 400);
+        let unknown = get("/accounts/Bob/statement?from=2026-01-01&to=2026-01-31");
+        assert_eq!(unknown.status, 404);
+    }
+
+    #[test]
+    fn history_pages() {
+        let storage = SharedStorage::new(Storage::new());
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        for amount in 1..=3 {
+            storage
+                .apply(&Deposit {
+                    account: alice.clone(),
+                    amount: Money::new(amount, Currency::RUB),
+                })
+                .unwrap();
+        }
+        let get = |path| handle(&request("GET", path, ""), &storage);
+
+        let all = get("/accounts/Alice/history");
+        assert_eq!(all.status, 200);
+        assert_eq!(all.body.get("total").unwrap().to_string(), "3");
+        let page = get("/accounts/Alice/history?offset=1&limit=1");
+        let ops = page.body.get("ops").unwrap().to_string();
+        assert!(ops.contains("0.02 RUB"), "{}", ops);
+        assert!(!ops.contains("0.01 RUB"), "{}", ops);
+
+        assert_eq!(get("/accounts/Alice/history?limit=x").status, 400);
+        assert_eq!(get("/accounts/Bob/history").status, 404);
+    }
+
+    #[test]
+    fn transactions_map_to_combinator() {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        let storage = SharedStorage::new(storage);
+        let send = |body| handle(&request("POST", "/transactions", body), &storage);
+        for name in ["Alice", "Bob"] {
+            storage.add_user(name.to_string());
+        }
+
+        let ok = send(r#"{"type":"deposit","account":"Alice","amount":"5"}"#);
+        assert_eq!(ok.status, 200);
+        assert_eq!(ok.body.get("tx").and_then(Json::as_u64), Some(1));
+
+        let combined = send(
+            r#"[{"type":"deposit","account":"Alice","amount":"1"},
+                {"type":"transfer","from":"Alice","to":"Bob","amount":"2"},
+                {"type":"withdraw","account":"Alice","amount":"100.00 RUB"}]"#,
+        );
+        assert_eq!(combined.status, 409);
+        assert_eq!(error_code(&combined), Some("insufficient_funds"));
+        let step = combined.body.get("error").and_then(|e| e.get("step"));
+        assert_eq!(step.and_then(Json::as_u64), Some(2));
+
+        // вся цепочка откатилась
+        let alice = handle(&request("GET", "/accounts/Alice", ""), &storage);
+        assert_eq!(
+            alice.body.get("balance").and_then(Json::as_str),
+            Some("5.00 RUB")
+        );
+        let ops = alice.body.get("last_ops").unwrap().to_string();
+        assert_eq!(
+            ops,
+            r#"[{"kind":"deposit","amount":"5.00 RUB","tx":1,"at":"2026-01-31T00:00:00Z"}]"#
+        );
+
+        let paid =
+            send(r#"{"type":"transfer","from":"Alice","to":"Bob","amount":"2","memo":"обед"}"#);
+        assert_eq!(paid.status, 200);
+        let bob = handle(&request("GET", "/accounts/Bob", ""), &storage);
+        let op = bob.body.get("last_ops").unwrap().to_string();
+        assert_eq!(
+            op,
+            r#"[{"kind":"transfer_in","amount":"2.00 RUB","tx":3,"at":"2026-01-31T00:00:00Z","counterparty":"Alice","memo":"обед"}]"#
+        );
+        let bad = send(r#"{"type":"deposit","account":"Alice","amount":"1","memo":5}"#);
+        assert_eq!(bad.status, 400);
+
+        let unknown = send(r#"{"type":"transfer","from":"Alice","to":"Bbo","amount":"1"}"#);
+        assert_eq!(error_code(&unknown), Some("account_not_found"));
+        let own = send(r#"{"type":"transfer","from":"Alice","to":"Alice","amount":"1"}"#);
+        assert_eq!(error_code(&own), Some("self_transfer"));
+        assert_eq!(send(r#"{"type":"steal"}"#).status, 400);
+        assert_eq!(send("[]").status, 400);
+        let mismatch = send(r#"{"type":"deposit","account":"Alice","amount":"1 USD"}"#);
+        assert_eq!(error_code(&mismatch), Some("currency_mismatch"));
+    }
+
+    #[test]
+    fn idempotent_transactions() {
+        let storage = SharedStorage::new(Storage::new());
+        storage.add_user("Alice".to_string());
+        let send = |key: &str, body: &str| {
+            let request = Request {
+                idempotency_key: Some(key.to_string()),
+                ..request("POST", "/transactions", body)
+            };
+            handle(&request, &storage)
+        };
+        let deposit = r#"{"type":"deposit","account":"Alice","amount":"5"}"#;
+
+        let first = send("retry-1", deposit);
+        assert_eq!(first.status, 200);
+        assert_eq!(send("retry-1", deposit), first);
+        assert_eq!(
+            storage.get_balance(&"Alice".to_string()).unwrap().result,
+            500
+        );
+
+        let other = send(
+            "retry-1",
+            r#"{"type":"deposit","account":"Alice","amount":"6"}"#,
+        );
+        assert_eq!(error_code(&other), Some("idempotency_conflict"));
+        assert_eq!(
+            error_code(&send("a,b", deposit)),
+            Some("bad_idempotency_key")
+        );
+        let next = send("retry-2", deposit);
+        assert_eq!(next.body.get("tx").and_then(Json::as_u64), Some(2));
+    }
+
+    #[test]
+    fn read_request_with_body() {
+        let raw = "POST /accounts HTTP/1.1\r\nHost: x\r\ncontent-length: 4\r\n\r\n{}\r\n";
+        let request = read_request(&mut Cursor::new(raw)).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/accounts");
+        assert_eq!(request.body, "{}\r\n");
+        assert_eq!(request.idempotency_key, None);
+        let raw = "POST /transactions HTTP/1.1\r\nIdempotency-Key: pay-1\r\n\r\n";
+        let request = read_request(&mut Cursor::new(raw)).unwrap();
+        assert_eq!(request.idempotency_key.as_deref(), Some("pay-1"));
+        assert!(read_request(&mut Cursor::new("GET\r\n\r\n")).is_err());
+    }
+
+    #[test]
+    fn serve_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let storage = SharedStorage::new(Storage::new());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, &storage).unwrap();
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /accounts HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n[]"));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::errors::BankError;
+use crate::operations::{escape, unescape};
+use crate::storage::Storage;
+use crate::transaction::{Transaction, TxId};
+use std::fmt;
+use std::str::FromStr;
+
+/// Самый длинный допустимый ключ идемпотентности
+pub const MAX_KEY_LEN: usize = 128;
+
+/// Транзакция, проведённая с ключом идемпотентности. Хранится, пока существует
+/// хранилище: повтор с тем же ключом и через месяц вернёт тот же номер.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRecord {
+    pub key: String,
+    pub tx: TxId,
+    /// [`Transaction::fingerprint`] проведённой транзакции
+    pub fingerprint: String,
+}
+
+/// Ключ — от 1 до [`MAX_KEY_LEN`] печатных ASCII-символов без `,` и `;`:
+/// он пишется в строки снимка и журнала как есть
+pub fn check_key(key: &str) -> Result<(), BankError> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LEN
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && b != b',' && b != b';');
+    if valid {
+        Ok(())
+    } else {
+        Err(BankError::BadIdempotencyKey(key.to_string()))
+    }
+}
+
+impl Storage {
+    /// Проводит транзакцию и возвращает её номер.
+    ///
+    /// С ключом `key` транзакция проводится не больше одного раза: повторная отправка
+    /// той же транзакции ничего не меняет и возвращает номер первой, а другая
+    /// транзакция с тем же ключом завершается [`BankError::IdempotencyConflict`].
+    /// Ключ запоминается вместе с изменениями счетов, одной записью журнала.
+    /// Неудачная транзакция ключ не занимает: она ничего не провела, и её можно повторить.
+    pub fn submit<T: Transaction + ?Sized>(
+        &mut self,
+        tx: &T,
+        key: Option<&str>,
+    ) -> Result<TxId, BankError> {
+        if let Some(key) = key {
+            check_key(key)?;
+            if let Some(record) = self.keys.get(key) {
+                return replay(record, tx);
+            }
+        }
+        self.atomic(|storage| {
+            tx.apply(storage)?;
+            let id = storage.tx_id();
+            if let Some(key) = key {
+                storage.touch_key(key);
+                storage.keys.insert(
+                    key.to_string(),
+                    KeyRecord {
+                        key: key.to_string(),
+                        tx: id,
+                        fingerprint: tx.fingerprint(),
+                    },
+                );
+            }
+            Ok(id)
+        })
+    }
+}
+
+/// Результат повторной отправки транзакции с уже использованным ключом
+pub(crate) fn replay<T: Transaction + ?Sized>(
+    record: &KeyRecord,
+    tx: &T,
+) -> Result<TxId, BankError> {
+    if record.fingerprint == tx.fingerprint() {
+        Ok(record.tx)
+    } else {
+        Err(BankError::IdempotencyConflict {
+            key: record.key.clone(),
+            tx: record.tx,
+        })
+    }
+}
+
+/// Строка снимка и журнала: `key,tx,fingerprint`; описание кодируется `%XX`,
+/// как реквизиты операций, чтобы не содержать `,`, `;` и переводов строк
+impl fmt::Display for KeyRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.key, self.tx, escape(&self.fingerprint))
+    }
+}
+
+impl FromStr for KeyRecord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("плохой ключ идемпотентности '{}'", s);
+        let mut parts = s.splitn(3, ',');
+        let (Some(key), Some(tx), Some(fingerprint)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(bad());
+        };
+        check_key(key).map_err(|_| bad())?;
+        Ok(KeyRecord {
+            key: key.to_string(),
+            tx: tx.parse().map_err(|_| bad())?,
+            fingerprint: unescape(fingerprint).ok_or_else(bad)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LoadMode;
+    use crate::journal::Journal;
+    use crate::storage::{AccountPolicy, BalanceManager};
+    use crate::testing::rub;
+    use crate::transaction::{Deposit, Transfer, Withdraw};
+    use std::fs;
+
+    fn deposit(amount: u64) -> Deposit {
+        Deposit {
+            account: "Alice".to_string(),
+            amount: rub(amount),
+        }
+    }
+
+    #[test]
+    fn every_transaction_gets_an_id() {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let first = storage.submit(&deposit(100), None).unwrap();
+        let tx = deposit(10)
+            + Transfer {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                amount: rub(30),
+            };
+        let second = storage.submit(&tx, None).unwrap();
+        assert!(second > first);
+
+        // все шаги комбинированной транзакции записаны под одним номером
+        let ids: Vec<Option<TxId>> = storage.accounts["Alice"]
+            .last_ops
+            .iter()
+            .map(|op| op.meta.tx)
+            .collect();
+        assert_eq!(ids, vec![Some(first), Some(second), Some(second)]);
+        assert_eq!(storage.accounts["Bob"].last_ops[0].meta.tx, Some(second));
+
+        // неудачная транзакция номер не оставляет
+        let withdraw = Withdraw {
+            account: "Alice".to_string(),
+            amount: rub(1000),
+        };
+        assert!(storage.submit(&withdraw, None).is_err());
+        assert_eq!(storage.accounts["Alice"].last_ops.len(), 3);
+    }
+
+    #[test]
+    fn resubmission_returns_original_result() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        let id = storage.submit(&deposit(100), Some("dep-1")).unwrap();
+        assert_eq!(storage.submit(&deposit(100), Some("dep-1")).unwrap(), id);
+        assert_eq!(storage.accounts["Alice"].result, 100);
+        assert_eq!(storage.keys["dep-1"].fingerprint, "deposit Alice 1.00 RUB");
+
+        assert!(matches!(
+            storage.submit(&deposit(50), Some("dep-1")),
+            Err(BankError::IdempotencyConflict { tx, .. }) if tx == id
+        ));
+        assert!(matches!(
+            storage.submit(&deposit(50), Some("")),
+            Err(BankError::BadIdempotencyKey(_))
+        ));
+
+        // ключ неудачной транзакции можно использовать снова
+        let withdraw = Withdraw {
+            account: "Alice".to_string(),
+            amount: rub(500),
+        };
+        assert!(storage.submit(&withdraw, Some("wd-1")).is_err());
+        assert!(!storage.keys.contains_key("wd-1"));
+        storage.submit(&deposit(400), None).unwrap();
+        storage.submit(&withdraw, Some("wd-1")).unwrap();
+        assert_eq!(storage.accounts["Alice"].result, 0);
+    }
+
+    #[test]
+    fn keys_survive_restart() {
+        let file = "idempotency_keys.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.add_user("Alice".to_string());
+        let id = storage.submit(&deposit(100), Some("dep-1")).unwrap();
+
+        // из журнала
+        let (mut restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.submit(&deposit(100), Some("dep-1")).unwrap(), id);
+        assert_eq!(restored.accounts["Alice"].result, 100);
+
+        // из снимка; номера продолжаются, а не начинаются заново
+        restored.save(file);
+        assert!(
+            fs::read_to_string(file)
+                .unwrap()
+                .contains("#keys\ndep-1,1,deposit%20Alice%201.00%20RUB\n")
+        );
+        let (mut restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.submit(&deposit(100), Some("dep-1")).unwrap(), id);
+        assert_eq!(restored.submit(&deposit(100), None).unwrap(), id + 1);
+
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn memo_survives_journal_reload() {
+        let file = "idempotency_memo.csv";
+        let memo = "rent; jan, part\n2";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.add_user("Alice".to_string());
+        storage.submit(&deposit(50), None).unwrap();
+        let id = storage
+            .submit(&deposit(100).with_memo(memo), Some("k1"))
+            .unwrap();
+        storage.submit(&deposit(1), None).unwrap();
+
+        let (mut restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts["Alice"].result, 151);
+        assert_eq!(
+            restored.accounts["Alice"].last_ops[1].meta.memo.as_deref(),
+            Some(memo)
+        );
+        assert_eq!(
+            restored
+                .submit(&deposit(100).with_memo(memo), Some("k1"))
+                .unwrap(),
+            id
+        );
+        assert!(matches!(
+            restored.submit(&deposit(100).with_memo("rent"), Some("k1")),
+            Err(BankError::IdempotencyConflict { .. })
+        ));
+
+        fs::remove_file(file).ok();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn key_record_roundtrip() {
+        let record: KeyRecord = "k,3,transfer A B 1.00 RUB + deposit A 2.00 RUB"
+            .parse()
+            .unwrap();
+        assert_eq!(record.tx, 3);
+        assert_eq!(record.to_string().parse::<KeyRecord>(), Ok(record));
+        assert!("k,x,deposit".parse::<KeyRecord>().is_err());
+        assert!("k,1".parse::<KeyRecord>().is_err());
+        assert!(check_key(&"k".repeat(MAX_KEY_LEN + 1)).is_err());
+        assert!(check_key("pay;1").is_err());
+
+        // описание не ломает строку, что бы в нём ни было
+        let record = KeyRecord {
+            key: "k".to_string(),
+            tx: 4,
+            fingerprint: "deposit a;b,c\nd 100% 1.00 RUB".to_string(),
+        };
+        let line = record.to_string();
+        assert!(!line.contains([';', '\n', ' ']), "{}", line);
+        assert_eq!(line.split(',').count(), 3);
+        assert_eq!(line.parse::<KeyRecord>(), Ok(record));
+        assert!("k,1,deposit%ZZ".parse::<KeyRecord>().is_err());
+    }
+
+    #[test]
+    fn names_with_spaces_do_not_collide() {
+        let mut storage = Storage::new();
+        storage.add_user("a b".to_string());
+        storage.add_user("a".to_string());
+        storage.deposit(&"a b".to_string(), rub(100)).unwrap();
+        storage.deposit(&"a".to_string(), rub(100)).unwrap();
+        let transfer = |from: &str, to: &str| Transfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount: rub(10),
+        };
+        let first = transfer("a b", "c");
+        let second = transfer("a", "b c");
+        assert_eq!(first.fingerprint(), "transfer a%20b c 0.10 RUB");
+        assert_ne!(first.fingerprint(), second.fingerprint());
+
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let id = storage.submit(&first, Some("t-1")).unwrap();
+        assert!(matches!(
+            storage.submit(&second, Some("t-1")),
+            Err(BankError::IdempotencyConflict { tx, .. }) if tx == id
+        ));
+        assert!(!storage.accounts.contains_key("b c"));
+    }
+}
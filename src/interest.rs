@@ -0,0 +1,461 @@
+use crate::Name;
+use crate::clock::{Clock, Date};
+use crate::errors::BankError;
+use crate::ledger::LedgerAccount;
+use crate::money::{Amount, Money};
+use crate::operations::{Balance, OpKind};
+use crate::rates::{Rate, Rounding};
+use crate::shared::SharedStorage;
+use crate::storage::Storage;
+use std::fmt;
+use std::str::FromStr;
+
+/// Как проценты прошлых периодов влияют на следующие
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterestMode {
+    /// Проценты начисляются только на вложенные деньги: всё, что уже начислено
+    /// как `OpKind::Interest`, из базы вычитается
+    Simple,
+    /// Проценты капитализируются: база — весь остаток вместе с начисленным
+    Compound,
+}
+
+/// Как считать долю года между двумя датами
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayCount {
+    /// Фактическое число дней, год — 365 дней
+    Actual365,
+    /// Фактическое число дней, год — 360 дней
+    Actual360,
+    /// В каждом месяце 30 дней, год — 360 дней (европейский вариант 30E/360)
+    Thirty360,
+}
+
+impl DayCount {
+    /// Число дней между датами по соглашению
+    pub fn days(self, from: Date, to: Date) -> i64 {
+        match self {
+            DayCount::Actual365 | DayCount::Actual360 => from.days_until(to),
+            DayCount::Thirty360 => {
+                let (y1, m1, d1) = from.ymd();
+                let (y2, m2, d2) = to.ymd();
+                360 * i64::from(y2 - y1)
+                    + 30 * (i64::from(m2) - i64::from(m1))
+                    + i64::from(d2.min(30))
+                    - i64::from(d1.min(30))
+            }
+        }
+    }
+
+    /// Дней в году по соглашению
+    pub fn year(self) -> i64 {
+        match self {
+            DayCount::Actual365 => 365,
+            DayCount::Actual360 | DayCount::Thirty360 => 360,
+        }
+    }
+}
+
+/// Условия начисления процентов по счёту. Проценты начисляются в основной валюте
+/// счёта и только на положительный остаток: за долг по кредитной линии их нет.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterestTerms {
+    /// Годовая ставка долей единицы: `0.05` — 5% годовых
+    pub rate: Rate,
+    pub mode: InterestMode,
+    pub day_count: DayCount,
+    /// По какой день (не включая его) проценты уже начислены
+    pub accrued_to: Date,
+}
+
+/// Компактная запись для файлов: `0.05:compound:act/365:2026-01-01`
+impl fmt::Display for InterestTerms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.rate, self.mode, self.day_count, self.accrued_to
+        )
+    }
+}
+
+impl FromStr for InterestTerms {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [rate, mode, day_count, accrued_to] = parts.as_slice() else {
+            return Err(format!("некорректные условия процентов '{}'", s));
+        };
+        Ok(InterestTerms {
+            rate: rate.parse()?,
+            mode: mode.parse()?,
+            day_count: day_count.parse()?,
+            accrued_to: accrued_to.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for InterestMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InterestMode::Simple => "simple",
+            InterestMode::Compound => "compound",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InterestMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "simple" => Ok(InterestMode::Simple),
+            "compound" => Ok(InterestMode::Compound),
+            other => Err(format!("неизвестный способ начисления '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for DayCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DayCount::Actual365 => "act/365",
+            DayCount::Actual360 => "act/360",
+            DayCount::Thirty360 => "30/360",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DayCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "act/365" => Ok(DayCount::Actual365),
+            "act/360" => Ok(DayCount::Actual360),
+            "30/360" => Ok(DayCount::Thirty360),
+            other => Err(format!("неизвестный способ счёта дней '{}'", other)),
+        }
+    }
+}
+
+impl Balance {
+    /// Начисляет проценты с `accrued_to` по `until` (не включая его) и возвращает их сумму.
+    ///
+    /// Период режется по границам календарных месяцев, за каждый месяц в историю
+    /// пишется отдельная `OpKind::Interest`, так что в режиме `Compound` проценты
+    /// капитализируются ежемесячно. Каждая запись округляется по `rounding`.
+    /// База — остаток на момент расчёта, поэтому хранилище доначисляет проценты
+    /// перед каждым движением денег по счёту.
+    /// Если остаток переполнился бы, возвращает ошибкой сумму, которая не поместилась.
+    pub fn accrue_interest(&mut self, until: Date, rounding: Rounding) -> Result<Amount, Amount> {
+        let Some(terms) = self.interest else {
+            return Ok(Amount::ZERO);
+        };
+        let mut total = Amount::ZERO;
+        let mut start = terms.accrued_to;
+        while start < until {
+            let end = start.next_month().min(until);
+            let own = self.result.to_amount().unwrap_or(Amount::ZERO);
+            let base = match terms.mode {
+                InterestMode::Simple => own.checked_sub(self.earned_interest()),
+                InterestMode::Compound => Some(own),
+            }
+            .unwrap_or(Amount::ZERO);
+
+            let days = terms.day_count.days(start, end).max(0) as u128;
+            let num = u128::from(base.value()) * u128::from(terms.rate.scaled()) * days;
+            let den = 10u128.pow(Rate::DIGITS) * terms.day_count.year() as u128;
+            let interest = u64::try_from(rounding.divide(num, den))
+                .map(Amount::new)
+                .map_err(|_| Amount::MAX)?;
+
+            if !interest.is_zero() {
+                self.result = self.result.checked_add(interest).ok_or(interest)?;
+                self.last_ops.push(OpKind::Interest(interest).into());
+                // проценты начисляются на неотрицательный остаток, и он поместился
+                total = total.checked_add(interest).ok_or(interest)?;
+            }
+            start = end;
+        }
+        if let Some(terms) = self.interest.as_mut() {
+            terms.accrued_to = terms.accrued_to.max(until);
+        }
+        Ok(total)
+    }
+
+    /// Сколько процентов начислено за всю историю счёта, включая архивную
+    fn earned_interest(&self) -> Amount {
+        let archived = -self
+            .archived
+            .external(&LedgerAccount::Interest, self.currency);
+        let archived = u64::try_from(archived).map_or(Amount::MAX, Amount::new);
+        self.last_ops
+            .iter()
+            .filter_map(|op| match op.kind {
+                OpKind::Interest(amount) => Some(amount),
+                _ => None,
+            })
+            .try_fold(archived, Amount::checked_add)
+            .unwrap_or(Amount::MAX)
+    }
+}
+
+/// Задание начисления процентов: по часам `clock` доводит начисление по всем
+/// счетам с условиями до сегодняшнего дня.
+pub struct InterestEngine<C: Clock> {
+    pub clock: C,
+    /// По умолчанию остаток отбрасывается: банк не начисляет больше, чем по ставке
+    pub rounding: Rounding,
+}
+
+impl<C: Clock> InterestEngine<C> {
+    pub fn new(clock: C) -> Self {
+        InterestEngine {
+            clock,
+            rounding: Rounding::Down,
+        }
+    }
+
+    /// Условия со ставкой `rate`, начисление по которым начнётся с сегодняшнего дня
+    pub fn terms(&self, rate: Rate, mode: InterestMode, day_count: DayCount) -> InterestTerms {
+        InterestTerms {
+            rate,
+            mode,
+            day_count,
+            accrued_to: self.clock.today(),
+        }
+    }
+
+    /// Начисляет проценты по всем счетам атомарно. Возвращает начисленное по
+    /// каждому счёту, где сумма ненулевая, в порядке имён.
+    pub fn run(&self, storage: &mut Storage) -> Result<Vec<(Name, Money)>, BankError> {
+        storage.accrue_interest(self.clock.today(), self.rounding)
+    }
+
+    /// То же для общего хранилища
+    pub fn run_shared(&self, storage: &SharedStorage) -> Result<Vec<(Name, Money)>, BankError> {
+        storage.accrue_interest(self.clock.today(), self.rounding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::errors::LoadMode;
+    use crate::money::Currency;
+    use crate::storage::BalanceManager;
+    use crate::testing::date;
+    use std::sync::Arc;
+
+    fn savings(amount: u64, rate: &str, mode: InterestMode, day_count: DayCount) -> Balance {
+        let mut balance = Balance::new();
+        balance.process(&[&OpKind::Deposit(Amount::new(amount))]);
+        balance.interest = Some(InterestTerms {
+            rate: rate.parse().unwrap(),
+            mode,
+            day_count,
+            accrued_to: date("2026-01-01"),
+        });
+        balance
+    }
+
+    #[test]
+    fn day_count_conventions() {
+        let (from, to) = (date("2026-01-31"), date("2026-03-01"));
+        assert_eq!(DayCount::Actual365.days(from, to), 29);
+        assert_eq!(DayCount::Thirty360.days(from, to), 31);
+        assert_eq!(
+            DayCount::Thirty360.days(date("2026-01-01"), date("2027-01-01")),
+            360
+        );
+    }
+
+    #[test]
+    fn simple_and_compound_over_a_year() {
+        // 1000.00 под 12% годовых по 30/360: ровно 1% в месяц
+        let mut simple = savings(100_000, "0.12", InterestMode::Simple, DayCount::Thirty360);
+        let mut compound = savings(100_000, "0.12", InterestMode::Compound, DayCount::Thirty360);
+        let year_end = date("2027-01-01");
+
+        assert_eq!(
+            simple.accrue_interest(year_end, Rounding::Down),
+            Ok(Amount::new(12_000))
+        );
+        // 1000 * (1.01^12 - 1) = 126.825..., но каждый месяц округляется вниз
+        assert_eq!(
+            compound.accrue_interest(year_end, Rounding::Down),
+            Ok(Amount::new(12_678))
+        );
+        assert_eq!(compound.last_ops.len(), 13);
+        assert_eq!(
+            compound.last_ops[1].kind,
+            OpKind::Interest(Amount::new(1000))
+        );
+        assert_eq!(compound.interest.unwrap().accrued_to, year_end);
+
+        // Повторный запуск за тот же период ничего не начисляет
+        assert_eq!(
+            compound.accrue_interest(year_end, Rounding::Down),
+            Ok(Amount::ZERO)
+        );
+    }
+
+    #[test]
+    fn rounding_and_debt() {
+        // 11.00 под 5% по act/365 за 31 день = 4.671... копейки
+        let mut balance = savings(1100, "0.05", InterestMode::Compound, DayCount::Actual365);
+        let mut half_up = balance.clone();
+        assert_eq!(
+            balance.accrue_interest(date("2026-02-01"), Rounding::Down),
+            Ok(Amount::new(4))
+        );
+        half_up
+            .accrue_interest(date("2026-02-01"), Rounding::HalfUp)
+            .unwrap();
+        assert_eq!(balance.result, 1104);
+        assert_eq!(half_up.result, 1105);
+
+        // На долг по кредитной линии проценты не начисляются
+        let mut debtor = savings(0, "0.05", InterestMode::Compound, DayCount::Actual360);
+        debtor.credit_limit = Amount::new(500);
+        debtor.process(&[&OpKind::Withdraw(Amount::new(500))]);
+        assert_eq!(
+            debtor.accrue_interest(date("2027-01-01"), Rounding::Down),
+            Ok(Amount::ZERO)
+        );
+        assert_eq!(debtor.result, -500);
+    }
+
+    #[test]
+    fn terms_roundtrip() {
+        let terms = savings(0, "0.05", InterestMode::Simple, DayCount::Actual360)
+            .interest
+            .unwrap();
+        assert_eq!(terms.to_string(), "0.05:simple:act/360:2026-01-01");
+        assert_eq!(terms.to_string().parse(), Ok(terms));
+        assert!("0.05:simple:act/360".parse::<InterestTerms>().is_err());
+        assert!(
+            "0.05:weekly:act/360:2026-01-01"
+                .parse::<InterestTerms>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn engine_accrues_over_simulated_months() {
+        let clock = ManualClock::new(date("2026-01-01"));
+        let engine = InterestEngine::new(&clock);
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user("Bob".to_string());
+        storage
+            .deposit(&alice, Money::new(100_000, Currency::RUB))
+            .unwrap();
+        let terms = engine.terms(
+            "0.12".parse().unwrap(),
+            InterestMode::Compound,
+            DayCount::Thirty360,
+        );
+        storage.set_interest(&alice, Some(terms)).unwrap();
+
+        assert_eq!(engine.run(&mut storage).unwrap(), vec![]);
+        for _ in 0..3 {
+            clock.set(clock.today().next_month());
+            engine.run(&mut storage).unwrap();
+        }
+        // 1000 -> 1010 -> 1020.10 -> 1030.30
+        assert_eq!(storage.accounts["Alice"].result, 103_030);
+        assert_eq!(storage.accounts["Bob"].result, 0);
+
+        // Условия и дата последнего начисления переживают сохранение
+        let file = "interest_roundtrip.csv";
+        storage.save(file);
+        let (loaded, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts, storage.accounts);
+        std::fs::remove_file(file).unwrap();
+        let _ = std::fs::remove_file(crate::journal::Journal::path_for(file));
+
+        clock.set(clock.today().next_month());
+        let shared = SharedStorage::new(storage);
+        assert_eq!(
+            engine.run_shared(&shared).unwrap(),
+            vec![(alice.clone(), Money::new(1030, Currency::RUB))]
+        );
+    }
+
+    #[test]
+    fn movements_settle_interest_first() {
+        // 12% по 30/360; деньги внесены в последний день декабря
+        let clock = Arc::new(ManualClock::new(date("2026-12-01")));
+        let engine = InterestEngine::new(clock.clone());
+        let mut storage = Storage::new();
+        storage.clock = clock.clone();
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        let terms = engine.terms(
+            "0.12".parse().unwrap(),
+            InterestMode::Simple,
+            DayCount::Thirty360,
+        );
+        storage.set_interest(&alice, Some(terms)).unwrap();
+
+        clock.set(date("2026-12-31"));
+        storage
+            .deposit(&alice, Money::new(100_000, Currency::RUB))
+            .unwrap();
+        assert_eq!(
+            storage.accounts["Alice"].interest.unwrap().accrued_to,
+            date("2026-12-31")
+        );
+        // за один день, а не за месяц: 1000.00 * 0.12 / 360 = 0.33
+        clock.set(date("2027-01-01"));
+        assert_eq!(
+            engine.run(&mut storage).unwrap(),
+            vec![(alice.clone(), Money::new(33, Currency::RUB))]
+        );
+
+        // снятие тоже сначала доначисляет проценты за время, пока деньги лежали
+        clock.set(date("2027-02-01"));
+        storage
+            .withdraw(&alice, Money::new(100_000, Currency::RUB))
+            .unwrap();
+        assert_eq!(storage.accounts["Alice"].result, 1033);
+        assert_eq!(engine.run(&mut storage).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn closing_pays_out_interest_to_date() {
+        let clock = Arc::new(ManualClock::new(date("2026-01-01")));
+        let engine = InterestEngine::new(clock.clone());
+        let mut storage = Storage::new();
+        storage.clock = clock.clone();
+        let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage
+            .deposit(&alice, Money::new(100_000, Currency::RUB))
+            .unwrap();
+        let terms = engine.terms(
+            "0.12".parse().unwrap(),
+            InterestMode::Compound,
+            DayCount::Thirty360,
+        );
+        storage.set_interest(&alice, Some(terms)).unwrap();
+
+        // за январь набежал 1%, и он уходит вместе с остатком, а не остаётся на закрытом счёте
+        clock.set(date("2026-02-01"));
+        assert_eq!(
+            storage.close_account(&alice, Some(&bob)).unwrap(),
+            Money::new(101_000, Currency::RUB)
+        );
+        assert_eq!(storage.accounts["Bob"].result, 101_000);
+        assert_eq!(storage.accounts["Alice"].result, 0);
+        assert!(storage.trial_balance().is_balanced());
+    }
+}
@@ -0,0 +1,606 @@
+use crate::Name;
+use crate::holds::{Authorization, HoldId};
+use crate::idempotency::KeyRecord;
+use crate::interest::InterestTerms;
+use crate::money::{Amount, Currency, SignedAmount};
+use crate::operations::{Balance, Operation};
+use crate::schedule::{OrderId, StandingOrder};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Изменение одного счёта внутри записи журнала
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Новый баланс счёта: из истории оставляем первые `keep` операций и дописываем `ops`
+    Set {
+        name: Name,
+        currency: Currency,
+        result: SignedAmount,
+        credit_limit: Amount,
+        interest: Option<InterestTerms>,
+        holdings: BTreeMap<Currency, Amount>,
+        holds: BTreeMap<HoldId, Authorization>,
+        keep: usize,
+        ops: Vec<Operation>,
+    },
+    Remove(Name),
+    /// Новое состояние платёжного поручения целиком
+    Order(StandingOrder),
+    /// Новый ключ идемпотентности
+    Key(KeyRecord),
+    /// Первые `count` операций истории счёта перенесены в архив
+    Archive {
+        name: Name,
+        count: usize,
+    },
+}
+
+/// Одна запись журнала — все изменения, сделанные одной успешной операцией
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JournalEntry {
+    pub changes: Vec<Change>,
+}
+
+impl JournalEntry {
+    /// Вычисляет, чем `after` отличается от `before`
+    pub fn diff(before: &HashMap<Name, Balance>, after: &HashMap<Name, Balance>) -> Self {
+        let changed = after
+            .iter()
+            .filter_map(|(name, balance)| change(name, before.get(name), Some(balance)));
+        let removed = before
+            .keys()
+            .filter(|name| !after.contains_key(*name))
+            .map(|name| Change::Remove(name.clone()));
+        JournalEntry::sorted(changed.chain(removed).collect())
+    }
+
+    /// То же, что `diff`, но только по счетам `touched`: их прежним значениям
+    /// (`None` — счёта не было) и текущим в `accounts`
+    pub(crate) fn diff_touched(
+        touched: &HashMap<Name, Option<Balance>>,
+        accounts: &HashMap<Name, Balance>,
+    ) -> Self {
+        let changes = touched
+            .iter()
+            .filter_map(|(name, old)| change(name, old.as_ref(), accounts.get(name)))
+            .collect();
+        JournalEntry::sorted(changes)
+    }
+
+    fn sorted(mut changes: Vec<Change>) -> Self {
+        // HashMap не гарантирует порядок, а журнал удобнее читать отсортированным
+        changes.sort_by(|a, b| a.name().cmp(b.name()));
+        JournalEntry { changes }
+    }
+
+    /// Дописывает изменившиеся платёжные поручения в порядке номеров
+    pub fn with_orders(
+        mut self,
+        before: &BTreeMap<OrderId, StandingOrder>,
+        after: &BTreeMap<OrderId, StandingOrder>,
+    ) -> Self {
+        for (id, order) in after {
+            if before.get(id) != Some(order) {
+                self.changes.push(Change::Order(order.clone()));
+            }
+        }
+        self
+    }
+
+    /// Дописывает новые ключи идемпотентности в порядке ключей
+    pub fn with_keys(
+        mut self,
+        before: &BTreeMap<String, KeyRecord>,
+        after: &BTreeMap<String, KeyRecord>,
+    ) -> Self {
+        for (key, record) in after {
+            if before.get(key) != Some(record) {
+                self.changes.push(Change::Key(record.clone()));
+            }
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Повторяет записанные изменения поверх `accounts`, `orders` и `keys`
+    pub fn apply(
+        &self,
+        accounts: &mut HashMap<Name, Balance>,
+        orders: &mut BTreeMap<OrderId, StandingOrder>,
+        keys: &mut BTreeMap<String, KeyRecord>,
+    ) {
+        for change in &self.changes {
+            match change {
+                Change::Set {
+                    name,
+                    currency,
+                    result,
+                    credit_limit,
+                    interest,
+                    holdings,
+                    holds,
+                    keep,
+                    ops,
+                } => {
+                    let balance = accounts
+                        .entry(name.clone())
+                        .or_insert_with(|| Balance::with_currency(*currency));
+                    balance.currency = *currency;
+                    balance.result = *result;
+                    balance.credit_limit = *credit_limit;
+                    balance.interest = *interest;
+                    balance.holdings = holdings.clone();
+                    balance.holds = holds.clone();
+                    balance.last_ops.truncate(*keep);
+                    balance.last_ops.extend(ops.iter().cloned());
+                }
+                Change::Remove(name) => {
+                    accounts.remove(name);
+                }
+                Change::Order(order) => {
+                    orders.insert(order.id, order.clone());
+                }
+                Change::Key(record) => {
+                    keys.insert(record.key.clone(), record.clone());
+                }
+                Change::Archive { name, count } => {
+                    if let Some(balance) = accounts.get_mut(name) {
+                        balance.move_to_archive(name, *count);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Изменение счёта `name` со значения `old` на `new`; `None` — счёт не изменился
+fn change(name: &Name, old: Option<&Balance>, new: Option<&Balance>) -> Option<Change> {
+    let Some(balance) = new else {
+        return old.map(|_| Change::Remove(name.clone()));
+    };
+    if old == Some(balance) {
+        return None;
+    }
+    let old_ops: &[Operation] = old.map(|b| b.last_ops.as_slice()).unwrap_or(&[]);
+    let keep = old_ops
+        .iter()
+        .zip(&balance.last_ops)
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(Change::Set {
+        name: name.clone(),
+        currency: balance.currency,
+        result: balance.result,
+        credit_limit: balance.credit_limit,
+        interest: balance.interest,
+        holdings: balance.holdings.clone(),
+        holds: balance.holds.clone(),
+        keep,
+        ops: balance.last_ops[keep..].to_vec(),
+    })
+}
+
+impl Change {
+    fn name(&self) -> &Name {
+        match self {
+            Change::Set { name, .. } | Change::Remove(name) | Change::Archive { name, .. } => name,
+            Change::Order(order) => &order.from,
+            Change::Key(record) => &record.key,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Set {
+                name,
+                currency,
+                result,
+                credit_limit,
+                interest,
+                holdings,
+                holds,
+                keep,
+                ops,
+            } => {
+                let holdings: Vec<String> = holdings
+                    .iter()
+                    .map(|(currency, amount)| format!("{}:{}", currency, amount))
+                    .collect();
+                let holds: Vec<String> = holds
+                    .iter()
+                    .map(|(id, hold)| format!("{}:{}", id, hold))
+                    .collect();
+                let ops: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+                write!(
+                    f,
+                    "S,{},{},{},{},{},{},{},{},{}",
+                    name,
+                    currency,
+                    result,
+                    credit_limit,
+                    interest.map(|terms| terms.to_string()).unwrap_or_default(),
+                    holdings.join(" "),
+                    holds.join(" "),
+                    keep,
+                    ops.join(" ")
+                )
+            }
+            Change::Remove(name) => write!(f, "R,{}", name),
+            Change::Order(order) => write!(f, "O,{}", order),
+            Change::Key(record) => write!(f, "K,{}", record),
+            Change::Archive { name, count } => write!(f, "A,{},{}", name, count),
+        }
+    }
+}
+
+impl FromStr for Change {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        let mut fields = match parts.as_slice() {
+            ["S", fields @ ..] => fields.to_vec(),
+            ["R", name] => return Ok(Change::Remove(name.to_string())),
+            ["O", ..] => return Ok(Change::Order(s[2..].parse()?)),
+            ["K", ..] => return Ok(Change::Key(s[2..].parse()?)),
+            ["A", name, count] => {
+                return Ok(Change::Archive {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| format!("неизвестное изменение '{}'", s))?,
+                });
+            }
+            _ => return Err(format!("неизвестное изменение '{}'", s)),
+        };
+        // Записи, сделанные до появления блокировок, процентов, кредитных лимитов, валют
+        // и остатков в других валютах, дополняем пустыми полями до текущего вида
+        // "name,currency,result,limit,interest,holdings,holds,keep,ops"
+        let missing: &[usize] = match fields.len() {
+            9 => &[],
+            8 => &[6],
+            7 => &[4, 6],
+            6 => &[3, 4, 6],
+            5 => &[3, 4, 5, 6],
+            4 => &[1, 3, 4, 5, 6],
+            _ => return Err(format!("неизвестное изменение '{}'", s)),
+        };
+        for &index in missing {
+            fields.insert(index, "");
+        }
+        let [
+            name,
+            currency,
+            result,
+            limit,
+            interest,
+            holdings,
+            holds,
+            keep,
+            ops,
+        ] = fields[..]
+        else {
+            unreachable!("полей ровно девять");
+        };
+
+        Ok(Change::Set {
+            name: name.to_string(),
+            currency: match currency {
+                "" => Currency::default(),
+                code => code.parse().map_err(|e| format!("{}", e))?,
+            },
+            result: result
+                .parse()
+                .map_err(|_| format!("плохой баланс '{}'", result))?,
+            credit_limit: match limit {
+                "" => Amount::ZERO,
+                limit => limit
+                    .parse()
+                    .map_err(|_| format!("плохой кредитный лимит '{}'", limit))?,
+            },
+            interest: match interest {
+                "" => None,
+                terms => Some(terms.parse()?),
+            },
+            holdings: Balance::parse_holdings(holdings)?,
+            holds: Balance::parse_holds(holds)?,
+            keep: keep
+                .parse()
+                .map_err(|_| format!("плохая длина '{}'", keep))?,
+            ops: ops
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changes: Vec<String> = self.changes.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", changes.join(";"))
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let changes = s.split(';').map(str::parse).collect::<Result<_, _>>()?;
+        Ok(JournalEntry { changes })
+    }
+}
+
+/// Журнал упреждающей записи (write-ahead log): по строке на каждую успешную операцию.
+/// Строка считается записанной, только если она целиком дошла до диска вместе с `\n`.
+/// Файл открыт в режиме добавления, поэтому запись всегда идёт в конец, даже после `clear`.
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Путь к журналу для файла-снимка
+    pub fn path_for(snapshot: &str) -> PathBuf {
+        PathBuf::from(format!("{}.journal", snapshot))
+    }
+
+    /// Открывает (или создаёт) журнал и возвращает все целые записи из него.
+    /// Оборванный хвост, оставшийся после падения, отрезается. Испорченная строка,
+    /// дописанная до конца, — ошибка `InvalidData`: за ней могут идти подтверждённые
+    /// записи, и молча отбросить их нельзя.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<(Journal, Vec<JournalEntry>)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut entries = Vec::new();
+        let mut valid_len = 0;
+        for (index, line) in data.split_inclusive(|b| *b == b'\n').enumerate() {
+            // Последняя строка без `\n` — запись, которую не успели дописать
+            let Some(line) = line.strip_suffix(b"\n") else {
+                break;
+            };
+            let entry = std::str::from_utf8(line)
+                .map_err(|e| e.to_string())
+                .and_then(str::parse::<JournalEntry>)
+                .map_err(|reason| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("строка журнала {}: {}", index + 1, reason),
+                    )
+                })?;
+            entries.push(entry);
+            valid_len += line.len() + 1;
+        }
+
+        if valid_len < data.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok((Journal { file, path }, entries))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Дописывает запись и дожидается, пока она окажется на диске.
+    /// При ошибке дописанное отрезается, чтобы следующая запись не склеилась с обрывком.
+    pub fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        let len = self.file.metadata()?.len();
+        let written = self
+            .file
+            .write_all(format!("{}\n", entry).as_bytes())
+            .and_then(|_| self.file.sync_data());
+        if written.is_err() {
+            let _ = self.file.set_len(len);
+        }
+        written
+    }
+
+    /// Очищает журнал после того, как его содержимое попало в снимок
+    pub fn clear(&self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::{OpKind, OpMeta};
+    use std::fs;
+
+    #[test]
+    fn entry_roundtrip() {
+        let entry = JournalEntry {
+            changes: vec![
+                Change::Set {
+                    name: "Alice".to_string(),
+                    currency: Currency::USD,
+                    result: SignedAmount::from(Amount::new(70)),
+                    credit_limit: Amount::new(100),
+                    interest: Some("0.05:simple:30/360:2026-01-01".parse().unwrap()),
+                    holdings: BTreeMap::from([(Currency::EUR, Amount::new(5))]),
+                    holds: BTreeMap::from([(3, "20:2026-02-01".parse().unwrap())]),
+                    keep: 1,
+                    ops: vec![Operation {
+                        kind: OpKind::TransferOut(Amount::new(30)),
+                        meta: OpMeta {
+                            tx: Some(4),
+                            at: Some("2026-01-31T09:05:00Z".parse().unwrap()),
+                            counterparty: Some("Bob".to_string()),
+                            memo: Some("долг; часть 1, остаток позже".to_string()),
+                        },
+                    }],
+                },
+                Change::Remove("Bob".to_string()),
+                Change::Archive {
+                    name: "Carol".to_string(),
+                    count: 12,
+                },
+            ],
+        };
+
+        let line = entry.to_string();
+        assert_eq!(
+            line,
+            "S,Alice,USD,70,100,0.05:simple:30/360:2026-01-01,EUR:5,3:20:2026-02-01,1,\
+             T-:30#4^2026-01-31T09:05:00Z&Bob~долг%3B%20часть%201%2C%20остаток%20позже;R,Bob;A,Carol,12"
+        );
+        assert_eq!(line.parse::<JournalEntry>().unwrap(), entry);
+
+        // долг по кредитной линии пишется со знаком
+        let overdrawn: Change = "S,Alice,USD,-30,100,,,,2,W:100".parse().unwrap();
+        assert!(matches!(overdrawn, Change::Set { result, .. } if result == -30));
+        // до блокировок, процентов и кредитных лимитов строка была короче
+        let old: Change = "S,Alice,USD,-30,100,,EUR:5,1,W:30".parse().unwrap();
+        assert!(matches!(old, Change::Set { ref holds, .. } if holds.is_empty()));
+        let old: Change = "S,Alice,USD,-30,100,EUR:5,1,W:30".parse().unwrap();
+        assert!(matches!(old, Change::Set { interest: None, .. }));
+        let old: Change = "S,Alice,USD,70,EUR:5,1,W:30".parse().unwrap();
+        assert!(matches!(
+            old,
+            Change::Set {
+                credit_limit: Amount::ZERO,
+                ..
+            }
+        ));
+
+        // старые записи без валюты читаются в базовой валюте
+        let old: Change = "S,Bob,5,0,D:5".parse().unwrap();
+        assert!(matches!(
+            old,
+            Change::Set {
+                currency: Currency::RUB,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let mut before = HashMap::new();
+        before.insert("Alice".to_string(), Balance::new());
+        before.insert("Bob".to_string(), Balance::new());
+
+        let mut after = before.clone();
+        after.remove("Bob");
+        let alice = after.get_mut("Alice").unwrap();
+        alice.result = Amount::new(100).into();
+        alice.credit_limit = Amount::new(50);
+        alice.interest = Some("0.1:compound:act/365:2026-01-01".parse().unwrap());
+        alice
+            .last_ops
+            .push(OpKind::Deposit(Amount::new(100)).into());
+        after.insert("Carl".to_string(), Balance::new());
+
+        let entry = JournalEntry::diff(&before, &after);
+        assert_eq!(entry.changes.len(), 3);
+
+        let mut replayed = before.clone();
+        entry.apply(&mut replayed, &mut BTreeMap::new(), &mut BTreeMap::new());
+        assert_eq!(replayed, after);
+    }
+
+    #[test]
+    fn orders_are_journaled_whole() {
+        let order: StandingOrder =
+            "1,Alice,Bob,100,RUB,monthly:1,0:1,2026-02-01,2026-02-01,0,active,"
+                .parse()
+                .unwrap();
+        let before = BTreeMap::from([(1, order.clone())]);
+        let mut after = before.clone();
+        after.get_mut(&1).unwrap().status = crate::schedule::OrderStatus::Cancelled;
+        after.insert(2, StandingOrder { id: 2, ..order });
+
+        let entry =
+            JournalEntry::diff(&HashMap::new(), &HashMap::new()).with_orders(&before, &after);
+        let line = entry.to_string();
+        assert_eq!(
+            line,
+            "O,1,Alice,Bob,100,RUB,monthly:1,0:1,2026-02-01,2026-02-01,0,cancelled,;\
+             O,2,Alice,Bob,100,RUB,monthly:1,0:1,2026-02-01,2026-02-01,0,active,"
+        );
+        let entry: JournalEntry = line.parse().unwrap();
+
+        // запись можно повторить сколько угодно раз
+        let mut replayed = before.clone();
+        entry.apply(&mut HashMap::new(), &mut replayed, &mut BTreeMap::new());
+        entry.apply(&mut HashMap::new(), &mut replayed, &mut BTreeMap::new());
+        assert_eq!(replayed, after);
+    }
+
+    #[test]
+    fn keys_are_journaled() {
+        let record: KeyRecord = "pay-42,7,transfer Alice Bob 1.00 RUB".parse().unwrap();
+        let after = BTreeMap::from([(record.key.clone(), record.clone())]);
+        let entry = JournalEntry::default().with_keys(&BTreeMap::new(), &after);
+        let line = entry.to_string();
+        assert_eq!(line, "K,pay-42,7,transfer%20Alice%20Bob%201.00%20RUB");
+
+        let mut keys = BTreeMap::new();
+        line.parse::<JournalEntry>().unwrap().apply(
+            &mut HashMap::new(),
+            &mut BTreeMap::new(),
+            &mut keys,
+        );
+        assert_eq!(keys, after);
+        assert!("K,bad key,7,x".parse::<Change>().is_err());
+    }
+
+    #[test]
+    fn open_drops_torn_tail() {
+        let path = "torn.journal";
+        fs::write(path, "S,Alice,RUB,100,,0,D:100\nS,Alice,RUB,50,,1,W:5").unwrap();
+
+        let (mut journal, entries) = Journal::open(path).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        journal
+            .append(&JournalEntry {
+                changes: vec![Change::Remove("Alice".to_string())],
+            })
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "S,Alice,RUB,100,,0,D:100\nR,Alice\n"
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn open_refuses_corrupt_line() {
+        let path = "corrupt.journal";
+        let data = "S,Alice,RUB,100,,0,D:100\nS,Evil,Name,RUB,0,,0,\nS,Alice,RUB,150,,1,D:50\n";
+        fs::write(path, data).unwrap();
+
+        let err = Journal::open(path)
+            .err()
+            .expect("испорченная строка — ошибка");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("строка журнала 2"), "{}", err);
+        // подтверждённые записи после плохой строки не тронуты
+        assert_eq!(fs::read_to_string(path).unwrap(), data);
+
+        fs::remove_file(path).unwrap();
+    }
+}
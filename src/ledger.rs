@@ -0,0 +1,408 @@
+use crate::Name;
+use crate::money::{Currency, SignedAmount, SignedMoney};
+use crate::operations::{Balance, OpKind};
+use crate::storage::Storage;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Счёт главной книги: клиентский счёт или один из внешних счетов банка,
+/// с которых приходят и на которые уходят деньги клиентов
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    Customer(Name),
+    /// Касса: наличные и внешние платежи, то есть пополнения и снятия
+    Cash,
+    /// Транзитный счёт переводов между клиентами. Каждый перевод проходит через него
+    /// дважды — списанием и зачислением, поэтому в сведённых книгах его сальдо нулевое.
+    Clearing,
+    /// Валютная позиция банка: сюда поступает проданная клиентом валюта
+    /// и отсюда выдаётся купленная
+    Exchange,
+    /// Расходы на проценты по счетам
+    Interest,
+}
+
+/// Внешние счета пишутся с `@`, клиентские — именем: `@cash`, `Alice`
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::Customer(name) => write!(f, "{}", name),
+            LedgerAccount::Cash => write!(f, "@cash"),
+            LedgerAccount::Clearing => write!(f, "@clearing"),
+            LedgerAccount::Exchange => write!(f, "@exchange"),
+            LedgerAccount::Interest => write!(f, "@interest"),
+        }
+    }
+}
+
+/// Обратное к `Display`: `@cash` — касса, строка без `@` — клиентский счёт
+impl FromStr for LedgerAccount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "@cash" => Ok(LedgerAccount::Cash),
+            "@clearing" => Ok(LedgerAccount::Clearing),
+            "@exchange" => Ok(LedgerAccount::Exchange),
+            "@interest" => Ok(LedgerAccount::Interest),
+            _ if s.is_empty() || s.starts_with('@') => {
+                Err(format!("неизвестный счёт книги '{}'", s))
+            }
+            name => Ok(LedgerAccount::Customer(name.to_string())),
+        }
+    }
+}
+
+/// Проводка: изменение одного счёта книги. Плюс — деньги пришли на счёт, минус — ушли.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub amount: SignedMoney,
+}
+
+impl Posting {
+    fn new(account: LedgerAccount, amount: SignedAmount, currency: Currency) -> Self {
+        Posting {
+            account,
+            amount: SignedMoney { amount, currency },
+        }
+    }
+}
+
+impl OpKind {
+    /// Проводки операции по счёту `owner` с основной валютой `currency`.
+    /// По каждой валюте их сумма равна нулю: деньги не появляются из ниоткуда.
+    pub fn postings(&self, owner: &Name, currency: Currency) -> Vec<Posting> {
+        let customer = || LedgerAccount::Customer(owner.clone());
+        let pair = |external, amount: SignedAmount, currency| {
+            vec![
+                Posting::new(customer(), amount, currency),
+                Posting::new(external, -amount, currency),
+            ]
+        };
+        match self {
+            OpKind::Deposit(value) => pair(LedgerAccount::Cash, (*value).into(), currency),
+            OpKind::Withdraw(value) => {
+                pair(LedgerAccount::Cash, -SignedAmount::from(*value), currency)
+            }
+            OpKind::TransferIn(value) => pair(LedgerAccount::Clearing, (*value).into(), currency),
+            OpKind::TransferOut(value) => pair(
+                LedgerAccount::Clearing,
+                -SignedAmount::from(*value),
+                currency,
+            ),
+            OpKind::Interest(value) => pair(LedgerAccount::Interest, (*value).into(), currency),
+            OpKind::Exchange { sold, bought, .. } => {
+                let mut postings = pair(
+                    LedgerAccount::Exchange,
+                    -SignedAmount::from(sold.amount),
+                    sold.currency,
+                );
+                postings.extend(pair(
+                    LedgerAccount::Exchange,
+                    bought.amount.into(),
+                    bought.currency,
+                ));
+                postings
+            }
+            OpKind::CloseAccount => Vec::new(),
+        }
+    }
+
+    /// На сколько операция меняет остаток самого клиента в валюте `currency`
+    pub fn balance_delta(&self, currency: Currency) -> i128 {
+        self.postings(&Name::new(), currency)
+            .iter()
+            .filter(|posting| {
+                matches!(posting.account, LedgerAccount::Customer(_))
+                    && posting.amount.currency == currency
+            })
+            .map(|posting| posting.amount.amount.value())
+            .sum()
+    }
+}
+
+/// Сходится ли набор проводок: сумма по каждой валюте — ноль
+pub fn is_balanced(postings: &[Posting]) -> bool {
+    let mut totals: BTreeMap<Currency, i128> = BTreeMap::new();
+    for posting in postings {
+        *totals.entry(posting.amount.currency).or_default() += posting.amount.amount.value();
+    }
+    totals.values().all(|total| *total == 0)
+}
+
+/// Клиентский счёт, сохранённый остаток которого расходится с его историей
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub account: Name,
+    pub currency: Currency,
+    pub stored: i128,
+    pub derived: i128,
+}
+
+/// Оборотно-сальдовая ведомость: сальдо всех счетов книги, посчитанные
+/// по проводкам из истории операций. Суммы — в минимальных единицах; сальдо
+/// внешних счетов может выйти за пределы [`SignedAmount`], поэтому здесь `i128`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrialBalance {
+    /// Ненулевые сальдо по счёту и валюте
+    pub balances: BTreeMap<(LedgerAccount, Currency), i128>,
+    /// Счета, чей остаток не выводится из проводок
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl TrialBalance {
+    /// Сумма сальдо по каждой валюте, в которой есть проводки
+    pub fn totals(&self) -> BTreeMap<Currency, i128> {
+        let mut totals = BTreeMap::new();
+        for ((_, currency), amount) in &self.balances {
+            *totals.entry(*currency).or_default() += amount;
+        }
+        totals
+    }
+
+    /// Сальдо счёта книги в валюте `currency`
+    pub fn balance(&self, account: &LedgerAccount, currency: Currency) -> i128 {
+        self.balances
+            .get(&(account.clone(), currency))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Книги сведены: по каждой валюте сальдо в сумме дают ноль, незавершённых
+    /// переводов нет, а остатки всех счетов совпадают с их историей
+    pub fn is_balanced(&self) -> bool {
+        self.totals().values().all(|total| *total == 0)
+            && !self
+                .balances
+                .keys()
+                .any(|(account, _)| *account == LedgerAccount::Clearing)
+            && self.mismatches.is_empty()
+    }
+}
+
+impl Balance {
+    /// Были ли у счёта переводы, в том числе в архиве. Вторая половина каждого
+    /// перевода лежит на другом счёте, поэтому без этого счёта транзитный счёт
+    /// книги перестал бы сходиться.
+    pub fn has_transfers(&self) -> bool {
+        let archived = self
+            .archived
+            .postings
+            .iter()
+            .any(|((account, _), amount)| *account == LedgerAccount::Clearing && *amount != 0);
+        archived
+            || self
+                .last_ops
+                .iter()
+                .any(|op| matches!(op.kind, OpKind::TransferIn(_) | OpKind::TransferOut(_)))
+    }
+}
+
+impl Storage {
+    /// Все проводки по истории счетов в памяти в порядке имён; архивные операции
+    /// представлены только итогом в [`Balance::archived`](crate::Balance::archived)
+    pub fn postings(&self) -> Vec<Posting> {
+        let mut names: Vec<&Name> = self.accounts.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .flat_map(|name| {
+                let balance = &self.accounts[name];
+                balance
+                    .last_ops
+                    .iter()
+                    .flat_map(|op| op.kind.postings(name, balance.currency))
+            })
+            .collect()
+    }
+
+    /// Сводит книги: проводит всю историю операций, начиная с итогов архива,
+    /// и сверяет полученные остатки клиентских счетов с сохранёнными
+    pub fn trial_balance(&self) -> TrialBalance {
+        let mut trial = TrialBalance::default();
+        for (name, balance) in &self.accounts {
+            for ((account, currency), amount) in &balance.archived.postings {
+                *trial
+                    .balances
+                    .entry((account.clone(), *currency))
+                    .or_default() += amount;
+                *trial
+                    .balances
+                    .entry((LedgerAccount::Customer(name.clone()), *currency))
+                    .or_default() -= amount;
+            }
+        }
+        for posting in self.postings() {
+            *trial
+                .balances
+                .entry((posting.account, posting.amount.currency))
+                .or_default() += posting.amount.amount.value();
+        }
+
+        let mut names: Vec<&Name> = self.accounts.keys().collect();
+        names.sort();
+        for name in names {
+            let account = LedgerAccount::Customer(name.clone());
+            let mut stored: BTreeMap<Currency, i128> = self.accounts[name]
+                .all_money()
+                .into_iter()
+                .map(|money| (money.currency, money.amount.value()))
+                .collect();
+            // валюты, которые есть в проводках, но пропали из остатков, тоже сверяем
+            for (ledger, currency) in trial.balances.keys() {
+                if *ledger == account {
+                    stored.entry(*currency).or_default();
+                }
+            }
+            for (currency, stored) in stored {
+                let derived = trial.balance(&account, currency);
+                if stored != derived {
+                    trial.mismatches.push(Mismatch {
+                        account: name.clone(),
+                        currency,
+                        stored,
+                        derived,
+                    });
+                }
+            }
+        }
+
+        trial.balances.retain(|_, amount| *amount != 0);
+        trial
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Date;
+    use crate::errors::BankError;
+    use crate::interest::{DayCount, InterestMode, InterestTerms};
+    use crate::money::Amount;
+    use crate::rates::Rounding;
+    use crate::storage::{AccountPolicy, BalanceManager};
+    use crate::testing::rub;
+
+    fn busy_storage() -> Storage {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        storage
+            .rates
+            .set(Currency::RUB, Currency::USD, "0.01".parse().unwrap());
+        let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+        storage.deposit(&alice, rub(10_000)).unwrap();
+        storage.transfer(&alice, &bob, rub(3_000)).unwrap();
+        storage.withdraw(&bob, rub(1_000)).unwrap();
+        storage.exchange(&alice, rub(5_000), Currency::USD).unwrap();
+        let start: Date = "2026-01-01".parse().unwrap();
+        storage
+            .set_interest(
+                &bob,
+                Some(InterestTerms {
+                    rate: "0.12".parse().unwrap(),
+                    mode: InterestMode::Simple,
+                    day_count: DayCount::Thirty360,
+                    accrued_to: start,
+                }),
+            )
+            .unwrap();
+        storage
+            .accrue_interest(start.next_month(), Rounding::HalfUp)
+            .unwrap();
+        storage
+    }
+
+    #[test]
+    fn every_operation_posts_balanced() {
+        let storage = busy_storage();
+        let postings = storage.postings();
+        assert!(is_balanced(&postings));
+        for balance in storage.accounts.values() {
+            for op in &balance.last_ops {
+                assert!(is_balanced(
+                    &op.kind.postings(&"X".to_string(), balance.currency)
+                ));
+            }
+        }
+        assert!(!is_balanced(&postings[..1]));
+    }
+
+    #[test]
+    fn trial_balance_sums_to_zero() {
+        let storage = busy_storage();
+        let trial = storage.trial_balance();
+        assert!(trial.is_balanced(), "{:?}", trial);
+        assert!(trial.totals().values().all(|total| *total == 0));
+
+        let alice = LedgerAccount::Customer("Alice".to_string());
+        let bob = LedgerAccount::Customer("Bob".to_string());
+        assert_eq!(trial.balance(&alice, Currency::RUB), 2_000);
+        assert_eq!(trial.balance(&alice, Currency::USD), 50);
+        assert_eq!(trial.balance(&bob, Currency::RUB), 2_020);
+        // наличными внесено 10 000, снято 1 000
+        assert_eq!(trial.balance(&LedgerAccount::Cash, Currency::RUB), -9_000);
+        assert_eq!(trial.balance(&LedgerAccount::Clearing, Currency::RUB), 0);
+        assert_eq!(
+            trial.balance(&LedgerAccount::Exchange, Currency::RUB),
+            5_000
+        );
+        assert_eq!(trial.balance(&LedgerAccount::Exchange, Currency::USD), -50);
+        assert_eq!(trial.balance(&LedgerAccount::Interest, Currency::RUB), -20);
+    }
+
+    #[test]
+    fn trial_balance_finds_broken_books() {
+        let mut storage = busy_storage();
+        storage.accounts.get_mut("Alice").unwrap().result = SignedAmount::from(Amount::new(1));
+        let trial = storage.trial_balance();
+        assert!(!trial.is_balanced());
+        assert_eq!(
+            trial.mismatches,
+            vec![Mismatch {
+                account: "Alice".to_string(),
+                currency: Currency::RUB,
+                stored: 1,
+                derived: 2_000,
+            }]
+        );
+
+        // без получателя перевод повис бы на транзитном счёте, поэтому удалить его нельзя
+        let mut storage = busy_storage();
+        assert!(matches!(
+            storage.remove_user(&"Bob".to_string()),
+            Err(BankError::HasTransfers(_))
+        ));
+        assert!(storage.trial_balance().is_balanced());
+        storage.accounts.remove("Bob");
+        let trial = storage.trial_balance();
+        assert!(trial.mismatches.is_empty());
+        assert_eq!(
+            trial.balance(&LedgerAccount::Clearing, Currency::RUB),
+            3_000
+        );
+        assert!(!trial.is_balanced());
+    }
+
+    #[test]
+    fn ledger_accounts_display() {
+        assert_eq!(
+            LedgerAccount::Customer("Alice".to_string()).to_string(),
+            "Alice"
+        );
+        assert_eq!(LedgerAccount::Cash.to_string(), "@cash");
+        assert_eq!(LedgerAccount::Clearing.to_string(), "@clearing");
+        for account in [
+            LedgerAccount::Customer("Alice".to_string()),
+            LedgerAccount::Cash,
+            LedgerAccount::Clearing,
+            LedgerAccount::Exchange,
+            LedgerAccount::Interest,
+        ] {
+            assert_eq!(account.to_string().parse::<LedgerAccount>(), Ok(account));
+        }
+        assert!("@bank".parse::<LedgerAccount>().is_err());
+        assert!("".parse::<LedgerAccount>().is_err());
+    }
+}
@@ -1,14 +1,47 @@
 pub mod analytics;
+pub mod archive;
+pub mod clock;
 pub mod errors;
+pub mod holds;
+pub mod http;
+pub mod idempotency;
+pub mod interest;
+pub mod journal;
+pub mod json;
+pub mod ledger;
+pub mod money;
 pub mod operations;
+pub mod protocol;
+pub mod rates;
+pub mod schedule;
+pub mod shared;
+pub mod statement;
 pub mod storage;
+#[cfg(test)]
+mod testing;
+pub mod timeseries;
 pub mod transaction;
 mod tx_chain;
 
-pub use analytics::find_best;
-pub use errors::BalanceManagerError;
-pub use operations::{Balance, OpKind};
-pub use storage::{BalanceManager, Storage};
-pub use transaction::{Deposit, Transaction, Transfer, TxCombinator, TxError, Withdraw};
+pub use analytics::{FlowStats, RankBy, find_best};
+pub use archive::{Archive, Archived, Retention};
+pub use clock::{Clock, Date, ManualClock, SystemClock, Timestamp};
+pub use errors::{BankError, LoadError, LoadMode, report};
+pub use holds::{Authorization, HoldId};
+pub use idempotency::KeyRecord;
+pub use interest::{DayCount, InterestEngine, InterestMode, InterestTerms};
+pub use ledger::{LedgerAccount, Posting, TrialBalance};
+pub use money::{Amount, Currency, Money, SignedAmount, SignedMoney};
+pub use operations::{Balance, OpKind, OpMeta, Operation};
+pub use rates::{Rate, RateTable, Rounding};
+pub use schedule::{OrderId, RetryPolicy, Schedule, StandingOrder};
+pub use shared::SharedStorage;
+pub use statement::{Statement, StatementLine};
+pub use storage::{AccountPolicy, BalanceManager, Storage, check_name};
+pub use timeseries::{Bucket, Metric, PeriodChange, Series, SeriesPoint};
+pub use transaction::{
+    Capture, Deposit, Exchange, Hold, Release, Transaction, Transfer, TxCombinator, TxId, WithMemo,
+    Withdraw,
+};
 
 pub type Name = String;
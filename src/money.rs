@@ -0,0 +1,457 @@
+use crate::errors::MoneyError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Денежная сумма в минимальных единицах (копейках, центах).
+/// Вся арифметика проверяемая: переполнение — это `None`, а не заворот или паника.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    pub const fn new(value: u64) -> Self {
+        Amount(value)
+    }
+
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount(value)
+    }
+}
+
+impl PartialEq<u64> for Amount {
+    fn eq(&self, other: &u64) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Amount)
+    }
+}
+
+/// Баланс со знаком в минимальных единицах: счёт с кредитным лимитом может уйти в минус.
+/// По модулю не больше `Amount::MAX`, так что неотрицательный баланс всегда переводится в `Amount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SignedAmount(i128);
+
+impl SignedAmount {
+    pub const ZERO: SignedAmount = SignedAmount(0);
+
+    /// `None`, если значение по модулю больше `Amount::MAX`
+    pub fn new(value: i128) -> Option<Self> {
+        (value.unsigned_abs() <= u64::MAX as u128).then_some(SignedAmount(value))
+    }
+
+    pub const fn value(self) -> i128 {
+        self.0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Неотрицательный баланс как `Amount`; `None` для долга
+    pub fn to_amount(self) -> Option<Amount> {
+        u64::try_from(self.0).ok().map(Amount)
+    }
+
+    /// Размер долга: `Amount::ZERO` для неотрицательного баланса
+    pub fn debt(self) -> Amount {
+        Amount(if self.0 < 0 {
+            self.0.unsigned_abs() as u64
+        } else {
+            0
+        })
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<SignedAmount> {
+        SignedAmount::new(self.0 + rhs.0 as i128)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<SignedAmount> {
+        SignedAmount::new(self.0 - rhs.0 as i128)
+    }
+}
+
+impl From<Amount> for SignedAmount {
+    fn from(value: Amount) -> Self {
+        SignedAmount(value.0 as i128)
+    }
+}
+
+impl std::ops::Neg for SignedAmount {
+    type Output = SignedAmount;
+
+    fn neg(self) -> Self::Output {
+        // границы симметричны, поэтому смена знака всегда допустима
+        SignedAmount(-self.0)
+    }
+}
+
+impl PartialEq<Amount> for SignedAmount {
+    fn eq(&self, other: &Amount) -> bool {
+        self.0 == other.0 as i128
+    }
+}
+
+impl PartialEq<i128> for SignedAmount {
+    fn eq(&self, other: &i128) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for SignedAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SignedAmount {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || MoneyError::BadAmount(s.to_string());
+        let value = match s.strip_prefix('-') {
+            Some(debt) => -(debt.parse::<u64>().map_err(|_| bad())? as i128),
+            None => s.parse::<u64>().map_err(|_| bad())? as i128,
+        };
+        SignedAmount::new(value).ok_or_else(bad)
+    }
+}
+
+/// Код валюты ISO 4217 из трёх заглавных латинских букв
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const RUB: Currency = Currency(*b"RUB");
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+
+    pub fn code(&self) -> &str {
+        // при создании проверяем, что все три байта — ASCII-буквы
+        std::str::from_utf8(&self.0).expect("код валюты всегда ASCII")
+    }
+
+    /// Сколько знаков после запятой у валюты (копейки, центы)
+    pub fn minor_digits(self) -> u32 {
+        match &self.0 {
+            b"JPY" | b"KRW" => 0,
+            _ => 2,
+        }
+    }
+
+    /// Сумма `value` в минимальных единицах этой валюты числом в основных единицах,
+    /// без кода валюты: `-0.05`. Для выписок и отчётов, где валюта в заголовке;
+    /// `value` может выйти за пределы [`SignedAmount`], как итоги по многим счетам.
+    pub(crate) fn major_units(self, value: i128) -> String {
+        let digits = self.minor_digits();
+        let scale = 10u128.pow(digits);
+        let sign = if value < 0 { "-" } else { "" };
+        let (whole, fraction) = (value.unsigned_abs() / scale, value.unsigned_abs() % scale);
+        if digits == 0 {
+            format!("{}{}", sign, whole)
+        } else {
+            format!(
+                "{}{}.{:0width$}",
+                sign,
+                whole,
+                fraction,
+                width = digits as usize
+            )
+        }
+    }
+}
+
+/// Базовая валюта банка: в ней открываются счета, если валюта не указана
+impl Default for Currency {
+    fn default() -> Self {
+        Currency::RUB
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            &[a, b, c] if [a, b, c].iter().all(u8::is_ascii_uppercase) => Ok(Currency([a, b, c])),
+            _ => Err(MoneyError::BadCurrency(s.to_string())),
+        }
+    }
+}
+
+/// Сумма в минимальных единицах вместе с валютой.
+/// Складывать и вычитать можно только суммы в одной валюте.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount: Amount,
+    pub currency: Currency,
+}
+
+impl Money {
+    /// Сумма `amount` в минимальных единицах валюты
+    pub const fn new(amount: u64, currency: Currency) -> Self {
+        Money {
+            amount: Amount::new(amount),
+            currency,
+        }
+    }
+
+    pub fn checked_add(self, rhs: Money) -> Result<Money, MoneyError> {
+        self.same_currency(rhs)?;
+        let amount = self
+            .amount
+            .checked_add(rhs.amount)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money { amount, ..self })
+    }
+
+    pub fn checked_sub(self, rhs: Money) -> Result<Money, MoneyError> {
+        self.same_currency(rhs)?;
+        let amount = self
+            .amount
+            .checked_sub(rhs.amount)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money { amount, ..self })
+    }
+
+    fn same_currency(self, rhs: Money) -> Result<(), MoneyError> {
+        if self.currency == rhs.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch {
+                expected: self.currency,
+                found: rhs.currency,
+            })
+        }
+    }
+
+    /// Разбирает сумму в основных единицах ("12.34") для валюты `currency`
+    pub fn parse_major(s: &str, currency: Currency) -> Result<Money, MoneyError> {
+        let bad = || MoneyError::BadAmount(s.to_string());
+        let digits = currency.minor_digits();
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if whole.is_empty()
+            || fraction.len() > digits as usize
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(bad());
+        }
+
+        let whole: u64 = whole.parse().map_err(|_| bad())?;
+        let fraction: u64 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<u64>().map_err(|_| bad())? * 10u64.pow(digits - fraction.len() as u32)
+        };
+        let amount = whole
+            .checked_mul(10u64.pow(digits))
+            .and_then(|v| v.checked_add(fraction))
+            .ok_or_else(bad)?;
+        Ok(Money::new(amount, currency))
+    }
+}
+
+/// Сумма в основных единицах: `12.34 USD`, `500 JPY`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.currency.minor_digits();
+        let value = self.amount.value();
+        if digits == 0 {
+            return write!(f, "{} {}", value, self.currency);
+        }
+        let scale = 10u64.pow(digits);
+        write!(
+            f,
+            "{}.{:0width$} {}",
+            value / scale,
+            value % scale,
+            self.currency,
+            width = digits as usize
+        )
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (amount, currency) = s
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| MoneyError::BadAmount(s.to_string()))?;
+        Money::parse_major(amount, currency.trim().parse()?)
+    }
+}
+
+/// Баланс со знаком вместе с валютой, например `-12.34 USD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignedMoney {
+    pub amount: SignedAmount,
+    pub currency: Currency,
+}
+
+impl From<Money> for SignedMoney {
+    fn from(money: Money) -> Self {
+        SignedMoney {
+            amount: money.amount.into(),
+            currency: money.currency,
+        }
+    }
+}
+
+impl PartialEq<Money> for SignedMoney {
+    fn eq(&self, other: &Money) -> bool {
+        *self == SignedMoney::from(*other)
+    }
+}
+
+impl fmt::Display for SignedMoney {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let magnitude = Money {
+            amount: Amount(self.amount.0.unsigned_abs() as u64),
+            currency: self.currency,
+        };
+        if self.amount.is_negative() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", magnitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_arithmetic() {
+        let a = Amount::new(10);
+        assert_eq!(a.checked_add(Amount::new(5)), Some(Amount::new(15)));
+        assert_eq!(a.checked_sub(Amount::new(4)), Some(Amount::new(6)));
+        assert_eq!(a.checked_sub(Amount::new(11)), None);
+        assert_eq!(Amount::MAX.checked_add(Amount::new(1)), None);
+    }
+
+    #[test]
+    fn wider_than_u32() {
+        let big: Amount = "5000000000".parse().unwrap();
+        assert_eq!(big, 5_000_000_000u64);
+        assert_eq!(big.to_string(), "5000000000");
+    }
+
+    #[test]
+    fn currency_codes() {
+        assert_eq!("USD".parse::<Currency>().unwrap(), Currency::USD);
+        assert_eq!(Currency::default(), Currency::RUB);
+        assert!("usd".parse::<Currency>().is_err());
+        assert!("US".parse::<Currency>().is_err());
+        assert!("ДОЛ".parse::<Currency>().is_err());
+    }
+
+    #[test]
+    fn money_rejects_mixed_currencies() {
+        let rub = Money::new(100, Currency::RUB);
+        let usd = Money::new(100, Currency::USD);
+
+        assert_eq!(
+            rub.checked_add(Money::new(50, Currency::RUB)),
+            Ok(Money::new(150, Currency::RUB))
+        );
+        assert_eq!(
+            rub.checked_add(usd),
+            Err(MoneyError::CurrencyMismatch {
+                expected: Currency::RUB,
+                found: Currency::USD,
+            })
+        );
+        assert_eq!(
+            rub.checked_sub(Money::new(101, Currency::RUB)),
+            Err(MoneyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn money_display_and_parse() {
+        assert_eq!(Money::new(1234, Currency::USD).to_string(), "12.34 USD");
+        assert_eq!(Money::new(5, Currency::RUB).to_string(), "0.05 RUB");
+        let jpy: Currency = "JPY".parse().unwrap();
+        assert_eq!(Money::new(500, jpy).to_string(), "500 JPY");
+
+        assert_eq!(
+            "12.3 USD".parse::<Money>(),
+            Ok(Money::new(1230, Currency::USD))
+        );
+        assert_eq!(
+            Money::parse_major("7", Currency::RUB),
+            Ok(Money::new(700, Currency::RUB))
+        );
+        assert!(Money::parse_major("1.234", Currency::RUB).is_err());
+        assert!(Money::parse_major("1.5", jpy).is_err());
+        assert!(Money::parse_major("-1", Currency::RUB).is_err());
+        assert!("12.34".parse::<Money>().is_err());
+
+        assert_eq!(Currency::RUB.major_units(-5), "-0.05");
+        assert_eq!(Currency::USD.major_units(123_456), "1234.56");
+        assert_eq!(jpy.major_units(-500), "-500");
+    }
+
+    #[test]
+    fn signed_amount_goes_below_zero() {
+        let balance = SignedAmount::from(Amount::new(30));
+        let debt = balance.checked_sub(Amount::new(50)).unwrap();
+        assert_eq!(debt, -20);
+        assert_eq!(debt.debt(), Amount::new(20));
+        assert_eq!(debt.to_amount(), None);
+        assert_eq!(balance.to_amount(), Some(Amount::new(30)));
+
+        let floor = SignedAmount::ZERO.checked_sub(Amount::MAX).unwrap();
+        assert_eq!(floor.checked_sub(Amount::new(1)), None);
+        assert_eq!(floor.to_string().parse::<SignedAmount>(), Ok(floor));
+        assert!("--1".parse::<SignedAmount>().is_err());
+
+        let money = SignedMoney {
+            amount: debt,
+            currency: Currency::RUB,
+        };
+        assert_eq!(money.to_string(), "-0.20 RUB");
+    }
+}
@@ -1,40 +1,469 @@
+use crate::Name;
+use crate::archive::Archived;
+use crate::clock::{Date, Timestamp};
+use crate::holds::{Authorization, HoldId};
+use crate::interest::InterestTerms;
+use crate::money::{Amount, Currency, Money, SignedAmount, SignedMoney};
+use crate::rates::Rate;
+use crate::transaction::TxId;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum OpKind {
-    Deposit(u32),
-    Withdraw(u32),
+    Deposit(Amount),
+    Withdraw(Amount),
+    /// Зачисление переводом с другого счёта банка
+    TransferIn(Amount),
+    /// Списание переводом на другой счёт банка
+    TransferOut(Amount),
+    /// Обмен `sold` на `bought` по курсу `rate` (сколько `bought` за единицу `sold`)
+    Exchange {
+        sold: Money,
+        bought: Money,
+        rate: Rate,
+    },
+    /// Начисленные проценты в основной валюте счёта
+    Interest(Amount),
+    /// Закрытие счёта; возможно только при нулевых остатках и всегда последнее в истории
     CloseAccount,
 }
 
+impl OpKind {
+    /// Вид операции для API и выписок: `deposit`, `transfer_in`, `close`...
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpKind::Deposit(_) => "deposit",
+            OpKind::Withdraw(_) => "withdraw",
+            OpKind::TransferIn(_) => "transfer_in",
+            OpKind::TransferOut(_) => "transfer_out",
+            OpKind::Exchange { .. } => "exchange",
+            OpKind::Interest(_) => "interest",
+            OpKind::CloseAccount => "close",
+        }
+    }
+}
+
+/// Компактная запись операции для файлов: `D:100`, `W:50`, `T+:30`, `T-:30`,
+/// `X:1000:USD>92500:RUB@92.5`, `I:12`, `C`
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpKind::Deposit(value) => write!(f, "D:{}", value),
+            OpKind::Withdraw(value) => write!(f, "W:{}", value),
+            OpKind::TransferIn(value) => write!(f, "T+:{}", value),
+            OpKind::TransferOut(value) => write!(f, "T-:{}", value),
+            OpKind::Exchange { sold, bought, rate } => write!(
+                f,
+                "X:{}:{}>{}:{}@{}",
+                sold.amount, sold.currency, bought.amount, bought.currency, rate
+            ),
+            OpKind::Interest(value) => write!(f, "I:{}", value),
+            OpKind::CloseAccount => write!(f, "C"),
+        }
+    }
+}
+
+impl FromStr for OpKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_value = |v: &str| v.parse().map_err(|_| format!("плохая сумма в '{}'", s));
+        let parse_money = |v: &str| -> Result<Money, String> {
+            let (amount, currency) = v
+                .split_once(':')
+                .ok_or_else(|| format!("плохая сумма в '{}'", s))?;
+            Ok(Money {
+                amount: parse_value(amount)?,
+                currency: currency.parse().map_err(|e| format!("{}", e))?,
+            })
+        };
+        match s.split_once(':') {
+            Some(("D", value)) => Ok(OpKind::Deposit(parse_value(value)?)),
+            Some(("W", value)) => Ok(OpKind::Withdraw(parse_value(value)?)),
+            Some(("T+", value)) => Ok(OpKind::TransferIn(parse_value(value)?)),
+            Some(("T-", value)) => Ok(OpKind::TransferOut(parse_value(value)?)),
+            Some(("I", value)) => Ok(OpKind::Interest(parse_value(value)?)),
+            Some(("X", value)) => {
+                let (sold, rest) = value
+                    .split_once('>')
+                    .ok_or_else(|| format!("плохой обмен '{}'", s))?;
+                let (bought, rate) = rest
+                    .split_once('@')
+                    .ok_or_else(|| format!("плохой обмен '{}'", s))?;
+                Ok(OpKind::Exchange {
+                    sold: parse_money(sold)?,
+                    bought: parse_money(bought)?,
+                    rate: rate.parse()?,
+                })
+            }
+            None if s == "C" => Ok(OpKind::CloseAccount),
+            _ => Err(format!("неизвестная операция '{}'", s)),
+        }
+    }
+}
+
+/// Реквизиты записи истории. У записей из старых файлов их нет.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpMeta {
+    /// Номер транзакции, которая провела операцию
+    pub tx: Option<TxId>,
+    /// Когда операция проведена
+    pub at: Option<Timestamp>,
+    /// Второй счёт перевода: получатель у `TransferOut`, отправитель у `TransferIn`
+    pub counterparty: Option<Name>,
+    /// Назначение платежа, свободный текст
+    pub memo: Option<String>,
+}
+
+/// Запись истории счёта: операция и её реквизиты
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub kind: OpKind,
+    pub meta: OpMeta,
+}
+
+impl From<OpKind> for Operation {
+    fn from(kind: OpKind) -> Self {
+        Operation {
+            kind,
+            meta: OpMeta::default(),
+        }
+    }
+}
+
+/// Символы, с которых начинаются реквизиты записи: номер транзакции, время,
+/// второй счёт и назначение. В [`OpKind`] их не бывает.
+const META_MARKERS: [char; 4] = ['#', '^', '&', '~'];
+
+/// Операция и за ней реквизиты в постоянном порядке: `T-:30#17^2026-01-31T09:05:00Z&Bob~rent`.
+/// Имя и назначение кодируются через `%XX`, чтобы не содержать пробелов, запятых
+/// и символов реквизитов. Без реквизитов — как [`OpKind`].
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(tx) = self.meta.tx {
+            write!(f, "#{}", tx)?;
+        }
+        if let Some(at) = self.meta.at {
+            write!(f, "^{}", at)?;
+        }
+        if let Some(counterparty) = &self.meta.counterparty {
+            write!(f, "&{}", escape(counterparty))?;
+        }
+        if let Some(memo) = &self.meta.memo {
+            write!(f, "~{}", escape(memo))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("плохие реквизиты операции '{}'", s);
+        let split = s.find(META_MARKERS).unwrap_or(s.len());
+        let mut op = Operation::from(s[..split].parse::<OpKind>()?);
+        let mut rest = &s[split..];
+        let mut previous = None;
+        while let Some(marker) = rest.chars().next() {
+            let value = &rest[1..];
+            let end = value.find(META_MARKERS).unwrap_or(value.len());
+            let (value, tail) = value.split_at(end);
+            // реквизиты идут в постоянном порядке и не повторяются
+            let position = META_MARKERS.iter().position(|m| *m == marker);
+            if position <= previous {
+                return Err(bad());
+            }
+            previous = position;
+            match marker {
+                '#' => op.meta.tx = Some(value.parse().map_err(|_| bad())?),
+                '^' => op.meta.at = Some(value.parse().map_err(|_| bad())?),
+                '&' => op.meta.counterparty = Some(unescape(value).ok_or_else(bad)?),
+                _ => op.meta.memo = Some(unescape(value).ok_or_else(bad)?),
+            }
+            rest = tail;
+        }
+        Ok(op)
+    }
+}
+
+/// Кодирует `%XX` всё, что не может стоять внутри записи операции
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '%' || c == ',' || c == ';' || c.is_whitespace() || c.is_control() {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        } else if META_MARKERS.contains(&c) {
+            out.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub(crate) fn unescape(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Баланс счёта. Суммы в `result`, `Deposit`, `Withdraw` и переводах — в основной валюте счёта
+/// `currency`. Остатки в других валютах лежат в `holdings` и меняются только обменом.
+/// В основной валюте счёт может уйти в минус, но не глубже `credit_limit`.
+/// `result` — учётный баланс; доступный остаток меньше его на сумму блокировок `holds`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Balance {
-    pub result: u64,
-    pub last_ops: Vec<OpKind>,
+    pub result: SignedAmount,
+    pub currency: Currency,
+    /// Кредитная линия в основной валюте; ноль — овердрафт запрещён
+    pub credit_limit: Amount,
+    /// Условия начисления процентов; `None` — проценты не начисляются
+    pub interest: Option<InterestTerms>,
+    pub holdings: BTreeMap<Currency, Amount>,
+    /// Блокировки в основной валюте, ещё не списанные и не снятые
+    pub holds: BTreeMap<HoldId, Authorization>,
+    /// Операции, перенесённые из `last_ops` в архив на диске
+    pub archived: Archived,
+    /// Последние операции счёта, начиная с первой неархивной
+    pub last_ops: Vec<Operation>,
 }
 
 impl Balance {
     pub fn new() -> Self {
+        Self::with_currency(Currency::default())
+    }
+
+    pub fn with_currency(currency: Currency) -> Self {
         Balance {
-            result: 0,
+            result: SignedAmount::ZERO,
+            currency,
+            credit_limit: Amount::ZERO,
+            interest: None,
+            holdings: BTreeMap::new(),
+            holds: BTreeMap::new(),
+            archived: Archived::default(),
             last_ops: Vec::new(),
         }
     }
 
+    /// Собственные деньги в валюте `currency` без учёта кредита (при долге — ноль);
+    /// `None`, если счёт её не держит
+    pub fn amount_in(&self, currency: Currency) -> Option<Amount> {
+        if currency == self.currency {
+            Some(self.result.to_amount().unwrap_or(Amount::ZERO))
+        } else {
+            self.holdings.get(&currency).copied()
+        }
+    }
+
+    /// Неиспользованная часть кредитной линии
+    pub fn available_credit(&self) -> Amount {
+        self.credit_limit
+            .checked_sub(self.result.debt())
+            .unwrap_or(Amount::ZERO)
+    }
+
+    /// Сколько можно потратить в валюте `currency` в день `today`: в основной валюте —
+    /// собственные деньги плюс неиспользованный кредит за вычетом действующих блокировок,
+    /// в остальных — только остаток
+    pub fn available_in(&self, currency: Currency, today: Date) -> Option<Amount> {
+        let own = self.amount_in(currency)?;
+        if currency != self.currency {
+            return Some(own);
+        }
+        Some(
+            own.checked_add(self.available_credit())
+                .unwrap_or(Amount::MAX)
+                .checked_sub(self.held(today))
+                .unwrap_or(Amount::ZERO),
+        )
+    }
+
+    /// Доступный остаток в основной валюте, как [`Balance::available_in`]
+    pub fn available(&self, today: Date) -> Money {
+        Money {
+            amount: self
+                .available_in(self.currency, today)
+                .expect("основная валюта всегда есть"),
+            currency: self.currency,
+        }
+    }
+
+    /// Все остатки счёта: сначала основная валюта, затем остальные по коду
+    pub fn all_money(&self) -> Vec<SignedMoney> {
+        let mut all = vec![self.money()];
+        all.extend(self.holdings.iter().map(|(currency, amount)| {
+            SignedMoney::from(Money {
+                amount: *amount,
+                currency: *currency,
+            })
+        }));
+        all
+    }
+
+    /// Остаток со знаком в валюте `currency`
+    fn signed_in(&self, currency: Currency) -> Option<SignedAmount> {
+        if currency == self.currency {
+            Some(self.result)
+        } else {
+            self.holdings.get(&currency).map(|amount| (*amount).into())
+        }
+    }
+
+    /// Ниже какого остатка нельзя опуститься в валюте `currency`
+    fn floor(&self, currency: Currency) -> SignedAmount {
+        if currency == self.currency {
+            -SignedAmount::from(self.credit_limit)
+        } else {
+            SignedAmount::ZERO
+        }
+    }
+
+    /// Применяет обмен к остаткам; `None`, если денег с учётом кредита не хватает
+    /// или валюты совпадают
+    fn exchange(&self, sold: Money, bought: Money) -> Option<(SignedAmount, SignedAmount)> {
+        if sold.currency == bought.currency {
+            return None;
+        }
+        let left = self
+            .signed_in(sold.currency)?
+            .checked_sub(sold.amount)
+            .filter(|left| *left >= self.floor(sold.currency))?;
+        let got = self
+            .signed_in(bought.currency)
+            .unwrap_or(SignedAmount::ZERO)
+            .checked_add(bought.amount)?;
+        Some((left, got))
+    }
+
+    /// Остатки в дополнительных валютах для файлов: `USD:100 EUR:5`
+    pub fn format_holdings(&self) -> String {
+        let holdings: Vec<String> = self
+            .holdings
+            .iter()
+            .map(|(currency, amount)| format!("{}:{}", currency, amount))
+            .collect();
+        holdings.join(" ")
+    }
+
+    pub fn parse_holdings(s: &str) -> Result<BTreeMap<Currency, Amount>, String> {
+        s.split_whitespace()
+            .map(|item| {
+                let bad = || format!("плохой остаток '{}'", item);
+                let (currency, amount) = item.split_once(':').ok_or_else(bad)?;
+                Ok((
+                    currency.parse().map_err(|_| bad())?,
+                    amount.parse().map_err(|_| bad())?,
+                ))
+            })
+            .collect()
+    }
+
+    fn set_amount(&mut self, currency: Currency, amount: SignedAmount) {
+        if currency == self.currency {
+            self.result = amount;
+        } else {
+            let amount = amount
+                .to_amount()
+                .expect("остаток в валюте не уходит в минус");
+            self.holdings.insert(currency, amount);
+        }
+    }
+
+    /// Закрыт ли счёт: закрытый счёт виден со всей историей, но операций не принимает
+    pub fn is_closed(&self) -> bool {
+        self.last_ops
+            .last()
+            .is_some_and(|op| op.kind == OpKind::CloseAccount)
+    }
+
+    /// Первый ненулевой остаток, который мешает закрыть счёт
+    pub fn leftover(&self) -> Option<SignedMoney> {
+        self.all_money()
+            .into_iter()
+            .find(|money| money.amount != SignedAmount::ZERO)
+    }
+
+    /// Баланс вместе с валютой счёта
+    pub fn money(&self) -> SignedMoney {
+        SignedMoney {
+            amount: self.result,
+            currency: self.currency,
+        }
+    }
+
+    /// Применяет операции по порядку и возвращает те, что применить не удалось:
+    /// первую неудачную и все после неё
     pub fn process<'a>(&mut self, ops: &[&'a OpKind]) -> Vec<&'a OpKind> {
+        self.process_with(&OpMeta::default(), ops)
+    }
+
+    /// То же, что [`Balance::process`], но записывает операции в историю
+    /// с реквизитами `meta`
+    pub fn process_with<'a>(&mut self, meta: &OpMeta, ops: &[&'a OpKind]) -> Vec<&'a OpKind> {
         let mut remaining = ops.iter();
         let mut bad_ops = Vec::new();
 
         for op in &mut remaining {
-            match op {
-                OpKind::Deposit(value) => {
-                    self.result += *value as u64;
-                    self.last_ops.push((*op).clone());
+            // После закрытия счёта плохи все операции
+            if self.is_closed() {
+                bad_ops.push(*op);
+                break;
+            }
+            // Операция, которая увела бы баланс ниже кредитного лимита или переполнила его, — плохая
+            let applied = match op {
+                OpKind::Deposit(value) | OpKind::TransferIn(value) | OpKind::Interest(value) => {
+                    self.result.checked_add(*value).map(|result| {
+                        self.result = result;
+                    })
                 }
-                OpKind::Withdraw(value) if self.result >= *value as u64 => {
-                    self.result -= *value as u64;
-                    self.last_ops.push((*op).clone());
+                OpKind::Withdraw(value) | OpKind::TransferOut(value) => self
+                    .result
+                    .checked_sub(*value)
+                    .filter(|result| *result >= self.floor(self.currency))
+                    .map(|result| {
+                        self.result = result;
+                    }),
+                OpKind::Exchange { sold, bought, .. } => {
+                    self.exchange(*sold, *bought).map(|(left, got)| {
+                        self.set_amount(sold.currency, left);
+                        self.set_amount(bought.currency, got);
+                    })
                 }
-                other => {
-                    bad_ops.push(*other);
+                // Закрыть можно только пустой счёт; вместе с ним закрываются
+                // кредитная линия и начисление процентов и снимаются блокировки
+                OpKind::CloseAccount => match self.leftover() {
+                    Some(_) => None,
+                    None => {
+                        self.credit_limit = Amount::ZERO;
+                        self.interest = None;
+                        self.holds.clear();
+                        Some(())
+                    }
+                },
+            };
+            match applied {
+                Some(()) => self.last_ops.push(Operation {
+                    kind: (*op).clone(),
+                    meta: meta.clone(),
+                }),
+                None => {
+                    bad_ops.push(*op);
                     break;
                 }
             }
@@ -59,6 +488,7 @@ mod tests {
     fn new_balance() {
         let balance = Balance::new();
         assert_eq!(balance.result, 0);
+        assert_eq!(balance.currency, Currency::RUB);
         assert_eq!(balance.last_ops.len(), 0);
     }
 
@@ -66,9 +496,9 @@ mod tests {
     fn process_successful_operations() {
         let mut balance = Balance::new();
         let ops = [
-            &OpKind::Deposit(50),
-            &OpKind::Withdraw(30),
-            &OpKind::Deposit(20),
+            &OpKind::Deposit(Amount::new(50)),
+            &OpKind::Withdraw(Amount::new(30)),
+            &OpKind::Deposit(Amount::new(20)),
         ];
 
         let failed = balance.process(&ops);
@@ -82,10 +512,10 @@ mod tests {
     fn process_insufficient_funds() {
         let mut balance = Balance::new();
         let ops = [
-            &OpKind::Deposit(50),
-            &OpKind::Withdraw(30),
-            &OpKind::Withdraw(30),
-            &OpKind::Deposit(100),
+            &OpKind::Deposit(Amount::new(50)),
+            &OpKind::Withdraw(Amount::new(30)),
+            &OpKind::Withdraw(Amount::new(30)),
+            &OpKind::Deposit(Amount::new(100)),
         ];
 
         let failed = balance.process(&ops);
@@ -99,8 +529,8 @@ mod tests {
     fn process_close_account() {
         let mut balance = Balance::new();
         let ops = [
-            &OpKind::Deposit(32),
-            &OpKind::Withdraw(64),
+            &OpKind::Deposit(Amount::new(32)),
+            &OpKind::Withdraw(Amount::new(64)),
             &OpKind::CloseAccount,
         ];
 
@@ -109,5 +539,169 @@ mod tests {
         assert_eq!(balance.result, 32);
         assert_eq!(failed.len(), 2);
         assert_eq!(balance.last_ops.len(), 1);
+        assert!(!balance.is_closed());
+
+        let withdraw = OpKind::Withdraw(Amount::new(32));
+        let deposit = OpKind::Deposit(Amount::new(1));
+        let failed = balance.process(&[&withdraw, &OpKind::CloseAccount, &deposit]);
+
+        assert_eq!(failed, vec![&deposit]);
+        assert!(balance.is_closed());
+        assert_eq!(balance.last_ops.len(), 3);
+        assert_eq!(balance.process(&[&OpKind::CloseAccount]).len(), 1);
+    }
+
+    #[test]
+    fn close_requires_empty_holdings() {
+        let mut balance = Balance::new();
+        balance.credit_limit = Amount::new(10);
+        balance.holdings.insert(Currency::USD, Amount::new(1));
+        assert_eq!(
+            balance.leftover(),
+            Some(Money::new(1, Currency::USD).into())
+        );
+        assert_eq!(balance.process(&[&OpKind::CloseAccount]).len(), 1);
+
+        balance.holdings.insert(Currency::USD, Amount::ZERO);
+        assert_eq!(balance.leftover(), None);
+        assert!(balance.process(&[&OpKind::CloseAccount]).is_empty());
+        assert_eq!(balance.credit_limit, Amount::ZERO);
+    }
+
+    #[test]
+    fn process_within_credit_limit() {
+        let mut balance = Balance::new();
+        balance.credit_limit = Amount::new(100);
+        let deposit = OpKind::Deposit(Amount::new(30));
+        let overdraft = OpKind::Withdraw(Amount::new(120));
+        let too_deep = OpKind::Withdraw(Amount::new(20));
+
+        let failed = balance.process(&[&deposit, &overdraft, &too_deep]);
+
+        assert_eq!(failed, vec![&too_deep]);
+        assert_eq!(balance.result, -90);
+        assert_eq!(balance.amount_in(Currency::RUB), Some(Amount::ZERO));
+        assert_eq!(balance.available_credit(), Amount::new(10));
+        assert_eq!(
+            balance.available_in(Currency::RUB, "2026-01-01".parse().unwrap()),
+            Some(Amount::new(10))
+        );
+        assert_eq!(balance.money().to_string(), "-0.90 RUB");
+    }
+
+    #[test]
+    fn process_overflow_is_bad_op() {
+        let mut balance = Balance::new();
+        let big = OpKind::Deposit(Amount::MAX);
+        let one = OpKind::Deposit(Amount::new(1));
+
+        let failed = balance.process(&[&big, &one]);
+
+        assert_eq!(balance.result, Amount::MAX);
+        assert_eq!(failed, vec![&one]);
+    }
+
+    #[test]
+    fn process_exchange() {
+        let mut balance = Balance::new();
+        let deposit = OpKind::Deposit(Amount::new(10000));
+        let buy = OpKind::Exchange {
+            sold: Money::new(9250, Currency::RUB),
+            bought: Money::new(100, Currency::USD),
+            rate: "0.010811".parse().unwrap(),
+        };
+        let too_much = OpKind::Exchange {
+            sold: Money::new(101, Currency::USD),
+            bought: Money::new(9342, Currency::RUB),
+            rate: "92.5".parse().unwrap(),
+        };
+
+        let failed = balance.process(&[&deposit, &buy, &too_much]);
+
+        assert_eq!(failed, vec![&too_much]);
+        assert_eq!(balance.result, 750);
+        assert_eq!(balance.amount_in(Currency::USD), Some(Amount::new(100)));
+        assert_eq!(balance.amount_in(Currency::EUR), None);
+        assert_eq!(
+            balance.all_money(),
+            vec![
+                Money::new(750, Currency::RUB),
+                Money::new(100, Currency::USD)
+            ]
+        );
+        assert_eq!(balance.last_ops.len(), 2);
+        assert_eq!(balance.format_holdings(), "USD:100");
+        assert_eq!(
+            Balance::parse_holdings("USD:100").unwrap(),
+            balance.holdings
+        );
+        assert!(Balance::parse_holdings("USD100").is_err());
+    }
+
+    #[test]
+    fn op_kind_roundtrip() {
+        let exchange = OpKind::Exchange {
+            sold: Money::new(1000, Currency::USD),
+            bought: Money::new(92500, Currency::RUB),
+            rate: "92.5".parse().unwrap(),
+        };
+        assert_eq!(exchange.to_string(), "X:1000:USD>92500:RUB@92.5");
+        for op in [
+            OpKind::Deposit(Amount::new(7)),
+            OpKind::Withdraw(Amount::new(3)),
+            OpKind::TransferIn(Amount::new(5)),
+            OpKind::TransferOut(Amount::new(6)),
+            exchange,
+            OpKind::Interest(Amount::new(12)),
+            OpKind::CloseAccount,
+        ] {
+            assert_eq!(op.to_string().parse::<OpKind>().unwrap(), op);
+        }
+        assert!("X:1".parse::<OpKind>().is_err());
+        assert!("D:abc".parse::<OpKind>().is_err());
+
+        let tagged = Operation {
+            kind: OpKind::Withdraw(Amount::new(3)),
+            meta: OpMeta {
+                tx: Some(17),
+                ..OpMeta::default()
+            },
+        };
+        assert_eq!(tagged.to_string(), "W:3#17");
+        assert_eq!("W:3#17".parse::<Operation>(), Ok(tagged));
+        assert_eq!(
+            "C".parse::<Operation>(),
+            Ok(Operation::from(OpKind::CloseAccount))
+        );
+        assert!("W:3#".parse::<Operation>().is_err());
+    }
+
+    #[test]
+    fn operation_meta_roundtrip() {
+        let transfer = Operation {
+            kind: OpKind::TransferOut(Amount::new(30)),
+            meta: OpMeta {
+                tx: Some(5),
+                at: Some("2026-01-31T09:05:00Z".parse().unwrap()),
+                counterparty: Some("Bob Smith".to_string()),
+                memo: Some("аренда, январь #1 ~100%".to_string()),
+            },
+        };
+        let line = transfer.to_string();
+        assert_eq!(
+            line,
+            "T-:30#5^2026-01-31T09:05:00Z&Bob%20Smith~аренда%2C%20январь%20%231%20%7E100%25"
+        );
+        assert_eq!(line.parse::<Operation>(), Ok(transfer));
+
+        // обмен содержит `>` и `@`, но не символы реквизитов
+        let exchange: Operation = "X:1000:USD>92500:RUB@92.5~fx".parse().unwrap();
+        assert_eq!(exchange.meta.memo.as_deref(), Some("fx"));
+        assert_eq!(exchange.meta.tx, None);
+
+        assert!("D:1~a#2".parse::<Operation>().is_err());
+        assert!("D:1#2#3".parse::<Operation>().is_err());
+        assert!("D:1^2026-01-31".parse::<Operation>().is_err());
+        assert!("D:1&%ZZ".parse::<Operation>().is_err());
     }
 }
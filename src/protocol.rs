@@ -0,0 +1,706 @@
+use crate::Name;
+use crate::clock::{Clock, Date, SystemClock};
+use crate::errors::report;
+use crate::interest::{DayCount, InterestEngine, InterestMode};
+use crate::money::{Currency, Money};
+use crate::operations::OpKind;
+use crate::schedule::{OrderId, RetryPolicy, Schedule};
+use crate::shared::SharedStorage;
+use crate::transaction::{Deposit, Exchange, Transfer, Withdraw};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+
+/// Команда строкового протокола — те же команды, что у `utils`:
+/// `add <name> <balance> [валюта]`, `remove <name>`, `deposit <name> <amount>`,
+/// `withdraw <name> <amount>`, `transfer <from> <to> <amount>`,
+/// `exchange <name> <amount> <из> <в>`, `limit <name> <amount>`,
+/// `interest <name> <ставка> [simple|compound] [act/365|act/360|30/360]`, `accrue`,
+/// `order <from> <to> <amount> <once|every:N|monthly:D> [начало] [повторы:интервал]`,
+/// `cancel <номер>`, `close <name> [получатель остатка]`, `balance <name>`,
+/// `+ deposit <name> <amount> transfer <from> <to> <amount>`, `exit`.
+///
+/// Суммы хранятся строкой: в какой валюте их читать, известно только по счёту.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Add {
+        name: Name,
+        balance: String,
+        currency: Currency,
+    },
+    /// Удалить счёт без следа
+    Remove(Name),
+    /// Закрыть счёт, переведя остаток получателю
+    Close {
+        name: Name,
+        payout: Option<Name>,
+    },
+    Deposit {
+        name: Name,
+        amount: String,
+    },
+    Withdraw {
+        name: Name,
+        amount: String,
+    },
+    Transfer {
+        from: Name,
+        to: Name,
+        amount: String,
+    },
+    Exchange {
+        name: Name,
+        amount: String,
+        from: Currency,
+        to: Currency,
+    },
+    /// Кредитный лимит счёта в его валюте
+    Limit {
+        name: Name,
+        amount: String,
+    },
+    /// Годовая ставка по счёту; начисление идёт с сегодняшнего дня
+    Interest {
+        name: Name,
+        rate: String,
+        mode: InterestMode,
+        day_count: DayCount,
+    },
+    /// Начислить проценты по всем счетам по сегодняшний день
+    Accrue,
+    /// Платёжное поручение; без даты начала — с сегодняшнего дня
+    Order {
+        from: Name,
+        to: Name,
+        amount: String,
+        schedule: Schedule,
+        start: Option<Date>,
+        retry: RetryPolicy,
+    },
+    /// Отменить платёжное поручение
+    Cancel(OrderId),
+    Balance(Name),
+    /// Пополнение и перевод одной транзакцией
+    Combined {
+        deposit: Name,
+        deposit_amount: String,
+        from: Name,
+        to: Name,
+        transfer_amount: String,
+    },
+    Exit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        let currency = |code: &str| code.parse::<Currency>().map_err(|e| e.to_string());
+        let command = match args.as_slice() {
+            ["add", name, balance] => Command::Add {
+                name: name.to_string(),
+                balance: balance.to_string(),
+                currency: Currency::default(),
+            },
+            ["add", name, balance, code] => Command::Add {
+                name: name.to_string(),
+                balance: balance.to_string(),
+                currency: currency(code)?,
+            },
+            ["remove", name] => Command::Remove(name.to_string()),
+            ["close", name] => Command::Close {
+                name: name.to_string(),
+                payout: None,
+            },
+            ["close", name, payout] => Command::Close {
+                name: name.to_string(),
+                payout: Some(payout.to_string()),
+            },
+            ["deposit", name, amount] => Command::Deposit {
+                name: name.to_string(),
+                amount: amount.to_string(),
+            },
+            ["withdraw", name, amount] => Command::Withdraw {
+                name: name.to_string(),
+                amount: amount.to_string(),
+            },
+            ["transfer", from, to, amount] => Command::Transfer {
+                from: from.to_string(),
+                to: to.to_string(),
+                amount: amount.to_string(),
+            },
+            ["exchange", name, amount, from, to] => Command::Exchange {
+                name: name.to_string(),
+                amount: amount.to_string(),
+                from: currency(from)?,
+                to: currency(to)?,
+            },
+            ["limit", name, amount] => Command::Limit {
+                name: name.to_string(),
+                amount: amount.to_string(),
+            },
+            ["interest", name, rate, rest @ ..] if rest.len() <= 2 => Command::Interest {
+                name: name.to_string(),
+                rate: rate.to_string(),
+                mode: match rest.first() {
+                    Some(mode) => mode.parse()?,
+                    None => InterestMode::Compound,
+                },
+                day_count: match rest.get(1) {
+                    Some(day_count) => day_count.parse()?,
+                    None => DayCount::Actual365,
+                },
+            },
+            ["accrue"] => Command::Accrue,
+            ["order", from, to, amount, schedule, rest @ ..] if rest.len() <= 2 => Command::Order {
+                from: from.to_string(),
+                to: to.to_string(),
+                amount: amount.to_string(),
+                schedule: schedule.parse()?,
+                start: rest.first().map(|date| date.parse()).transpose()?,
+                retry: match rest.get(1) {
+                    Some(retry) => retry.parse()?,
+                    None => RetryPolicy::default(),
+                },
+            },
+            ["cancel", id] => Command::Cancel(
+                id.parse()
+                    .map_err(|_| "Номер поручения должен быть числом".to_string())?,
+            ),
+            ["balance", name] => Command::Balance(name.to_string()),
+            [
+                "+",
+                "deposit",
+                name,
+                amount,
+                "transfer",
+                from,
+                to,
+                transfer_amount,
+            ] => Command::Combined {
+                deposit: name.to_string(),
+                deposit_amount: amount.to_string(),
+                from: from.to_string(),
+                to: to.to_string(),
+                transfer_amount: transfer_amount.to_string(),
+            },
+            ["exit"] => Command::Exit,
+            [] => return Err("Пустая команда".to_string()),
+            [command, ..] => return Err(usage(command)),
+        };
+        Ok(command)
+    }
+}
+
+/// Подсказка для неверно записанной команды
+fn usage(command: &str) -> String {
+    let example = match command {
+        "add" => "add John 100 или add John 100 USD",
+        "remove" => "remove John",
+        "close" => "close John или close John Alice",
+        "deposit" => "deposit John 100",
+        "withdraw" => "withdraw John 100",
+        "transfer" => "transfer Alice Bob 100",
+        "exchange" => "exchange John 100 RUB USD",
+        "limit" => "limit John 500",
+        "interest" => "interest John 0.05 compound act/365",
+        "accrue" => "accrue",
+        "order" => "order Alice Bob 100 monthly:1 2026-01-01 3:1",
+        "cancel" => "cancel 1",
+        "balance" => "balance John",
+        "+" => "+ deposit Alice 100 transfer Alice Bob 30",
+        _ => return "Неизвестная команда".to_string(),
+    };
+    format!("Пример: {}", example)
+}
+
+/// Ответ на команду: одна строка, начинающаяся с `OK` или `ERR`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Ok(String),
+    Err(String),
+}
+
+impl std::fmt::Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Response::Ok(message) => write!(f, "OK {}", message),
+            Response::Err(message) => write!(f, "ERR {}", message),
+        }
+    }
+}
+
+/// Сумма в основных единицах ("12.50") в валюте счёта `name`;
+/// для ещё не открытого счёта — в базовой валюте
+fn parse_amount(storage: &SharedStorage, name: &Name, value: &str) -> Result<Money, String> {
+    let currency = storage
+        .get_balance(name)
+        .map(|b| b.currency)
+        .unwrap_or_default();
+    Money::parse_major(value, currency).map_err(|_| "Сумма должна быть числом".to_string())
+}
+
+impl Command {
+    /// Выполняет команду над общим хранилищем
+    pub fn execute(self, storage: &SharedStorage) -> Response {
+        match self.run(storage) {
+            Ok(message) => Response::Ok(message),
+            Err(message) => Response::Err(message),
+        }
+    }
+
+    fn run(self, storage: &SharedStorage) -> Result<String, String> {
+        match self {
+            Command::Add {
+                name,
+                balance,
+                currency,
+            } => {
+                let balance = Money::parse_major(&balance, currency)
+                    .map_err(|_| "Сумма должна быть числом".to_string())?;
+                storage
+                    .create_account(&name, currency, Some(balance))
+                    .map_err(|e| report(&e))?;
+                Ok(format!(
+                    "Пользователь {} добавлен с балансом {}",
+                    name, balance
+                ))
+            }
+            Command::Remove(name) => {
+                storage.remove_user(&name).map_err(|e| report(&e))?;
+                Ok(format!("Пользователь {} удалён", name))
+            }
+            Command::Close { name, payout } => {
+                let paid = storage
+                    .close_account(&name, payout.as_ref())
+                    .map_err(|e| report(&e))?;
+                match payout {
+                    Some(payout) if !paid.amount.is_zero() => Ok(format!(
+                        "Счёт {} закрыт, остаток {} переведён {}",
+                        name, paid, payout
+                    )),
+                    _ => Ok(format!("Счёт {} закрыт", name)),
+                }
+            }
+            Command::Deposit { name, amount } => {
+                let amount = parse_amount(storage, &name, &amount)?;
+                storage
+                    .apply(&Deposit {
+                        account: name.clone(),
+                        amount,
+                    })
+                    .map_err(|e| format!("Ошибка транзакции: {}", report(&e)))?;
+                Ok(format!("Транзакция: депозит {} на {}", name, amount))
+            }
+            Command::Withdraw { name, amount } => {
+                let amount = parse_amount(storage, &name, &amount)?;
+                storage
+                    .apply(&Withdraw {
+                        account: name.clone(),
+                        amount,
+                    })
+                    .map_err(|e| format!("Ошибка транзакции: {}", report(&e)))?;
+                Ok(format!(
+                    "Транзакция: с баланса пользователя {} снято {}",
+                    name, amount
+                ))
+            }
+            Command::Transfer { from, to, amount } => {
+                let amount = parse_amount(storage, &from, &amount)?;
+                storage
+                    .apply(&Transfer {
+                        from: from.clone(),
+                        to: to.clone(),
+                        amount,
+                    })
+                    .map_err(|e| format!("Ошибка транзакции: {}", report(&e)))?;
+                Ok(format!(
+                    "Транзакция: перевод {} -> {} на {}",
+                    from, to, amount
+                ))
+            }
+            Command::Exchange {
+                name,
+                amount,
+                from,
+                to,
+            } => {
+                let amount = Money::parse_major(&amount, from)
+                    .map_err(|_| "Сумма должна быть числом".to_string())?;
+                storage
+                    .apply(&Exchange {
+                        account: name.clone(),
+                        amount,
+                        to,
+                    })
+                    .map_err(|e| format!("Ошибка транзакции: {}", report(&e)))?;
+                let balance = storage.get_balance(&name).unwrap_or_default();
+                match balance.last_ops.last().map(|op| &op.kind) {
+                    Some(OpKind::Exchange { sold, bought, rate }) => Ok(format!(
+                        "Транзакция: {} обменял {} на {} по курсу {}",
+                        name, sold, bought, rate
+                    )),
+                    _ => Ok(format!("Транзакция: обмен у {}", name)),
+                }
+            }
+            Command::Limit { name, amount } => {
+                let limit = parse_amount(storage, &name, &amount)?;
+                storage
+                    .set_credit_limit(&name, limit.amount)
+                    .map_err(|e| report(&e))?;
+                Ok(format!("Кредитный лимит {}: {}", name, limit))
+            }
+            Command::Interest {
+                name,
+                rate,
+                mode,
+                day_count,
+            } => {
+                let engine = InterestEngine::new(SystemClock);
+                let terms = engine.terms(rate.parse()?, mode, day_count);
+                // то, что набежало по старым условиям, начисляем по ним
+                engine.run_shared(storage).map_err(|e| report(&e))?;
+                storage
+                    .set_interest(&name, Some(terms))
+                    .map_err(|e| report(&e))?;
+                Ok(format!(
+                    "Проценты {}: {} годовых, {}, {}",
+                    name, terms.rate, terms.mode, terms.day_count
+                ))
+            }
+            Command::Accrue => {
+                let accrued = InterestEngine::new(SystemClock)
+                    .run_shared(storage)
+                    .map_err(|e| report(&e))?;
+                if accrued.is_empty() {
+                    return Ok("Начислять нечего".to_string());
+                }
+                let accrued: Vec<String> = accrued
+                    .iter()
+                    .map(|(name, money)| format!("{} {}", name, money))
+                    .collect();
+                Ok(format!("Начислены проценты: {}", accrued.join(", ")))
+            }
+            Command::Order {
+                from,
+                to,
+                amount,
+                schedule,
+                start,
+                retry,
+            } => {
+                let amount = parse_amount(storage, &from, &amount)?;
+                let transfer = Transfer { from, to, amount };
+                let start = start.unwrap_or_else(|| SystemClock.today());
+                let id = storage
+                    .add_order(transfer, schedule, start, retry)
+                    .map_err(|e| report(&e))?;
+                let due = storage
+                    .orders()
+                    .into_iter()
+                    .find(|order| order.id == id)
+                    .map(|order| order.due)
+                    .unwrap_or(start);
+                Ok(format!(
+                    "Поручение №{}: {}, первый перевод {}",
+                    id, schedule, due
+                ))
+            }
+            Command::Cancel(id) => {
+                storage.cancel_order(id).map_err(|e| report(&e))?;
+                Ok(format!("Поручение №{} отменено", id))
+            }
+            Command::Balance(name) => match storage.get_balance(&name) {
+                Some(b) => {
+                    let all: Vec<String> = b.all_money().iter().map(|m| m.to_string()).collect();
+                    let mut message = format!("Баланс {}: {}", name, all.join(", "));
+                    if !b.credit_limit.is_zero() {
+                        message.push_str(&format!(
+                            " (кредитный лимит {})",
+                            Money {
+                                amount: b.credit_limit,
+                                currency: b.currency,
+                            }
+                        ));
+                    }
+                    if b.is_closed() {
+                        message.push_str(" (счёт закрыт)");
+                    }
+                    Ok(message)
+                }
+                None => Err(format!("Пользователь {} не найден", name)),
+            },
+            Command::Combined {
+                deposit,
+                deposit_amount,
+                from,
+                to,
+                transfer_amount,
+            } => {
+                let deposit_amount = parse_amount(storage, &deposit, &deposit_amount)?;
+                let transfer_amount = parse_amount(storage, &from, &transfer_amount)?;
+                let tx = Deposit {
+                    account: deposit,
+                    amount: deposit_amount,
+                } + Transfer {
+                    from,
+                    to,
+                    amount: transfer_amount,
+                };
+                storage
+                    .apply(&tx)
+                    .map_err(|e| format!("Ошибка при выполнении: {}", report(&e)))?;
+                Ok("Комбинированная транзакция выполнена!".to_string())
+            }
+            Command::Exit => Ok("До свидания".to_string()),
+        }
+    }
+}
+
+/// Обслуживает одного клиента: читает команды построчно и отвечает на каждую
+/// одной строкой, пока клиент не пришлёт `exit` или не закроет соединение
+pub fn handle_client(stream: TcpStream, storage: &SharedStorage) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, exit) = match line.parse::<Command>() {
+            Ok(command) => {
+                let exit = command == Command::Exit;
+                (command.execute(storage), exit)
+            }
+            Err(message) => (Response::Err(message), false),
+        };
+        writeln!(writer, "{}", response)?;
+        if exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            "add John 100 USD".parse(),
+            Ok(Command::Add {
+                name: "John".to_string(),
+                balance: "100".to_string(),
+                currency: Currency::USD,
+            })
+        );
+        assert_eq!(
+            "  transfer Alice  Bob 5 ".parse(),
+            Ok(Command::Transfer {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                amount: "5".to_string(),
+            })
+        );
+        assert_eq!(
+            "transfer Alice".parse::<Command>(),
+            Err("Пример: transfer Alice Bob 100".to_string())
+        );
+        assert_eq!(
+            "fly away".parse::<Command>(),
+            Err("Неизвестная команда".to_string())
+        );
+        assert!("add John 100 usd".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn execute_commands() {
+        let storage = SharedStorage::new(Storage::new());
+        let run = |line: &str| line.parse::<Command>().unwrap().execute(&storage);
+
+        assert!(matches!(run("add Alice 10.50"), Response::Ok(_)));
+        assert_eq!(
+            run("add Alice 1"),
+            Response::Err("Пользователь 'Alice' уже существует".to_string())
+        );
+        assert!(matches!(run("add Evil,Name 1"), Response::Err(_)));
+        assert!(matches!(run("transfer Alice Bob 3"), Response::Err(_)));
+        assert!(matches!(run("add Bob 0"), Response::Ok(_)));
+        assert!(matches!(run("transfer Alice Bob 3"), Response::Ok(_)));
+        assert!(matches!(run("transfer Alice Alice 3"), Response::Err(_)));
+        assert_eq!(
+            run("balance Alice"),
+            Response::Ok("Баланс Alice: 7.50 RUB".to_string())
+        );
+        assert!(matches!(run("withdraw Bob 5"), Response::Err(_)));
+        assert!(matches!(
+            run("+ deposit Alice 1 transfer Alice Bob 100"),
+            Response::Err(_)
+        ));
+        assert_eq!(
+            run("balance Alice"),
+            Response::Ok("Баланс Alice: 7.50 RUB".to_string())
+        );
+        assert_eq!(
+            run("remove Bob"),
+            Response::Err(
+                "У 'Bob' были переводы с другими счетами: удалить его нельзя, только закрыть"
+                    .to_string()
+            )
+        );
+        assert!(matches!(run("add Carl 0"), Response::Ok(_)));
+        assert!(matches!(run("remove Carl"), Response::Ok(_)));
+        assert!(matches!(run("balance Carl"), Response::Err(_)));
+    }
+
+    #[test]
+    fn execute_close() {
+        let storage = SharedStorage::new(Storage::new());
+        let run = |line: &str| line.parse::<Command>().unwrap().execute(&storage);
+
+        assert!(matches!(run("add Alice 10"), Response::Ok(_)));
+        assert!(matches!(run("add Bob 0"), Response::Ok(_)));
+        assert_eq!(
+            run("close Alice"),
+            Response::Err("Нельзя закрыть счёт 'Alice': на нём остаётся 10.00 RUB".to_string())
+        );
+        assert_eq!(
+            run("close Alice Bob"),
+            Response::Ok("Счёт Alice закрыт, остаток 10.00 RUB переведён Bob".to_string())
+        );
+        assert_eq!(
+            run("balance Alice"),
+            Response::Ok("Баланс Alice: 0.00 RUB (счёт закрыт)".to_string())
+        );
+        assert_eq!(
+            run("deposit Alice 1"),
+            Response::Err("Ошибка транзакции: Счёт 'Alice' закрыт".to_string())
+        );
+        assert!(matches!(run("close Bob Alice"), Response::Err(_)));
+    }
+
+    #[test]
+    fn execute_credit_limit() {
+        let storage = SharedStorage::new(Storage::new());
+        let run = |line: &str| line.parse::<Command>().unwrap().execute(&storage);
+
+        assert!(matches!(run("add Alice 10"), Response::Ok(_)));
+        assert!(matches!(run("withdraw Alice 15"), Response::Err(_)));
+        assert!(matches!(run("limit Alice 20"), Response::Ok(_)));
+        assert!(matches!(run("withdraw Alice 15"), Response::Ok(_)));
+        assert_eq!(
+            run("balance Alice"),
+            Response::Ok("Баланс Alice: -5.00 RUB (кредитный лимит 20.00 RUB)".to_string())
+        );
+        assert!(matches!(run("limit Alice 1"), Response::Err(_)));
+        assert!(matches!(run("limit Bob 1"), Response::Err(_)));
+    }
+
+    #[test]
+    fn parse_and_execute_interest() {
+        assert_eq!(
+            "interest John 0.05 simple 30/360".parse(),
+            Ok(Command::Interest {
+                name: "John".to_string(),
+                rate: "0.05".to_string(),
+                mode: InterestMode::Simple,
+                day_count: DayCount::Thirty360,
+            })
+        );
+        assert!("interest John 0.05 weekly".parse::<Command>().is_err());
+
+        let storage = SharedStorage::new(Storage::new());
+        let run = |line: &str| line.parse::<Command>().unwrap().execute(&storage);
+        assert!(matches!(run("add Alice 100"), Response::Ok(_)));
+        assert!(matches!(run("interest Alice 0.1"), Response::Ok(_)));
+        assert!(matches!(run("interest Alice 1.2.3"), Response::Err(_)));
+        assert!(matches!(run("interest Bob 0.1"), Response::Err(_)));
+        // в тот же день начислять нечего
+        assert_eq!(run("accrue"), Response::Ok("Начислять нечего".to_string()));
+        assert!(
+            storage
+                .get_balance(&"Alice".to_string())
+                .unwrap()
+                .interest
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn parse_and_execute_orders() {
+        assert_eq!(
+            "order Alice Bob 10 every:7 2026-01-01 2:1".parse(),
+            Ok(Command::Order {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                amount: "10".to_string(),
+                schedule: Schedule::EveryDays(7),
+                start: "2026-01-01".parse().ok(),
+                retry: RetryPolicy {
+                    retries: 2,
+                    every_days: 1,
+                },
+            })
+        );
+        assert!("order Alice Bob 10 weekly".parse::<Command>().is_err());
+        assert!("cancel first".parse::<Command>().is_err());
+
+        let storage = SharedStorage::new(Storage::new());
+        let run = |line: &str| line.parse::<Command>().unwrap().execute(&storage);
+        assert!(matches!(run("add Alice 100"), Response::Ok(_)));
+        assert!(matches!(run("add Bob 0"), Response::Ok(_)));
+        assert_eq!(
+            run("order Alice Bob 10 monthly:1 2026-01-15"),
+            Response::Ok("Поручение №1: monthly:1, первый перевод 2026-02-01".to_string())
+        );
+        assert!(matches!(run("order Alice Carl 10 once"), Response::Err(_)));
+        assert_eq!(
+            run("cancel 1"),
+            Response::Ok("Поручение №1 отменено".to_string())
+        );
+        assert!(matches!(run("cancel 1"), Response::Err(_)));
+    }
+
+    #[test]
+    fn serve_several_clients() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let storage = SharedStorage::new(Storage::new());
+        storage.add_user("Alice".to_string());
+
+        let server = {
+            let storage = storage.clone();
+            thread::spawn(move || {
+                let clients: Vec<_> = listener
+                    .incoming()
+                    .take(2)
+                    .map(|stream| {
+                        let storage = storage.clone();
+                        thread::spawn(move || handle_client(stream.unwrap(), &storage))
+                    })
+                    .collect();
+                for client in clients {
+                    client.join().unwrap().unwrap();
+                }
+            })
+        };
+
+        let talk = |lines: &str| -> Vec<String> {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(lines.as_bytes()).unwrap();
+            BufReader::new(stream).lines().map(|l| l.unwrap()).collect()
+        };
+        let first = talk("deposit Alice 5\nexit\n");
+        let second = talk("deposit Alice 2\nbalance Alice\nexit\n");
+        server.join().unwrap();
+
+        assert_eq!(first[0], "OK Транзакция: депозит Alice на 5.00 RUB");
+        assert_eq!(second[1], "OK Баланс Alice: 7.00 RUB");
+        assert_eq!(second.last().unwrap(), "OK До свидания");
+    }
+}
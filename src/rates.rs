@@ -0,0 +1,318 @@
+use crate::errors::{LineError, LoadError, RejectedLine};
+use crate::money::{Amount, Currency, Money};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+/// Курс обмена с фиксированной точностью: сколько единиц одной валюты дают за единицу другой.
+/// Хранится в миллионных долях, так что "92.5" — это `Rate(92_500_000)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rate(u64);
+
+impl Rate {
+    /// Число знаков после запятой в курсе
+    pub const DIGITS: u32 = 6;
+    const SCALE: u64 = 10u64.pow(Self::DIGITS);
+
+    pub const fn from_scaled(value: u64) -> Self {
+        Rate(value)
+    }
+
+    pub const fn scaled(self) -> u64 {
+        self.0
+    }
+}
+
+/// Курс без лишних нулей: `92.5`, `1`, `0.010811`
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / Self::SCALE;
+        let fraction = self.0 % Self::SCALE;
+        if fraction == 0 {
+            return write!(f, "{}", whole);
+        }
+        let fraction = format!("{:0width$}", fraction, width = Self::DIGITS as usize);
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("некорректный курс '{}'", s);
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if whole.is_empty()
+            || fraction.len() > Self::DIGITS as usize
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(bad());
+        }
+        let whole: u64 = whole.parse().map_err(|_| bad())?;
+        let fraction: u64 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<u64>().map_err(|_| bad())?
+                * 10u64.pow(Self::DIGITS - fraction.len() as u32)
+        };
+        let value = whole
+            .checked_mul(Self::SCALE)
+            .and_then(|v| v.checked_add(fraction))
+            .filter(|v| *v > 0)
+            .ok_or_else(bad)?;
+        Ok(Rate(value))
+    }
+}
+
+/// Как округлять результат обмена до минимальных единиц
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Отбрасываем остаток: клиент никогда не получает больше, чем по курсу
+    #[default]
+    Down,
+    /// Половина и больше — вверх
+    HalfUp,
+    /// Ровно половина — к чётному ("банковское" округление)
+    HalfEven,
+}
+
+impl Rounding {
+    pub(crate) fn divide(self, num: u128, den: u128) -> u128 {
+        let (quotient, remainder) = (num / den, num % den);
+        let round_up = match self {
+            Rounding::Down => false,
+            Rounding::HalfUp => remainder * 2 >= den,
+            Rounding::HalfEven => {
+                remainder * 2 > den || (remainder * 2 == den && quotient % 2 == 1)
+            }
+        };
+        quotient + round_up as u128
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "down" => Ok(Rounding::Down),
+            "half-up" => Ok(Rounding::HalfUp),
+            "half-even" => Ok(Rounding::HalfEven),
+            other => Err(format!("неизвестное правило округления '{}'", other)),
+        }
+    }
+}
+
+/// Таблица курсов. Курс `A -> B` означает, сколько B дают за одну единицу A;
+/// если прямого курса нет, используется обратный к `B -> A`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateTable {
+    rates: HashMap<(Currency, Currency), Rate>,
+    pub rounding: Rounding,
+}
+
+impl RateTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, from: Currency, to: Currency, rate: Rate) {
+        self.rates.insert((from, to), rate);
+    }
+
+    pub fn get(&self, from: Currency, to: Currency) -> Option<Rate> {
+        self.rates.get(&(from, to)).copied()
+    }
+
+    /// Пересчитывает `amount` в валюту `to`. Возвращает полученную сумму и
+    /// применённый курс (сколько `to` за единицу исходной валюты).
+    /// `None`, если курса нет или результат не помещается в сумму.
+    pub fn convert(&self, amount: Money, to: Currency) -> Option<(Money, Rate)> {
+        let from = amount.currency;
+        if from == to {
+            return None;
+        }
+
+        // Суммы в минимальных единицах, поэтому учитываем разницу в числе знаков валют
+        let value = amount.amount.value() as u128;
+        let to_scale = 10u128.pow(to.minor_digits());
+        let from_scale = 10u128.pow(from.minor_digits());
+        let rate_scale = Rate::SCALE as u128;
+
+        let (rate, num, den) = if let Some(rate) = self.get(from, to) {
+            let num = value.checked_mul(rate.0 as u128)?.checked_mul(to_scale)?;
+            (rate, num, rate_scale * from_scale)
+        } else {
+            let inverse = self.get(to, from)?;
+            let num = value.checked_mul(rate_scale)?.checked_mul(to_scale)?;
+            // в историю пишем эффективный курс в прямом направлении
+            let rate = Rounding::HalfUp.divide(rate_scale * rate_scale, inverse.0 as u128);
+            (
+                Rate(u64::try_from(rate).ok()?),
+                num,
+                inverse.0 as u128 * from_scale,
+            )
+        };
+
+        let converted = u64::try_from(self.rounding.divide(num, den)).ok()?;
+        Some((
+            Money {
+                amount: Amount::new(converted),
+                currency: to,
+            },
+            rate,
+        ))
+    }
+
+    /// Загружает таблицу из файла со строками "FROM,TO,RATE", например "USD,RUB,92.5".
+    /// Строка "rounding,<down|half-up|half-even>" задаёт правило округления,
+    /// строки, начинающиеся с `#`, и пустые строки пропускаются.
+    pub fn load(file: &str) -> Result<RateTable, LoadError> {
+        Self::from_reader(io::BufReader::new(File::open(file)?))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<RateTable, LoadError> {
+        let mut table = RateTable::new();
+        let mut rejected = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(reason) = table.parse_line(line) {
+                rejected.push(RejectedLine {
+                    line: index + 1,
+                    reason,
+                });
+            }
+        }
+
+        if !rejected.is_empty() {
+            return Err(LoadError::Rejected(rejected));
+        }
+        Ok(table)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), LineError> {
+        let parts: Vec<&str> = line.split(',').map(str::trim).collect();
+        match parts.as_slice() {
+            ["rounding", mode] => {
+                self.rounding = mode.parse().map_err(LineError::BadRate)?;
+            }
+            [from, to, rate] => {
+                let currency = |code: &str| {
+                    code.parse::<Currency>()
+                        .map_err(|_| LineError::BadCurrency(code.to_string()))
+                };
+                let (from, to) = (currency(from)?, currency(to)?);
+                let rate = rate.parse().map_err(LineError::BadRate)?;
+                self.set(from, to, rate);
+            }
+            _ => {
+                return Err(LineError::FieldCount {
+                    expected: 3,
+                    found: parts.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn usd(amount: u64) -> Money {
+        Money::new(amount, Currency::USD)
+    }
+
+    #[test]
+    fn rate_parse_and_display() {
+        let rate: Rate = "92.5".parse().unwrap();
+        assert_eq!(rate.scaled(), 92_500_000);
+        assert_eq!(rate.to_string(), "92.5");
+        assert_eq!("1".parse::<Rate>().unwrap().to_string(), "1");
+        assert!("0".parse::<Rate>().is_err());
+        assert!("1.1234567".parse::<Rate>().is_err());
+        assert!("-1".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn convert_direct_and_inverse() {
+        let mut table = RateTable::new();
+        table.set(Currency::USD, Currency::RUB, "92.5".parse().unwrap());
+
+        // 10.00 USD -> 925.00 RUB
+        let (rub, rate) = table.convert(usd(1000), Currency::RUB).unwrap();
+        assert_eq!(rub, Money::new(92500, Currency::RUB));
+        assert_eq!(rate.to_string(), "92.5");
+
+        // 100.00 RUB -> 100 / 92.5 = 1.08108... USD
+        let (back, rate) = table
+            .convert(Money::new(10000, Currency::RUB), Currency::USD)
+            .unwrap();
+        assert_eq!(back, usd(108));
+        assert_eq!(rate.to_string(), "0.010811");
+
+        assert_eq!(table.convert(usd(1), Currency::EUR), None);
+        assert_eq!(table.convert(usd(1), Currency::USD), None);
+    }
+
+    #[test]
+    fn convert_rounding_rules() {
+        let mut table = RateTable::new();
+        table.set(Currency::USD, Currency::EUR, "0.5".parse().unwrap());
+
+        // 0.05 USD * 0.5 = 0.025 EUR
+        let eur = |table: &RateTable| table.convert(usd(5), Currency::EUR).unwrap().0;
+        assert_eq!(eur(&table).amount, 2);
+        table.rounding = Rounding::HalfUp;
+        assert_eq!(eur(&table).amount, 3);
+        table.rounding = Rounding::HalfEven;
+        assert_eq!(eur(&table).amount, 2);
+        // 0.07 * 0.5 = 0.035 -> к чётному 0.04
+        assert_eq!(table.convert(usd(7), Currency::EUR).unwrap().0.amount, 4);
+    }
+
+    #[test]
+    fn convert_between_precisions() {
+        let jpy: Currency = "JPY".parse().unwrap();
+        let mut table = RateTable::new();
+        table.set(Currency::USD, jpy, "150".parse().unwrap());
+
+        // 1.00 USD -> 150 JPY, у иены нет копеек
+        let (yen, _) = table.convert(usd(100), jpy).unwrap();
+        assert_eq!(yen, Money::new(150, jpy));
+        let (dollars, _) = table.convert(Money::new(300, jpy), Currency::USD).unwrap();
+        assert_eq!(dollars, usd(200));
+    }
+
+    #[test]
+    fn load_table_from_reader() {
+        let data = "# курсы на сегодня\nUSD,RUB,92.5\nrounding,half-up\nEUR,RUB,100\n";
+        let table = RateTable::from_reader(Cursor::new(data)).unwrap();
+        assert_eq!(table.rounding, Rounding::HalfUp);
+        assert_eq!(
+            table.get(Currency::EUR, Currency::RUB),
+            Some(Rate::from_scaled(100_000_000))
+        );
+
+        let data = b"USD,RUB,abc\nUSD,XX,1\nrounding,up\nUSD\n";
+        let Err(LoadError::Rejected(rejected)) = RateTable::from_reader(Cursor::new(&data[..]))
+        else {
+            panic!("Ожидалась ошибка Rejected");
+        };
+        let lines: Vec<usize> = rejected.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4]);
+        assert_eq!(rejected[1].reason, LineError::BadCurrency("XX".to_string()));
+    }
+}
@@ -0,0 +1,764 @@
+use crate::Name;
+use crate::clock::Date;
+use crate::errors::BankError;
+use crate::money::Money;
+use crate::storage::{AccountPolicy, Storage, check_name};
+use crate::transaction::{Transaction, Transfer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// Номер платёжного поручения, уникальный в пределах хранилища
+pub type OrderId = u64;
+
+/// Когда исполнять поручение
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Один раз
+    Once,
+    /// Каждые `n` дней
+    EveryDays(u32),
+    /// Каждый месяц в день `day`; в коротких месяцах — в последний день месяца
+    Monthly(u32),
+}
+
+impl Schedule {
+    /// Первый срок по расписанию не раньше `start`
+    pub fn first_on_or_after(self, start: Date) -> Date {
+        match self {
+            Schedule::Monthly(day) => {
+                let due = start.in_month(day);
+                if due < start {
+                    start.next_month().in_month(day)
+                } else {
+                    due
+                }
+            }
+            Schedule::Once | Schedule::EveryDays(_) => start,
+        }
+    }
+
+    /// Срок, следующий за `due`; `None` для разового поручения
+    pub fn next_after(self, due: Date) -> Option<Date> {
+        match self {
+            Schedule::Once => None,
+            Schedule::EveryDays(days) => Some(due.add_days(i64::from(days))),
+            Schedule::Monthly(day) => Some(due.next_month().in_month(day)),
+        }
+    }
+}
+
+/// `once`, `every:7`, `monthly:1`
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Once => write!(f, "once"),
+            Schedule::EveryDays(days) => write!(f, "every:{}", days),
+            Schedule::Monthly(day) => write!(f, "monthly:{}", day),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("некорректное расписание '{}'", s);
+        let number = |value: &str| value.parse::<u32>().map_err(|_| bad());
+        match s.split_once(':') {
+            None if s == "once" => Ok(Schedule::Once),
+            Some(("every", days)) => match number(days)? {
+                0 => Err(bad()),
+                days => Ok(Schedule::EveryDays(days)),
+            },
+            Some(("monthly", day)) => match number(day)? {
+                day @ 1..=31 => Ok(Schedule::Monthly(day)),
+                _ => Err(bad()),
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// Сколько раз и с каким интервалом повторять неудавшийся перевод.
+/// Когда повторы кончились, срок пропускается и поручение ждёт следующего.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub every_days: u32,
+}
+
+impl Default for RetryPolicy {
+    /// Без повторов
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            every_days: 1,
+        }
+    }
+}
+
+/// `3:1` — три повтора раз в день
+impl fmt::Display for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.retries, self.every_days)
+    }
+}
+
+impl FromStr for RetryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || format!("некорректная политика повторов '{}'", s);
+        let (retries, every_days) = s.split_once(':').ok_or_else(bad)?;
+        let policy = RetryPolicy {
+            retries: retries.parse().map_err(|_| bad())?,
+            every_days: every_days.parse().map_err(|_| bad())?,
+        };
+        if policy.every_days == 0 {
+            return Err(bad());
+        }
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Active,
+    /// Разовое поручение исполнено
+    Completed,
+    /// Разовое поручение не удалось исполнить и повторы кончились
+    Failed,
+    Cancelled,
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OrderStatus::Active => "active",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Failed => "failed",
+            OrderStatus::Cancelled => "cancelled",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(OrderStatus::Active),
+            "completed" => Ok(OrderStatus::Completed),
+            "failed" => Ok(OrderStatus::Failed),
+            "cancelled" => Ok(OrderStatus::Cancelled),
+            other => Err(format!("неизвестное состояние поручения '{}'", other)),
+        }
+    }
+}
+
+/// Почему перевод по поручению не прошёл
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    InsufficientFunds,
+    AccountNotFound,
+    AccountClosed,
+    Other,
+}
+
+impl From<&BankError> for FailureKind {
+    fn from(err: &BankError) -> Self {
+        match err.root() {
+            BankError::NotEnoughMoney { .. } => FailureKind::InsufficientFunds,
+            BankError::UserNotFound(_) => FailureKind::AccountNotFound,
+            BankError::AccountClosed(_) => FailureKind::AccountClosed,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+/// Неудачная попытка исполнения: `2026-02-01:nsf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderFailure {
+    pub date: Date,
+    pub kind: FailureKind,
+}
+
+impl fmt::Display for OrderFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            FailureKind::InsufficientFunds => "nsf",
+            FailureKind::AccountNotFound => "no_account",
+            FailureKind::AccountClosed => "closed",
+            FailureKind::Other => "other",
+        };
+        write!(f, "{}:{}", self.date, kind)
+    }
+}
+
+impl FromStr for OrderFailure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (date, kind) = s
+            .split_once(':')
+            .ok_or_else(|| format!("некорректная ошибка поручения '{}'", s))?;
+        let kind = match kind {
+            "nsf" => FailureKind::InsufficientFunds,
+            "no_account" => FailureKind::AccountNotFound,
+            "closed" => FailureKind::AccountClosed,
+            "other" => FailureKind::Other,
+            other => return Err(format!("неизвестная ошибка поручения '{}'", other)),
+        };
+        Ok(OrderFailure {
+            date: date.parse()?,
+            kind,
+        })
+    }
+}
+
+/// Платёжное поручение: перевод `amount` со счёта `from` на счёт `to` по расписанию
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandingOrder {
+    pub id: OrderId,
+    pub from: Name,
+    pub to: Name,
+    pub amount: Money,
+    pub schedule: Schedule,
+    pub retry: RetryPolicy,
+    /// Текущий срок по расписанию
+    pub due: Date,
+    /// Когда пробовать в следующий раз: `due` или позже, если уже были неудачи
+    pub next_run: Date,
+    /// Сколько повторов уже потрачено на текущий срок
+    pub attempts: u32,
+    pub status: OrderStatus,
+    /// Все неудачные попытки
+    pub failures: Vec<OrderFailure>,
+}
+
+impl StandingOrder {
+    pub fn new(
+        id: OrderId,
+        transfer: Transfer,
+        schedule: Schedule,
+        start: Date,
+        retry: RetryPolicy,
+    ) -> Self {
+        let due = schedule.first_on_or_after(start);
+        StandingOrder {
+            id,
+            from: transfer.from,
+            to: transfer.to,
+            amount: transfer.amount,
+            schedule,
+            retry,
+            due,
+            next_run: due,
+            attempts: 0,
+            status: OrderStatus::Active,
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn transfer(&self) -> Transfer {
+        Transfer {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            amount: self.amount,
+        }
+    }
+
+    pub fn is_due(&self, now: Date) -> bool {
+        self.status == OrderStatus::Active && self.next_run <= now
+    }
+
+    /// Учитывает исход попытки, сделанной в день `next_run`
+    pub fn record(&mut self, result: &Result<(), BankError>) {
+        if let Err(err) = result {
+            self.failures.push(OrderFailure {
+                date: self.next_run,
+                kind: err.into(),
+            });
+            if self.attempts < self.retry.retries {
+                self.attempts += 1;
+                self.next_run = self.next_run.add_days(i64::from(self.retry.every_days));
+                return;
+            }
+        }
+
+        self.attempts = 0;
+        match self.schedule.next_after(self.due) {
+            Some(due) => {
+                self.due = due;
+                self.next_run = due;
+            }
+            None if result.is_ok() => self.status = OrderStatus::Completed,
+            None => self.status = OrderStatus::Failed,
+        }
+    }
+}
+
+/// Строка для файлов:
+/// `id,from,to,amount,currency,schedule,retry,due,next_run,attempts,status,failures`
+impl fmt::Display for StandingOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures: Vec<String> = self.failures.iter().map(|f| f.to_string()).collect();
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.id,
+            self.from,
+            self.to,
+            self.amount.amount,
+            self.amount.currency,
+            self.schedule,
+            self.retry,
+            self.due,
+            self.next_run,
+            self.attempts,
+            self.status,
+            failures.join(" ")
+        )
+    }
+}
+
+impl FromStr for StandingOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        let [
+            id,
+            from,
+            to,
+            amount,
+            currency,
+            schedule,
+            retry,
+            due,
+            next_run,
+            attempts,
+            status,
+            failures,
+        ] = parts.as_slice()
+        else {
+            return Err(format!("некорректное поручение '{}'", s));
+        };
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("некорректное число '{}' в поручении", value))
+        };
+        Ok(StandingOrder {
+            id: number(id)?,
+            from: from.to_string(),
+            to: to.to_string(),
+            amount: Money {
+                amount: amount
+                    .parse()
+                    .map_err(|_| format!("плохая сумма '{}' в поручении", amount))?,
+                currency: currency.parse().map_err(|e| format!("{}", e))?,
+            },
+            schedule: schedule.parse()?,
+            retry: retry.parse()?,
+            due: due.parse()?,
+            next_run: next_run.parse()?,
+            attempts: number(attempts)? as u32,
+            status: status.parse()?,
+            failures: failures
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Результат одной попытки исполнить поручение
+#[derive(Debug)]
+pub struct OrderRun {
+    pub order: OrderId,
+    /// День, за который делалась попытка
+    pub date: Date,
+    pub result: Result<(), BankError>,
+}
+
+/// Поручение, которое пора исполнять первым: с самым ранним сроком попытки,
+/// при равных сроках — с меньшим номером. Поручения из `skip` не рассматриваются.
+pub(crate) fn next_due(
+    orders: &BTreeMap<OrderId, StandingOrder>,
+    now: Date,
+    skip: &BTreeSet<OrderId>,
+) -> Option<OrderId> {
+    orders
+        .values()
+        .filter(|order| order.is_due(now) && !skip.contains(&order.id))
+        .min_by_key(|order| (order.next_run, order.id))
+        .map(|order| order.id)
+}
+
+impl Storage {
+    /// Регистрирует платёжное поручение: перевод `transfer` по расписанию `schedule`,
+    /// начиная с `start`. Счёт списания должен существовать и вестись в валюте перевода.
+    /// Возвращает номер поручения.
+    pub fn add_order(
+        &mut self,
+        transfer: Transfer,
+        schedule: Schedule,
+        start: Date,
+        retry: RetryPolicy,
+    ) -> Result<OrderId, BankError> {
+        check_name(&transfer.from)?;
+        check_name(&transfer.to)?;
+        let valid_schedule = match schedule {
+            Schedule::Once => true,
+            Schedule::EveryDays(days) => days > 0,
+            Schedule::Monthly(day) => (1..=31).contains(&day),
+        };
+        if !valid_schedule {
+            return Err(BankError::BadSchedule(schedule.to_string()));
+        }
+        // с нулевым интервалом повтор пришёлся бы на тот же день и `run_due` не закончился бы
+        if retry.every_days == 0 {
+            return Err(BankError::BadSchedule(format!(
+                "{} retry {}",
+                schedule, retry
+            )));
+        }
+        if transfer.from == transfer.to {
+            return Err(BankError::SelfTransfer(transfer.from));
+        }
+        let balance = self
+            .accounts
+            .get(&transfer.from)
+            .ok_or_else(|| BankError::UserNotFound(transfer.from.clone()))?;
+        if balance.is_closed() {
+            return Err(BankError::AccountClosed(transfer.from));
+        }
+        if balance.currency != transfer.amount.currency {
+            return Err(BankError::CurrencyMismatch {
+                account: transfer.from.clone(),
+                expected: balance.currency,
+                found: transfer.amount.currency,
+            });
+        }
+        match self.accounts.get(&transfer.to) {
+            Some(balance) if balance.is_closed() => {
+                return Err(BankError::AccountClosed(transfer.to));
+            }
+            None if self.account_policy == AccountPolicy::Strict => {
+                return Err(BankError::UserNotFound(transfer.to));
+            }
+            _ => {}
+        }
+
+        self.atomic(|storage| {
+            let id = storage.orders.keys().next_back().map_or(1, |id| id + 1);
+            let order = StandingOrder::new(id, transfer, schedule, start, retry);
+            storage.touch_order(id);
+            storage.orders.insert(id, order);
+            Ok(id)
+        })
+    }
+
+    /// Отменяет активное поручение; история его попыток сохраняется
+    pub fn cancel_order(&mut self, id: OrderId) -> Result<(), BankError> {
+        self.atomic(|storage| match storage.order_mut(id) {
+            Some(order) if order.status == OrderStatus::Active => {
+                order.status = OrderStatus::Cancelled;
+                Ok(())
+            }
+            _ => Err(BankError::OrderNotFound(id)),
+        })
+    }
+
+    /// Исполняет все поручения, срок которых наступил к `now`, в порядке сроков.
+    /// Пропущенные сроки догоняются по одному: поручение, не исполнявшееся три месяца,
+    /// переведёт деньги три раза. Неудачный перевод не прерывает остальные —
+    /// он записывается в поручение и повторяется по его [`RetryPolicy`].
+    pub fn run_due(&mut self, now: Date) -> Vec<OrderRun> {
+        let mut runs = Vec::new();
+        let mut skip = BTreeSet::new();
+        while let Some(id) = next_due(&self.orders, now, &skip) {
+            match self.attempt_order(id) {
+                Ok(run) => runs.push(run),
+                // попытку не удалось записать в журнал — вернёмся к ней при следующем запуске
+                Err(_) => {
+                    skip.insert(id);
+                }
+            }
+        }
+        runs
+    }
+
+    /// Одна попытка исполнить поручение `id` вместе с учётом её исхода.
+    /// Ошибка — только если всё это не удалось записать в журнал.
+    pub(crate) fn attempt_order(&mut self, id: OrderId) -> Result<OrderRun, BankError> {
+        self.atomic(|storage| {
+            let order = storage
+                .orders
+                .get(&id)
+                .ok_or(BankError::OrderNotFound(id))?;
+            let date = order.next_run;
+            let result = order.transfer().apply(storage);
+            let order = storage.order_mut(id).expect("поручение только что было");
+            order.record(&result);
+            Ok(OrderRun {
+                order: id,
+                date,
+                result,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LoadMode;
+    use crate::journal::Journal;
+    use crate::money::Currency;
+    use crate::testing::date;
+    use std::fs;
+
+    fn order(schedule: Schedule, retry: RetryPolicy) -> StandingOrder {
+        let transfer = Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: Money::new(10000, Currency::RUB),
+        };
+        StandingOrder::new(1, transfer, schedule, date("2026-01-15"), retry)
+    }
+
+    #[test]
+    fn schedule_dates() {
+        let monthly = Schedule::Monthly(31);
+        let first = monthly.first_on_or_after(date("2026-01-15"));
+        assert_eq!(first, date("2026-01-31"));
+        assert_eq!(monthly.next_after(first), Some(date("2026-02-28")));
+        assert_eq!(
+            Schedule::Monthly(1).first_on_or_after(date("2026-01-15")),
+            date("2026-02-01")
+        );
+        assert_eq!(
+            Schedule::EveryDays(7).next_after(date("2026-01-15")),
+            Some(date("2026-01-22"))
+        );
+        assert_eq!(Schedule::Once.next_after(first), None);
+
+        for s in ["once", "every:7", "monthly:1"] {
+            assert_eq!(s.parse::<Schedule>().unwrap().to_string(), s);
+        }
+        for bad in ["every:0", "monthly:32", "weekly", "every:x"] {
+            assert!(bad.parse::<Schedule>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn failures_are_retried_then_skipped() {
+        let retry = RetryPolicy {
+            retries: 2,
+            every_days: 3,
+        };
+        let mut order = order(Schedule::Monthly(1), retry);
+        let nsf = || {
+            Err(BankError::NotEnoughMoney {
+                account: "Alice".to_string(),
+                required: Money::new(10000, Currency::RUB),
+                available: Money::new(0, Currency::RUB),
+                credit: Money::new(0, Currency::RUB),
+            })
+        };
+
+        order.record(&nsf());
+        order.record(&nsf());
+        assert_eq!(order.next_run, date("2026-02-07"));
+        assert_eq!(order.attempts, 2);
+        // повторы кончились — ждём следующего срока
+        order.record(&nsf());
+        assert_eq!(order.due, date("2026-03-01"));
+        assert_eq!(order.next_run, date("2026-03-01"));
+        assert_eq!(order.attempts, 0);
+        assert_eq!(order.failures.len(), 3);
+        assert_eq!(order.failures[2].to_string(), "2026-02-07:nsf");
+
+        order.record(&Ok(()));
+        assert_eq!(order.due, date("2026-04-01"));
+        assert_eq!(order.status, OrderStatus::Active);
+
+        let mut once = self::order(Schedule::Once, RetryPolicy::default());
+        once.record(&Err(BankError::UserNotFound("Bob".to_string())));
+        assert_eq!(once.status, OrderStatus::Failed);
+        assert_eq!(once.failures[0].kind, FailureKind::AccountNotFound);
+        assert!(!once.is_due(date("2030-01-01")));
+    }
+
+    #[test]
+    fn order_roundtrip() {
+        let mut order = order(Schedule::EveryDays(7), RetryPolicy::default());
+        order.record(&Err(BankError::SelfTransfer("Alice".to_string())));
+        let line = order.to_string();
+        assert_eq!(
+            line,
+            "1,Alice,Bob,10000,RUB,every:7,0:1,2026-01-22,2026-01-22,0,active,2026-01-15:other"
+        );
+        assert_eq!(line.parse::<StandingOrder>(), Ok(order));
+        assert!("1,Alice,Bob".parse::<StandingOrder>().is_err());
+        assert!("0:0".parse::<RetryPolicy>().is_err());
+    }
+
+    fn storage_with_alice(amount: u64) -> Storage {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage.add_user("Bob".to_string());
+        storage
+            .credit(&"Alice".to_string(), Money::new(amount, Currency::RUB))
+            .unwrap();
+        storage
+    }
+
+    fn alice_to_bob(amount: u64) -> Transfer {
+        Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: Money::new(amount, Currency::RUB),
+        }
+    }
+
+    #[test]
+    fn run_due_catches_up_and_records_failures() {
+        let mut storage = storage_with_alice(250);
+        let id = storage
+            .add_order(
+                alice_to_bob(100),
+                Schedule::Monthly(1),
+                date("2026-01-01"),
+                RetryPolicy::default(),
+            )
+            .unwrap();
+        assert_eq!(id, 1);
+
+        assert!(storage.run_due(date("2025-12-31")).is_empty());
+        // три срока: два перевода проходят, на третий денег не хватает
+        let runs = storage.run_due(date("2026-03-15"));
+        let dates: Vec<String> = runs.iter().map(|r| r.date.to_string()).collect();
+        assert_eq!(dates, ["2026-01-01", "2026-02-01", "2026-03-01"]);
+        assert!(runs[0].result.is_ok() && runs[1].result.is_ok());
+        assert!(matches!(
+            runs[2].result,
+            Err(BankError::NotEnoughMoney { .. })
+        ));
+        assert_eq!(storage.accounts["Alice"].result, 50);
+        assert_eq!(storage.accounts["Bob"].result, 200);
+
+        let order = &storage.orders[&id];
+        assert_eq!(order.next_run, date("2026-04-01"));
+        assert_eq!(order.failures[0].to_string(), "2026-03-01:nsf");
+
+        storage.cancel_order(id).unwrap();
+        assert!(storage.run_due(date("2027-01-01")).is_empty());
+        assert!(matches!(
+            storage.cancel_order(id),
+            Err(BankError::OrderNotFound(1))
+        ));
+    }
+
+    #[test]
+    fn add_order_validates_accounts() {
+        let mut storage = storage_with_alice(0);
+        let start = date("2026-01-01");
+        let retry = RetryPolicy::default();
+        let mut to_self = alice_to_bob(1);
+        to_self.to = "Alice".to_string();
+        assert!(matches!(
+            storage.add_order(to_self, Schedule::Once, start, retry),
+            Err(BankError::SelfTransfer(_))
+        ));
+        let mut unknown = alice_to_bob(1);
+        unknown.to = "Carl".to_string();
+        assert!(matches!(
+            storage.add_order(unknown, Schedule::Once, start, retry),
+            Err(BankError::UserNotFound(_))
+        ));
+        let mut usd = alice_to_bob(1);
+        usd.amount.currency = Currency::USD;
+        assert!(matches!(
+            storage.add_order(usd, Schedule::Once, start, retry),
+            Err(BankError::CurrencyMismatch { .. })
+        ));
+        // при AutoCreate получатель откроется при исполнении, поэтому имя проверяется заранее
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let mut bad_name = alice_to_bob(1);
+        bad_name.to = "x,y;z".to_string();
+        assert!(matches!(
+            storage.add_order(bad_name, Schedule::Once, start, retry),
+            Err(BankError::BadName(_))
+        ));
+        assert!(storage.orders.is_empty());
+    }
+
+    #[test]
+    fn add_order_rejects_zero_intervals() {
+        let mut storage = storage_with_alice(100);
+        let start = date("2026-01-01");
+        let retry = RetryPolicy::default();
+        for schedule in [
+            Schedule::EveryDays(0),
+            Schedule::Monthly(0),
+            Schedule::Monthly(32),
+        ] {
+            assert!(matches!(
+                storage.add_order(alice_to_bob(1), schedule, start, retry),
+                Err(BankError::BadSchedule(_))
+            ));
+        }
+        let no_wait = RetryPolicy {
+            retries: 3,
+            every_days: 0,
+        };
+        assert!(matches!(
+            storage.add_order(alice_to_bob(1), Schedule::Once, start, no_wait),
+            Err(BankError::BadSchedule(_))
+        ));
+        assert!(storage.orders.is_empty());
+        assert!(storage.run_due(date("2026-02-01")).is_empty());
+    }
+
+    #[test]
+    fn orders_survive_save_and_journal_replay() {
+        let file = "orders_roundtrip.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.add_user("Alice".to_string());
+        storage.add_user("Bob".to_string());
+        storage
+            .credit(&"Alice".to_string(), Money::new(100, Currency::RUB))
+            .unwrap();
+        let retry = RetryPolicy {
+            retries: 1,
+            every_days: 2,
+        };
+        storage
+            .add_order(
+                alice_to_bob(60),
+                Schedule::EveryDays(7),
+                date("2026-01-01"),
+                retry,
+            )
+            .unwrap();
+        storage.run_due(date("2026-01-10"));
+
+        // снимка ещё нет — поручения восстанавливаются из журнала
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.orders, storage.orders);
+        assert_eq!(restored.accounts["Bob"].result, 60);
+
+        storage.save(file);
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.orders, storage.orders);
+        assert_eq!(restored.orders[&1].failures.len(), 2);
+
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+}
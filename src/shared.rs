@@ -0,0 +1,937 @@
+use crate::Name;
+use crate::archive::{self, Archive, Retention};
+use crate::clock::{Clock, Date};
+use crate::errors::{BankError, LoadMode, report};
+use crate::holds::HoldId;
+use crate::idempotency::{self, KeyRecord};
+use crate::interest::InterestTerms;
+use crate::journal::{Change, Journal, JournalEntry};
+use crate::money::{Amount, Currency, Money, SignedAmount};
+use crate::operations::{Balance, Operation};
+use crate::rates::{RateTable, Rounding};
+use crate::schedule::{self, OrderId, OrderRun, RetryPolicy, Schedule, StandingOrder};
+use crate::statement::Statement;
+use crate::storage::{AccountPolicy, BalanceManager, Storage, check_name};
+use crate::transaction::{Transaction, Transfer, TxId};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Хранилище, с которым одновременно работают несколько клиентов.
+///
+/// Клонировать дёшево: все клоны видят одни и те же счета. У каждого счёта свой
+/// мьютекс, поэтому операции над разными счетами идут параллельно. Операция
+/// блокирует свои счета в порядке имён, так что две встречные транзакции
+/// не могут заблокировать друг друга.
+///
+/// Платёжные поручения и ключи идемпотентности защищены каждые своим общим
+/// мьютексом. Они всегда берутся раньше блокировок счетов (сначала поручения,
+/// потом ключи), а обычные операции их не трогают.
+#[derive(Clone)]
+pub struct SharedStorage {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    // Операции над существующими счетами держат чтение, открытие и закрытие счетов — запись
+    accounts: RwLock<HashMap<Name, Mutex<Balance>>>,
+    rates: RwLock<RateTable>,
+    orders: Mutex<BTreeMap<OrderId, StandingOrder>>,
+    keys: Mutex<BTreeMap<String, KeyRecord>>,
+    next_tx: Arc<AtomicU64>,
+    clock: Arc<dyn Clock + Send + Sync>,
+    account_policy: AccountPolicy,
+    retention: Retention,
+    archive: Option<Archive>,
+    journal: Mutex<Option<Journal>>,
+}
+
+impl SharedStorage {
+    /// Забирает счета, курсы, поручения, ключи, часы, политику открытия счетов,
+    /// окно хранения истории, архив и журнал из `storage`
+    pub fn new(storage: Storage) -> Self {
+        let accounts = storage
+            .accounts
+            .into_iter()
+            .map(|(name, balance)| (name, Mutex::new(balance)))
+            .collect();
+        SharedStorage {
+            inner: Arc::new(Inner {
+                accounts: RwLock::new(accounts),
+                rates: RwLock::new(storage.rates),
+                orders: Mutex::new(storage.orders),
+                keys: Mutex::new(storage.keys),
+                next_tx: storage.next_tx,
+                clock: storage.clock,
+                account_policy: storage.account_policy,
+                retention: storage.retention,
+                archive: storage.archive,
+                journal: Mutex::new(storage.journal),
+            }),
+        }
+    }
+
+    /// Добавляет пользователя со счётом в базовой валюте; `None`, если он уже есть,
+    /// имя не проходит [`check_name`] или изменение не удалось записать в журнал
+    pub fn add_user(&self, name: Name) -> Option<u64> {
+        self.add_user_with_currency(name, Currency::default())
+    }
+
+    pub fn add_user_with_currency(&self, name: Name, currency: Currency) -> Option<u64> {
+        self.create_account(&name, currency, None).ok().map(|()| 0)
+    }
+
+    /// Открывает счёт с начальным балансом одной записью журнала,
+    /// как [`Storage::create_account`]
+    pub fn create_account(
+        &self,
+        name: &Name,
+        currency: Currency,
+        initial: Option<Money>,
+    ) -> Result<(), BankError> {
+        check_name(name)?;
+        self.run(&[name], |storage| {
+            storage.create_account(name, currency, initial)
+        })
+    }
+
+    /// Удаляет пользователя без следа, как [`Storage::remove_user`]; счёт с переводами
+    /// удалить нельзя
+    pub fn remove_user(&self, name: &Name) -> Result<Balance, BankError> {
+        let mut accounts = self.write_accounts();
+        let balance = accounts
+            .get(name)
+            .map(|balance| lock(balance).clone())
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        if balance.has_transfers() {
+            return Err(BankError::HasTransfers(name.clone()));
+        }
+        let before = HashMap::from([(name.clone(), balance)]);
+        self.commit(&JournalEntry::diff(&before, &HashMap::new()))?;
+        accounts.remove(name);
+        Ok(before.into_values().next().expect("счёт только что найден"))
+    }
+
+    /// Закрывает счёт, как [`Storage::close_account`]. Поручения со счёта и на счёт
+    /// отменяются под тем же мьютексом, что и при их исполнении.
+    pub fn close_account(&self, name: &Name, payout: Option<&Name>) -> Result<Money, BankError> {
+        let mut orders = lock(&self.inner.orders);
+        let names: Vec<&Name> = std::iter::once(name).chain(payout).collect();
+        self.run_in(&names, &mut orders, |storage| {
+            storage.close_account(name, payout)
+        })
+    }
+
+    /// Сегодняшний день по часам хранилища
+    pub fn today(&self) -> Date {
+        self.inner.clock.today()
+    }
+
+    pub fn get_balance(&self, name: &Name) -> Option<Balance> {
+        let accounts = self.read_accounts();
+        accounts.get(name).map(|balance| lock(balance).clone())
+    }
+
+    /// Страница полной истории счёта, как [`Storage::history`]
+    pub fn history(
+        &self,
+        name: &Name,
+        offset: u64,
+        limit: usize,
+    ) -> Result<Vec<Operation>, BankError> {
+        let balance = self
+            .get_balance(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        archive::page(self.inner.archive.as_ref(), name, &balance, offset, limit)
+    }
+
+    /// Выписка по счёту, как [`Storage::statement`]
+    pub fn statement(&self, name: &Name, from: Date, to: Date) -> Result<Statement, BankError> {
+        let balance = self
+            .get_balance(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        let history = archive::page(self.inner.archive.as_ref(), name, &balance, 0, usize::MAX)?;
+        Statement::for_history(name, balance.currency, &history, from, to)
+    }
+
+    /// Получает все аккаунты с их балансами (при овердрафте — отрицательными)
+    pub fn get_all(&self) -> Vec<(Name, SignedAmount)> {
+        let accounts = self.read_accounts();
+        accounts
+            .iter()
+            .map(|(name, balance)| (name.clone(), lock(balance).result))
+            .collect()
+    }
+
+    /// Устанавливает кредитный лимит счёта, как [`Storage::set_credit_limit`]
+    pub fn set_credit_limit(&self, name: &Name, limit: Amount) -> Result<(), BankError> {
+        self.run(&[name], |storage| storage.set_credit_limit(name, limit))
+    }
+
+    /// Задаёт условия начисления процентов, как [`Storage::set_interest`]
+    pub fn set_interest(&self, name: &Name, terms: Option<InterestTerms>) -> Result<(), BankError> {
+        self.run(&[name], |storage| storage.set_interest(name, terms))
+    }
+
+    /// Начисляет проценты, как [`Storage::accrue_interest`], блокируя все счета.
+    /// Счета, открытые во время начисления, догонят его при следующем запуске.
+    pub fn accrue_interest(
+        &self,
+        until: Date,
+        rounding: Rounding,
+    ) -> Result<Vec<(Name, Money)>, BankError> {
+        let names: Vec<Name> = self.read_accounts().keys().cloned().collect();
+        let names: Vec<&Name> = names.iter().collect();
+        self.run(&names, |storage| storage.accrue_interest(until, rounding))
+    }
+
+    /// Снимает истёкшие блокировки, как [`Storage::expire_holds`], блокируя все счета
+    pub fn expire_holds(&self, today: Date) -> Result<Vec<(Name, HoldId, Money)>, BankError> {
+        let names: Vec<Name> = self.read_accounts().keys().cloned().collect();
+        let names: Vec<&Name> = names.iter().collect();
+        self.run(&names, |storage| storage.expire_holds(today))
+    }
+
+    /// Переносит старую историю в архив, как [`Storage::archive_history`],
+    /// блокируя все счета
+    pub fn archive_history(&self) -> Result<usize, BankError> {
+        let Some(archive) = &self.inner.archive else {
+            return Ok(0);
+        };
+        let mut accounts = self.write_accounts();
+        let (entry, moved) = archive::roll(
+            archive,
+            accounts.iter_mut().map(|(name, balance)| {
+                (
+                    name,
+                    &*balance.get_mut().unwrap_or_else(PoisonError::into_inner),
+                )
+            }),
+            self.inner.retention,
+            self.inner.clock.today(),
+        )?;
+        self.commit(&entry)?;
+        for change in &entry.changes {
+            if let Change::Archive { name, count } = change {
+                let balance = accounts
+                    .get_mut(name)
+                    .expect("перенос только по своим счетам");
+                balance
+                    .get_mut()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .move_to_archive(name, *count);
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Регистрирует платёжное поручение, как [`Storage::add_order`]
+    pub fn add_order(
+        &self,
+        transfer: Transfer,
+        schedule: Schedule,
+        start: Date,
+        retry: RetryPolicy,
+    ) -> Result<OrderId, BankError> {
+        let mut orders = lock(&self.inner.orders);
+        let (from, to) = (transfer.from.clone(), transfer.to.clone());
+        self.run_in(&[&from, &to], &mut orders, |storage| {
+            storage.add_order(transfer, schedule, start, retry)
+        })
+    }
+
+    /// Отменяет поручение, как [`Storage::cancel_order`]
+    pub fn cancel_order(&self, id: OrderId) -> Result<(), BankError> {
+        let mut orders = lock(&self.inner.orders);
+        self.run_in(&[], &mut orders, |storage| storage.cancel_order(id))
+    }
+
+    /// Все платёжные поручения в порядке номеров
+    pub fn orders(&self) -> Vec<StandingOrder> {
+        lock(&self.inner.orders).values().cloned().collect()
+    }
+
+    /// Исполняет наступившие поручения, как [`Storage::run_due`]. Каждая попытка
+    /// блокирует только свои два счёта, остальные операции идут параллельно.
+    pub fn run_due(&self, now: Date) -> Vec<OrderRun> {
+        let mut orders = lock(&self.inner.orders);
+        let mut runs = Vec::new();
+        let mut skip = BTreeSet::new();
+        while let Some(id) = schedule::next_due(&orders, now, &skip) {
+            let (from, to) = (orders[&id].from.clone(), orders[&id].to.clone());
+            match self.run_in(&[&from, &to], &mut orders, |storage| {
+                storage.attempt_order(id)
+            }) {
+                Ok(run) => runs.push(run),
+                Err(_) => {
+                    skip.insert(id);
+                }
+            }
+        }
+        runs
+    }
+
+    pub fn set_rates(&self, rates: RateTable) {
+        *self
+            .inner
+            .rates
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = rates;
+    }
+
+    /// Применяет транзакцию, блокируя только счета из [`Transaction::accounts`]
+    pub fn apply<T: Transaction>(&self, tx: &T) -> Result<(), BankError> {
+        self.run(&tx.accounts(), |storage| tx.apply(storage))
+    }
+
+    /// Проводит транзакцию, как [`Storage::submit`]. Отправки с ключом идут
+    /// по одной, чтобы две одновременные попытки с одним ключом не провели её дважды.
+    pub fn submit<T: Transaction + ?Sized>(
+        &self,
+        tx: &T,
+        key: Option<&str>,
+    ) -> Result<TxId, BankError> {
+        let Some(key) = key else {
+            return self.run(&tx.accounts(), |storage| storage.submit(tx, None));
+        };
+        idempotency::check_key(key)?;
+        let mut keys = lock(&self.inner.keys);
+        if let Some(record) = keys.get(key) {
+            return idempotency::replay(record, tx);
+        }
+        let record = self.run(&tx.accounts(), |storage| {
+            storage.submit(tx, Some(key))?;
+            Ok::<_, BankError>(storage.keys[key].clone())
+        })?;
+        keys.insert(key.to_string(), record.clone());
+        Ok(record.tx)
+    }
+
+    /// Плановое обслуживание по часам хранилища: снимает истёкшие блокировки,
+    /// исполняет наступившие поручения, переносит старую историю в архив и сохраняет
+    /// снимок в `path`. Ошибки только печатаются: следующий запуск повторит попытку.
+    pub fn checkpoint(&self, path: &str) {
+        let today = self.today();
+        if let Err(e) = self.expire_holds(today) {
+            eprintln!("Не удалось снять истёкшие блокировки: {}", report(&e));
+        }
+        for run in self.run_due(today) {
+            if let Err(e) = run.result {
+                eprintln!(
+                    "Поручение №{} за {} не исполнено: {}",
+                    run.order,
+                    run.date,
+                    report(&e)
+                );
+            }
+        }
+        if let Err(e) = self.archive_history() {
+            eprintln!("Не удалось перенести историю в архив: {}", report(&e));
+        }
+        self.save(path);
+    }
+
+    /// Согласованная копия всех счетов, поручений, ключей и курсов (без журнала,
+    /// но с архивом истории)
+    pub fn snapshot(&self) -> Storage {
+        let orders = lock(&self.inner.orders);
+        let keys = lock(&self.inner.keys);
+        let mut storage = self.snapshot_of(&mut self.write_accounts());
+        storage.orders = orders.clone();
+        storage.keys = keys.clone();
+        storage
+    }
+
+    /// Сохраняет снимок так же, как [`Storage::save`], очищая общий журнал
+    pub fn save(&self, file: &str) {
+        // пока держим запись, новые операции не начнутся и журнал не пополнится
+        let orders = lock(&self.inner.orders);
+        let keys = lock(&self.inner.keys);
+        let mut accounts = self.write_accounts();
+        let mut storage = self.snapshot_of(&mut accounts);
+        storage.orders = orders.clone();
+        storage.keys = keys.clone();
+        let mut journal = lock(&self.inner.journal);
+        storage.journal = journal.take();
+        storage.save(file);
+        *journal = storage.journal.take();
+    }
+
+    fn snapshot_of(&self, accounts: &mut HashMap<Name, Mutex<Balance>>) -> Storage {
+        let mut storage = Storage::new();
+        storage.account_policy = self.inner.account_policy;
+        storage.accounts = accounts
+            .iter_mut()
+            .map(|(name, balance)| {
+                let balance = balance.get_mut().unwrap_or_else(PoisonError::into_inner);
+                (name.clone(), balance.clone())
+            })
+            .collect();
+        storage.rates = self.rates().clone();
+        storage.next_tx = self.inner.next_tx.clone();
+        storage.clock = self.inner.clock.clone();
+        storage.retention = self.inner.retention;
+        storage.archive = self.inner.archive.clone();
+        storage
+    }
+
+    fn read_accounts(&self) -> RwLockReadGuard<'_, HashMap<Name, Mutex<Balance>>> {
+        self.inner
+            .accounts
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_accounts(&self) -> RwLockWriteGuard<'_, HashMap<Name, Mutex<Balance>>> {
+        self.inner
+            .accounts
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn rates(&self) -> RwLockReadGuard<'_, RateTable> {
+        self.inner
+            .rates
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Выполняет `f` над копией счетов `names`, держа их блокировки, и при успехе
+    /// записывает изменения в журнал и переносит их в общие счета.
+    /// Если какого-то из счетов ещё нет, `f` может его открыть — тогда на время
+    /// операции берётся блокировка всего хранилища.
+    fn run<T, E: From<BankError>>(
+        &self,
+        names: &[&Name],
+        f: impl FnOnce(&mut Storage) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.run_in(names, &mut BTreeMap::new(), f)
+    }
+
+    /// То же, что `run`, но `f` видит и может менять платёжные поручения `orders`.
+    /// Вызывающий держит мьютекс поручений.
+    fn run_in<T, E: From<BankError>>(
+        &self,
+        names: &[&Name],
+        orders: &mut BTreeMap<OrderId, StandingOrder>,
+        f: impl FnOnce(&mut Storage) -> Result<T, E>,
+    ) -> Result<T, E> {
+        // Единый порядок блокировок исключает взаимоблокировку встречных переводов
+        let mut names = names.to_vec();
+        names.sort();
+        names.dedup();
+
+        {
+            let accounts = self.read_accounts();
+            if names.iter().all(|name| accounts.contains_key(*name)) {
+                let mut guards: Vec<MutexGuard<Balance>> =
+                    names.iter().map(|name| lock(&accounts[*name])).collect();
+                let before = names
+                    .iter()
+                    .zip(&guards)
+                    .map(|(name, balance)| ((*name).clone(), (**balance).clone()))
+                    .collect();
+                let (value, mut after) = self.execute(&names, &before, orders, f)?;
+                for (name, guard) in names.iter().zip(&mut guards) {
+                    if let Some(balance) = after.remove(*name) {
+                        **guard = balance;
+                    }
+                }
+                return Ok(value);
+            }
+        }
+
+        let mut accounts = self.write_accounts();
+        let before = names
+            .iter()
+            .filter_map(|name| {
+                let balance = accounts.get_mut(*name)?;
+                let balance = balance.get_mut().unwrap_or_else(PoisonError::into_inner);
+                Some(((*name).clone(), balance.clone()))
+            })
+            .collect();
+        let (value, after) = self.execute(&names, &before, orders, f)?;
+        for (name, balance) in after {
+            accounts.insert(name, Mutex::new(balance));
+        }
+        Ok(value)
+    }
+
+    /// Выполняет `f` над копией счетов `before` и пишет изменения в журнал.
+    /// Счёт вне `names` ни заблокирован, ни перенесён бы в общие счета не был,
+    /// поэтому такая операция — ошибка в [`Transaction::accounts`]: она завершается
+    /// [`BankError::UndeclaredAccounts`] до записи в журнал, и ни журнал, ни общие
+    /// счета не меняются.
+    fn execute<T, E: From<BankError>>(
+        &self,
+        names: &[&Name],
+        before: &HashMap<Name, Balance>,
+        orders: &mut BTreeMap<OrderId, StandingOrder>,
+        f: impl FnOnce(&mut Storage) -> Result<T, E>,
+    ) -> Result<(T, HashMap<Name, Balance>), E> {
+        let mut scratch = Storage::new();
+        scratch.accounts = before.clone();
+        scratch.orders = orders.clone();
+        scratch.rates = self.rates().clone();
+        scratch.account_policy = self.inner.account_policy;
+        scratch.next_tx = self.inner.next_tx.clone();
+        scratch.clock = self.inner.clock.clone();
+        let value = f(&mut scratch)?;
+        let outside: Vec<&Name> = scratch
+            .accounts
+            .keys()
+            .filter(|name| !names.contains(name))
+            .collect();
+        if !outside.is_empty() {
+            let mut outside: Vec<Name> = outside.into_iter().cloned().collect();
+            outside.sort();
+            return Err(BankError::UndeclaredAccounts(outside).into());
+        }
+        // ключей в копии нет, поэтому все её ключи — новые
+        self.commit(
+            &JournalEntry::diff(before, &scratch.accounts)
+                .with_orders(orders, &scratch.orders)
+                .with_keys(&BTreeMap::new(), &scratch.keys),
+        )
+        .map_err(BankError::from)?;
+        *orders = scratch.orders;
+        Ok((value, scratch.accounts))
+    }
+
+    /// Пишет изменения в журнал. Вызывается под блокировками затронутых счетов,
+    /// поэтому записи об одном счёте идут в журнале в порядке выполнения.
+    fn commit(&self, entry: &JournalEntry) -> io::Result<()> {
+        let mut journal = lock(&self.inner.journal);
+        let Some(journal) = journal.as_mut() else {
+            return Ok(());
+        };
+        if entry.is_empty() {
+            return Ok(());
+        }
+        journal.append(entry)
+    }
+}
+
+/// Как часто сервер снимает истёкшие блокировки, исполняет наступившие платёжные
+/// поручения, переносит старую историю в архив и сбрасывает снимок на диск; между
+/// снимками изменения живут в журнале
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Запускает сервер банка на `addr`: загружает `balance.csv` с журналом и курсы
+/// из `rates.csv`, если он есть, раз в [`CHECKPOINT_INTERVAL`] обслуживает хранилище
+/// и отдаёт каждое соединение `handle` в своём потоке. Общая часть `bank-server`
+/// и `bank-http`, которые различаются только протоколом. Возвращает ошибку запуска.
+pub fn serve(
+    addr: &str,
+    retention: Retention,
+    title: &str,
+    handle: fn(TcpStream, &SharedStorage) -> io::Result<()>,
+) -> Result<(), String> {
+    let (mut storage, _) =
+        Storage::load_data("balance.csv", LoadMode::Strict).map_err(|e| e.to_string())?;
+    storage.retention = retention;
+    let storage = SharedStorage::new(storage);
+    if Path::new("rates.csv").exists() {
+        storage.set_rates(RateTable::load("rates.csv").map_err(|e| e.to_string())?);
+    }
+
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Не удалось занять адрес {}: {}", addr, e))?;
+    println!("=== {} слушает {} ===", title, addr);
+
+    {
+        let storage = storage.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(CHECKPOINT_INTERVAL);
+                storage.checkpoint("balance.csv");
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Ошибка соединения: {}", e);
+                continue;
+            }
+        };
+        let storage = storage.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(e) = handle(stream, &storage) {
+                eprintln!("Клиент {:?} отключился с ошибкой: {}", peer, e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Паника в чужой операции не портит данные: она работала с копией счетов
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl From<Storage> for SharedStorage {
+    fn from(storage: Storage) -> Self {
+        SharedStorage::new(storage)
+    }
+}
+
+impl BalanceManager for SharedStorage {
+    fn deposit(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.run(&[name], |storage| {
+            storage.ensure_account(name, amount.currency)?;
+            storage.credit(name, amount)
+        })
+    }
+
+    fn withdraw(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.run(&[name], |storage| storage.debit(name, amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::testing::rub;
+    use crate::transaction::{Deposit, Hold, Transfer};
+    use std::{fs, thread};
+
+    #[test]
+    fn shared_handles_see_same_accounts() {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let shared = SharedStorage::new(storage);
+        let mut other = shared.clone();
+        let alice = "Alice".to_string();
+
+        assert_eq!(shared.add_user(alice.clone()), Some(0));
+        assert_eq!(shared.add_user(alice.clone()), None);
+        other.deposit(&alice, rub(100)).unwrap();
+        assert_eq!(shared.get_balance(&alice).unwrap().result, 100);
+
+        let result = other.withdraw(&"Bob".to_string(), rub(1));
+        assert!(matches!(result, Err(BankError::UserNotFound(_))));
+        assert!(shared.get_balance(&"Bob".to_string()).is_none());
+
+        // политика AutoCreate действует и в общем хранилище: транзакция открывает новый счёт
+        shared
+            .apply(&Transfer {
+                from: alice.clone(),
+                to: "Bob".to_string(),
+                amount: rub(40),
+            })
+            .unwrap();
+        assert_eq!(shared.get_balance(&"Bob".to_string()).unwrap().result, 40);
+        // после перевода счета можно только закрыть: удаление разорвало бы пару проводок
+        assert!(matches!(
+            shared.remove_user(&alice),
+            Err(BankError::HasTransfers(_))
+        ));
+        assert_eq!(shared.get_all().len(), 2);
+
+        // как и у `Storage`, зачисление на неизвестное имя открывает счёт
+        let carl = "Carl".to_string();
+        other.deposit(&carl, rub(5)).unwrap();
+        assert_eq!(shared.get_balance(&carl).unwrap().result, 5);
+        assert!(matches!(
+            other.deposit(&"x,y".to_string(), rub(5)),
+            Err(BankError::BadName(_))
+        ));
+        assert_eq!(shared.get_all().len(), 3);
+        assert_eq!(shared.remove_user(&carl).unwrap().result, 5);
+        assert_eq!(shared.get_all().len(), 2);
+    }
+
+    #[test]
+    fn failed_transaction_leaves_accounts_untouched() {
+        let shared = SharedStorage::new(Storage::new());
+        shared.add_user("Alice".to_string());
+        shared.add_user("Bob".to_string());
+        shared
+            .apply(&Deposit {
+                account: "Alice".to_string(),
+                amount: rub(10),
+            })
+            .unwrap();
+        let tx = Deposit {
+            account: "Alice".to_string(),
+            amount: rub(5),
+        } + Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(100),
+        };
+
+        assert!(shared.apply(&tx).is_err());
+        let snapshot = shared.snapshot();
+        assert_eq!(snapshot.accounts["Alice"].result, 10);
+        assert_eq!(snapshot.accounts["Bob"].result, 0);
+
+        // без AutoCreate неизвестный получатель — ошибка, а не новый счёт
+        let tx = Transfer {
+            from: "Alice".to_string(),
+            to: "Carl".to_string(),
+            amount: rub(1),
+        };
+        assert!(matches!(shared.apply(&tx), Err(BankError::UserNotFound(_))));
+        assert!(shared.get_balance(&"Carl".to_string()).is_none());
+    }
+
+    #[test]
+    fn checkpoint_expires_holds_runs_orders_and_saves() {
+        let file = "shared_checkpoint.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        let clock = Arc::new(ManualClock::new("2026-01-15".parse().unwrap()));
+        storage.clock = clock.clone();
+        let shared = SharedStorage::new(storage);
+        let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+        shared.add_user(alice.clone());
+        shared.add_user(bob.clone());
+        shared.clone().deposit(&alice, rub(100)).unwrap();
+        shared
+            .apply(&Hold {
+                account: alice.clone(),
+                hold: 1,
+                amount: rub(30),
+                expires: "2026-02-01".parse().unwrap(),
+            })
+            .unwrap();
+        let transfer = Transfer {
+            from: alice.clone(),
+            to: bob.clone(),
+            amount: rub(10),
+        };
+        let start = "2026-02-01".parse().unwrap();
+        shared
+            .add_order(transfer, Schedule::Once, start, RetryPolicy::default())
+            .unwrap();
+
+        clock.set(start);
+        shared.checkpoint(file);
+        assert!(shared.get_balance(&alice).unwrap().holds.is_empty());
+        assert_eq!(shared.get_balance(&bob).unwrap().result, 10);
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts[&bob].result, 10);
+        assert!(restored.accounts[&alice].holds.is_empty());
+
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    /// Перевод, который называет только счёт отправителя
+    struct Undeclared(Transfer);
+
+    impl Transaction for Undeclared {
+        fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+            self.0.apply(storage)
+        }
+
+        fn fingerprint(&self) -> String {
+            self.0.fingerprint()
+        }
+
+        fn accounts(&self) -> Vec<&Name> {
+            vec![&self.0.from]
+        }
+    }
+
+    #[test]
+    fn transaction_outside_its_accounts_is_refused() {
+        let file = "shared_undeclared.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let shared = SharedStorage::new(storage);
+        shared.add_user("Alice".to_string());
+        shared.add_user("Bob".to_string());
+        shared
+            .clone()
+            .deposit(&"Alice".to_string(), rub(100))
+            .unwrap();
+        let journaled = fs::read_to_string(Journal::path_for(file)).unwrap();
+
+        // и для существующего получателя, и для нового, которого открыл бы перевод
+        for to in ["Bob", "Carl"] {
+            let tx = Undeclared(Transfer {
+                from: "Alice".to_string(),
+                to: to.to_string(),
+                amount: rub(30),
+            });
+            match shared.apply(&tx) {
+                Err(BankError::UndeclaredAccounts(names)) => assert_eq!(names, [to]),
+                other => panic!("{}: {:?}", to, other),
+            }
+        }
+        assert_eq!(
+            shared.get_balance(&"Alice".to_string()).unwrap().result,
+            100
+        );
+        assert_eq!(shared.get_balance(&"Bob".to_string()).unwrap().result, 0);
+        assert!(shared.get_balance(&"Carl".to_string()).is_none());
+        assert_eq!(
+            fs::read_to_string(Journal::path_for(file)).unwrap(),
+            journaled
+        );
+
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn shared_operations_are_journaled() {
+        let file = "shared_journal.csv";
+        let (storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        let shared = SharedStorage::new(storage);
+        shared.add_user("Alice".to_string());
+        shared
+            .apply(&Deposit {
+                account: "Alice".to_string(),
+                amount: rub(70),
+            })
+            .unwrap();
+
+        // снимка ещё нет — состояние восстанавливается из журнала
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts["Alice"].result, 70);
+
+        shared.save(file);
+        assert_eq!(fs::metadata(Journal::path_for(file)).unwrap().len(), 0);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn shared_history_reads_through_archive() {
+        let file = "shared_archive.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.retention = Retention::LastOps(1);
+        let today: Date = "2026-01-31".parse().unwrap();
+        storage.clock = Arc::new(ManualClock::new(today));
+        let shared = SharedStorage::new(storage);
+        let alice = "Alice".to_string();
+        shared.add_user(alice.clone());
+        for amount in [10, 20, 30] {
+            shared
+                .apply(&Deposit {
+                    account: alice.clone(),
+                    amount: rub(amount),
+                })
+                .unwrap();
+        }
+        let full = shared.history(&alice, 0, usize::MAX).unwrap();
+
+        assert_eq!(shared.archive_history().unwrap(), 2);
+        assert_eq!(shared.get_balance(&alice).unwrap().last_ops.len(), 1);
+        assert_eq!(shared.history(&alice, 0, usize::MAX).unwrap(), full);
+        assert_eq!(shared.snapshot().history(&alice, 1, 1).unwrap(), full[1..2]);
+        assert!(shared.snapshot().trial_balance().is_balanced());
+        let statement = shared.statement(&alice, today, today).unwrap();
+        assert_eq!(statement.lines.len(), 3);
+        assert_eq!(statement.closing, 60);
+
+        // перенос в журнале: без снимка счёт восстанавливается уже урезанным
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts[&alice].archived.count, 2);
+        assert_eq!(restored.history(&alice, 0, usize::MAX).unwrap(), full);
+
+        fs::remove_file(Journal::path_for(file)).unwrap();
+        fs::remove_file(Archive::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn shared_orders_run_and_persist() {
+        let file = "shared_orders.csv";
+        let (storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        let shared = SharedStorage::new(storage);
+        shared.add_user("Alice".to_string());
+        shared.add_user("Bob".to_string());
+        shared
+            .clone()
+            .deposit(&"Alice".to_string(), rub(150))
+            .unwrap();
+        let start: Date = "2026-01-01".parse().unwrap();
+        let transfer = Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(100),
+        };
+        let id = shared
+            .add_order(
+                transfer,
+                Schedule::EveryDays(1),
+                start,
+                RetryPolicy::default(),
+            )
+            .unwrap();
+
+        let runs = shared.run_due(start.add_days(1));
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].result.is_ok() && runs[1].result.is_err());
+        assert_eq!(shared.get_balance(&"Bob".to_string()).unwrap().result, 100);
+        shared.cancel_order(id).unwrap();
+        assert!(shared.cancel_order(id).is_err());
+
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(
+            restored.orders.values().cloned().collect::<Vec<_>>(),
+            shared.orders()
+        );
+        shared.save(file);
+        assert_eq!(shared.snapshot().orders, restored.orders);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn concurrent_transfers_conserve_money() {
+        const ACCOUNTS: usize = 8;
+        const THREADS: usize = 8;
+        const TRANSFERS: usize = 500;
+
+        let shared = SharedStorage::new(Storage::new());
+        let names: Vec<Name> = (0..ACCOUNTS).map(|i| format!("user{}", i)).collect();
+        for name in &names {
+            shared.add_user(name.clone());
+            shared.clone().deposit(name, rub(1000)).unwrap();
+        }
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let shared = shared.clone();
+                let names = names.clone();
+                thread::spawn(move || {
+                    // простой линейный конгруэнтный генератор, чтобы не тянуть зависимость
+                    let mut seed = t as u64 + 1;
+                    let mut next = move || {
+                        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        (seed >> 33) as usize
+                    };
+                    for _ in 0..TRANSFERS {
+                        let from = &names[next() % ACCOUNTS];
+                        let to = &names[next() % ACCOUNTS];
+                        // встречные переводы и нехватка денег здесь ожидаемы
+                        let _ = shared.apply(&Transfer {
+                            from: from.clone(),
+                            to: to.clone(),
+                            amount: rub(next() as u64 % 300),
+                        });
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total: i128 = shared.get_all().iter().map(|(_, a)| a.value()).sum();
+        assert_eq!(total, 1000 * ACCOUNTS as i128);
+        assert_eq!(shared.get_all().len(), ACCOUNTS);
+    }
+}
@@ -0,0 +1,443 @@
+use crate::Name;
+use crate::clock::Date;
+use crate::errors::BankError;
+use crate::json::Json;
+use crate::money::{Amount, Currency, Money, SignedAmount, SignedMoney};
+use crate::operations::{OpKind, Operation};
+use crate::storage::Storage;
+use std::fmt;
+
+/// Выписка по счёту за период: входящий остаток, операции с остатком после каждой,
+/// обороты и исходящий остаток. Всё считается по истории в основной валюте счёта;
+/// обмен между двумя другими валютами попадает в выписку с нулевой суммой.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    pub account: Name,
+    pub currency: Currency,
+    /// Первый день периода
+    pub from: Date,
+    /// Последний день периода, включительно
+    pub to: Date,
+    /// Остаток на начало дня `from`
+    pub opening: SignedAmount,
+    pub lines: Vec<StatementLine>,
+    /// Сумма поступлений за период
+    pub credits: Amount,
+    /// Сумма списаний за период
+    pub debits: Amount,
+    /// Остаток на конец дня `to`
+    pub closing: SignedAmount,
+}
+
+/// Операция выписки
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    pub op: Operation,
+    /// Изменение остатка: плюс — поступление, минус — списание
+    pub amount: SignedAmount,
+    /// Остаток после операции
+    pub balance: SignedAmount,
+}
+
+impl Statement {
+    /// Выписка по счёту `name` в валюте `currency` с полной историей `history`
+    /// с `from` по `to` включительно. Операции без времени — из файлов, записанных
+    /// до его появления, — считаются проведёнными раньше любого периода и входят
+    /// во входящий остаток.
+    pub fn for_history(
+        name: &Name,
+        currency: Currency,
+        history: &[Operation],
+        from: Date,
+        to: Date,
+    ) -> Result<Statement, BankError> {
+        if from > to {
+            return Err(BankError::BadPeriod { from, to });
+        }
+        let day = |op: &Operation| op.meta.at.map(|at| at.date());
+        let mut statement = Statement {
+            account: name.clone(),
+            currency,
+            from,
+            to,
+            opening: SignedAmount::ZERO,
+            lines: Vec::new(),
+            credits: Amount::ZERO,
+            debits: Amount::ZERO,
+            closing: SignedAmount::ZERO,
+        };
+
+        for op in history {
+            if day(op).is_none_or(|day| day < from) {
+                statement.opening = shift(name, statement.opening, currency, op)?;
+            }
+        }
+        let mut running = statement.opening;
+        for op in history {
+            if !day(op).is_some_and(|day| (from..=to).contains(&day)) {
+                continue;
+            }
+            let before = running;
+            running = shift(name, running, currency, op)?;
+            let amount = SignedAmount::new(running.value() - before.value())
+                .expect("одна операция меняет остаток не больше чем на Amount::MAX");
+            match amount.to_amount() {
+                Some(credit) => {
+                    statement.credits = statement.credits.checked_add(credit).unwrap_or(Amount::MAX)
+                }
+                None => {
+                    statement.debits = statement
+                        .debits
+                        .checked_add(amount.debt())
+                        .unwrap_or(Amount::MAX)
+                }
+            }
+            statement.lines.push(StatementLine {
+                op: op.clone(),
+                amount,
+                balance: running,
+            });
+        }
+        statement.closing = running;
+        Ok(statement)
+    }
+
+    /// Выписка в CSV: строка заголовка, входящий остаток, операции и исходящий
+    /// остаток. Суммы — в основных единицах валюты счёта, без кода валюты.
+    pub fn to_csv(&self) -> String {
+        let major = |amount: SignedAmount| self.currency.major_units(amount.value());
+        let mut csv = String::from("at,tx,kind,counterparty,memo,amount,balance\n");
+        csv.push_str(&format!(",,opening,,,,{}\n", major(self.opening)));
+        for line in &self.lines {
+            let meta = &line.op.meta;
+            let fields = [
+                meta.at.map(|at| at.to_string()).unwrap_or_default(),
+                meta.tx.map(|tx| tx.to_string()).unwrap_or_default(),
+                line.op.kind.name().to_string(),
+                csv_field(meta.counterparty.as_deref().unwrap_or_default()),
+                csv_field(meta.memo.as_deref().unwrap_or_default()),
+                major(line.amount),
+                major(line.balance),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv.push_str(&format!(",,closing,,,,{}\n", major(self.closing)));
+        csv
+    }
+
+    pub fn to_json(&self) -> Json {
+        let money = |amount| Json::string(self.money(amount));
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                let meta = &line.op.meta;
+                let mut fields = vec![
+                    ("kind".to_string(), Json::string(line.op.kind.name())),
+                    ("description".to_string(), Json::string(describe(&line.op))),
+                    ("amount".to_string(), money(line.amount)),
+                    ("balance".to_string(), money(line.balance)),
+                ];
+                if let Some(tx) = meta.tx {
+                    fields.push(("tx".to_string(), Json::Number(tx.to_string())));
+                }
+                if let Some(at) = meta.at {
+                    fields.push(("at".to_string(), Json::string(at)));
+                }
+                if let Some(counterparty) = &meta.counterparty {
+                    fields.push(("counterparty".to_string(), Json::string(counterparty)));
+                }
+                if let Some(memo) = &meta.memo {
+                    fields.push(("memo".to_string(), Json::string(memo)));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+        let total = |amount| {
+            Json::string(Money {
+                amount,
+                currency: self.currency,
+            })
+        };
+        Json::object([
+            ("account", Json::string(&self.account)),
+            ("currency", Json::string(self.currency)),
+            ("from", Json::string(self.from)),
+            ("to", Json::string(self.to)),
+            ("opening", money(self.opening)),
+            ("lines", Json::Array(lines)),
+            ("credits", total(self.credits)),
+            ("debits", total(self.debits)),
+            ("closing", money(self.closing)),
+        ])
+    }
+
+    fn money(&self, amount: SignedAmount) -> SignedMoney {
+        SignedMoney {
+            amount,
+            currency: self.currency,
+        }
+    }
+}
+
+/// Выписка для чтения человеком
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Выписка по счёту {} ({}) с {} по {}",
+            self.account, self.currency, self.from, self.to
+        )?;
+        writeln!(f, "Входящий остаток: {}", self.money(self.opening))?;
+        for line in &self.lines {
+            let meta = &line.op.meta;
+            let at = meta.at.map_or("—".to_string(), |at| at.to_string());
+            let tx = meta.tx.map_or("—".to_string(), |tx| format!("№{}", tx));
+            let sign = if line.amount.is_negative() { "" } else { "+" };
+            write!(
+                f,
+                "{}  {:<6} {:<32} {:>15} {:>15}",
+                at,
+                tx,
+                describe(&line.op),
+                format!("{}{}", sign, self.money(line.amount)),
+                self.money(line.balance).to_string()
+            )?;
+            if let Some(memo) = &meta.memo {
+                write!(f, "  {}", memo)?;
+            }
+            writeln!(f)?;
+        }
+        let total = |amount| Money {
+            amount,
+            currency: self.currency,
+        };
+        writeln!(f, "Поступления: {}", total(self.credits))?;
+        writeln!(f, "Списания: {}", total(self.debits))?;
+        write!(f, "Исходящий остаток: {}", self.money(self.closing))
+    }
+}
+
+impl Storage {
+    /// Выписка по счёту `name` с `from` по `to` включительно, как
+    /// [`Statement::for_history`]; архивная часть истории читается с диска.
+    /// Закрытый счёт тоже можно выписать.
+    pub fn statement(&self, name: &Name, from: Date, to: Date) -> Result<Statement, BankError> {
+        let history = self.history(name, 0, usize::MAX)?;
+        Statement::for_history(name, self.accounts[name].currency, &history, from, to)
+    }
+}
+
+/// Остаток после операции `op`: её проводка по счёту клиента в основной валюте
+fn shift(
+    name: &Name,
+    balance: SignedAmount,
+    currency: Currency,
+    op: &Operation,
+) -> Result<SignedAmount, BankError> {
+    let delta = op.kind.balance_delta(currency);
+    SignedAmount::new(balance.value() + delta).ok_or_else(|| BankError::Overflow {
+        account: name.clone(),
+        balance: SignedMoney {
+            amount: balance,
+            currency,
+        },
+        amount: Money {
+            amount: Amount::new(delta.unsigned_abs() as u64),
+            currency,
+        },
+    })
+}
+
+/// Описание операции для выписки: `перевод на счёт Bob`, `обмен 1.00 USD на 92.50 RUB`
+fn describe(op: &Operation) -> String {
+    let counterparty = op.meta.counterparty.as_deref();
+    match (&op.kind, counterparty) {
+        (OpKind::Deposit(_), _) => "пополнение".to_string(),
+        (OpKind::Withdraw(_), _) => "снятие".to_string(),
+        (OpKind::TransferIn(_), Some(from)) => format!("перевод со счёта {}", from),
+        (OpKind::TransferOut(_), Some(to)) => format!("перевод на счёт {}", to),
+        (OpKind::TransferIn(_) | OpKind::TransferOut(_), None) => "перевод".to_string(),
+        (OpKind::Exchange { sold, bought, .. }, _) => format!("обмен {} на {}", sold, bought),
+        (OpKind::Interest(_), _) => "проценты".to_string(),
+        (OpKind::CloseAccount, _) => "закрытие счёта".to_string(),
+    }
+}
+
+/// Поле CSV; с запятой, кавычкой или переводом строки — в кавычках
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::storage::{AccountPolicy, BalanceManager};
+    use crate::testing::{date, rub};
+    use crate::transaction::{Transaction, Transfer};
+    use std::sync::Arc;
+
+    /// Январь Алисы: пополнение до периода, в периоде перевод Бобу
+    /// с назначением и снятие, после периода ещё одно пополнение
+    fn january() -> Storage {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let clock = Arc::new(ManualClock::new(date("2025-12-31")));
+        storage.clock = clock.clone();
+        let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+        storage.deposit(&alice, rub(10_000)).unwrap();
+
+        clock.set(date("2026-01-05"));
+        clock.advance_secs(9 * 3600);
+        Transfer {
+            from: alice.clone(),
+            to: bob,
+            amount: rub(3_000),
+        }
+        .with_memo("аренда, январь")
+        .apply(&mut storage)
+        .unwrap();
+        clock.set(date("2026-01-31"));
+        storage.withdraw(&alice, rub(500)).unwrap();
+
+        clock.set(date("2026-02-01"));
+        storage.deposit(&alice, rub(100)).unwrap();
+        storage
+    }
+
+    #[test]
+    fn statement_totals_and_running_balance() {
+        let storage = january();
+        let alice = "Alice".to_string();
+        let statement = storage
+            .statement(&alice, date("2026-01-01"), date("2026-01-31"))
+            .unwrap();
+
+        assert_eq!(statement.opening, 10_000);
+        let running: Vec<(i128, i128)> = statement
+            .lines
+            .iter()
+            .map(|line| (line.amount.value(), line.balance.value()))
+            .collect();
+        assert_eq!(running, vec![(-3_000, 7_000), (-500, 6_500)]);
+        assert_eq!(statement.credits, Amount::ZERO);
+        assert_eq!(statement.debits, Amount::new(3_500));
+        assert_eq!(statement.closing, 6_500);
+
+        // выписка по всей истории заканчивается текущим остатком
+        let all = storage
+            .statement(&alice, date("2025-01-01"), date("2026-12-31"))
+            .unwrap();
+        assert_eq!(all.opening, 0);
+        assert_eq!(all.credits, Amount::new(10_100));
+        assert_eq!(all.closing, storage.accounts["Alice"].result);
+
+        let empty = storage
+            .statement(&alice, date("2026-03-01"), date("2026-03-31"))
+            .unwrap();
+        assert!(empty.lines.is_empty());
+        assert_eq!((empty.opening, empty.closing), (all.closing, all.closing));
+    }
+
+    #[test]
+    fn statement_errors_and_undated_history() {
+        let mut storage = january();
+        let alice = "Alice".to_string();
+        assert!(matches!(
+            storage.statement(&alice, date("2026-02-01"), date("2026-01-01")),
+            Err(BankError::BadPeriod { .. })
+        ));
+        assert!(matches!(
+            storage.statement(
+                &"Nobody".to_string(),
+                date("2026-01-01"),
+                date("2026-01-31")
+            ),
+            Err(BankError::UserNotFound(_))
+        ));
+
+        // операции без времени из старых файлов — в начале любой выписки
+        let balance = storage.accounts.get_mut("Alice").unwrap();
+        balance
+            .last_ops
+            .insert(0, OpKind::Deposit(Amount::new(1)).into());
+        balance.result = balance.result.checked_add(Amount::new(1)).unwrap();
+        let statement = storage
+            .statement(&alice, date("2026-02-01"), date("2026-02-01"))
+            .unwrap();
+        assert_eq!(statement.opening, 6_501);
+        assert_eq!(statement.closing, storage.accounts["Alice"].result);
+    }
+
+    #[test]
+    fn statement_formats() {
+        let storage = january();
+        let statement = storage
+            .statement(&"Alice".to_string(), date("2026-01-01"), date("2026-01-31"))
+            .unwrap();
+
+        let text = statement.to_string();
+        assert!(text.starts_with("Выписка по счёту Alice (RUB) с 2026-01-01 по 2026-01-31\n"));
+        assert!(text.contains("Входящий остаток: 100.00 RUB\n"));
+        assert!(text.contains("2026-01-05T09:00:00Z  №2"));
+        assert!(text.contains("перевод на счёт Bob"));
+        assert!(text.contains("-30.00 RUB"));
+        assert!(text.ends_with("Списания: 35.00 RUB\nИсходящий остаток: 65.00 RUB"));
+
+        assert_eq!(
+            statement.to_csv(),
+            "at,tx,kind,counterparty,memo,amount,balance\n\
+             ,,opening,,,,100.00\n\
+             2026-01-05T09:00:00Z,2,transfer_out,Bob,\"аренда, январь\",-30.00,70.00\n\
+             2026-01-31T00:00:00Z,3,withdraw,,,-5.00,65.00\n\
+             ,,closing,,,,65.00\n"
+        );
+
+        let json = statement.to_json();
+        assert_eq!(
+            json.get("opening").and_then(Json::as_str),
+            Some("100.00 RUB")
+        );
+        assert_eq!(json.get("debits").and_then(Json::as_str), Some("35.00 RUB"));
+        let Some(Json::Array(lines)) = json.get("lines") else {
+            panic!("нет операций");
+        };
+        assert_eq!(
+            lines[0].get("memo").and_then(Json::as_str),
+            Some("аренда, январь")
+        );
+        assert_eq!(
+            lines[1].get("balance").and_then(Json::as_str),
+            Some("65.00 RUB")
+        );
+    }
+
+    #[test]
+    fn exchange_moves_only_main_currency() {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new(date("2026-01-10")));
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, rub(10_000)).unwrap();
+        storage
+            .rates
+            .set(Currency::RUB, Currency::USD, "0.01".parse().unwrap());
+        storage.exchange(&alice, rub(5_000), Currency::USD).unwrap();
+
+        let day = date("2026-01-10");
+        let statement = storage.statement(&alice, day, day).unwrap();
+        assert_eq!(statement.lines[1].amount, -5_000);
+        assert_eq!(statement.closing, 5_000);
+        assert!(
+            statement
+                .to_string()
+                .contains("обмен 50.00 RUB на 0.50 USD")
+        );
+    }
+}
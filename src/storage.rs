@@ -1,19 +1,101 @@
 use crate::Name;
-use crate::errors::BalanceManagerError;
-use crate::operations::{Balance, OpKind};
-use std::collections::HashMap;
+use crate::archive::{Archive, Retention};
+use crate::clock::{Clock, Date, SystemClock};
+use crate::errors::{BankError, LineError, LoadError, LoadMode, RejectedLine};
+use crate::idempotency::KeyRecord;
+use crate::interest::InterestTerms;
+use crate::journal::{Journal, JournalEntry};
+use crate::money::{Amount, Currency, Money, SignedAmount, SignedMoney};
+use crate::operations::{Balance, OpKind, OpMeta};
+use crate::rates::{RateTable, Rounding};
+use crate::schedule::{OrderId, OrderStatus, StandingOrder};
+use crate::transaction::TxId;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::BufRead;
+use std::io::{BufRead, Write};
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{fs, io};
 
 pub trait BalanceManager {
-    fn deposit(&mut self, name: &Name, amount: u64) -> Result<(), BalanceManagerError>;
-    fn withdraw(&mut self, name: &Name, amount: u64) -> Result<(), BalanceManagerError>;
+    fn deposit(&mut self, name: &Name, amount: Money) -> Result<(), BankError>;
+    fn withdraw(&mut self, name: &Name, amount: Money) -> Result<(), BankError>;
+}
+
+/// Что делать, когда операция зачисляет деньги на несуществующий счёт
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountPolicy {
+    /// Счёт должен быть открыт заранее через `add_user`, иначе `UserNotFound`
+    #[default]
+    Strict,
+    /// Счёт открывается на лету в валюте зачисления
+    AutoCreate,
+}
+
+/// Имя счёта — непустая строка без `,`, `;`, управляющих символов и пробелов
+/// по краям: оно пишется в строки снимка, журнала и архива как есть
+pub fn check_name(name: &str) -> Result<(), BankError> {
+    let valid = !name.is_empty()
+        && name.trim() == name
+        && !name.chars().any(|c| c == ',' || c == ';' || c.is_control());
+    if valid {
+        Ok(())
+    } else {
+        Err(BankError::BadName(name.to_string()))
+    }
 }
 
 pub struct Storage {
     pub accounts: HashMap<Name, Balance>,
+    /// Открывать ли счета при зачислении на неизвестное имя
+    pub account_policy: AccountPolicy,
+    /// Курсы для обмена валют внутри счёта
+    pub rates: RateTable,
+    /// Платёжные поручения по номерам, включая исполненные и отменённые
+    pub orders: BTreeMap<OrderId, StandingOrder>,
+    /// Транзакции, проведённые с ключами идемпотентности, по ключам
+    pub keys: BTreeMap<String, KeyRecord>,
+    /// Часы, по которым ставится время операций в истории
+    pub clock: Arc<dyn Clock + Send + Sync>,
+    /// Сколько истории держать в памяти; остальное переносит [`Storage::archive_history`]
+    pub retention: Retention,
+    pub(crate) archive: Option<Archive>,
+    pub(crate) journal: Option<Journal>,
+    /// Следующий свободный номер транзакции; общий у `SharedStorage` и его копий
+    pub(crate) next_tx: Arc<AtomicU64>,
+    // Номер транзакции, которую проводит самый внешний `atomic`, если он уже выдан
+    tx: Option<TxId>,
+    // Назначение платежа для записей, которые сейчас попадают в историю
+    memo: Option<String>,
+    // По кадру на каждый вложенный вызов `atomic`: в журнал пишет только самый внешний
+    undo: Vec<Undo>,
+}
+
+/// Прежние значения счетов, поручений и ключей, которые операция тронула внутри
+/// одного вызова `atomic`; `None` — записи до неё не было. По ним `atomic` откатывает
+/// ошибку и пишет журнал, не копируя всё хранилище.
+#[derive(Default)]
+struct Undo {
+    accounts: HashMap<Name, Option<Balance>>,
+    orders: BTreeMap<OrderId, Option<StandingOrder>>,
+    keys: BTreeMap<String, Option<KeyRecord>>,
+}
+
+impl Undo {
+    /// Переносит кадр завершившегося вложенного вызова во внешний: прежнее значение
+    /// внешнего кадра старше и остаётся
+    fn merge(&mut self, inner: Undo) {
+        for (name, balance) in inner.accounts {
+            self.accounts.entry(name).or_insert(balance);
+        }
+        for (id, order) in inner.orders {
+            self.orders.entry(id).or_insert(order);
+        }
+        for (key, record) in inner.keys {
+            self.keys.entry(key).or_insert(record);
+        }
+    }
 }
 
 impl Default for Storage {
@@ -26,91 +108,758 @@ impl Storage {
     pub fn new() -> Self {
         Storage {
             accounts: HashMap::new(),
+            account_policy: AccountPolicy::default(),
+            rates: RateTable::new(),
+            orders: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            clock: Arc::new(SystemClock),
+            retention: Retention::default(),
+            archive: None,
+            journal: None,
+            next_tx: Arc::new(AtomicU64::new(1)),
+            tx: None,
+            memo: None,
+            undo: Vec::new(),
         }
     }
 
+    /// Добавляет пользователя со счётом в базовой валюте; `None`, если он уже есть,
+    /// имя не проходит [`check_name`] или изменение не удалось записать в журнал
     pub fn add_user(&mut self, name: Name) -> Option<u64> {
-        if let std::collections::hash_map::Entry::Vacant(e) = self.accounts.entry(name) {
-            e.insert(Balance::new());
-            Some(0)
-        } else {
-            None
+        self.add_user_with_currency(name, Currency::default())
+    }
+
+    /// Добавляет пользователя со счётом в валюте `currency`
+    pub fn add_user_with_currency(&mut self, name: Name, currency: Currency) -> Option<u64> {
+        self.create_account(&name, currency, None).ok().map(|()| 0)
+    }
+
+    /// Открывает счёт в валюте `currency` и зачисляет на него начальный баланс `initial`
+    /// одной записью журнала: если зачисление не удалось, счёт тоже не открывается
+    pub fn create_account(
+        &mut self,
+        name: &Name,
+        currency: Currency,
+        initial: Option<Money>,
+    ) -> Result<(), BankError> {
+        check_name(name)?;
+        self.atomic(|storage| {
+            if storage.accounts.contains_key(name) {
+                return Err(BankError::AccountExists(name.clone()));
+            }
+            storage.touch(name);
+            storage
+                .accounts
+                .insert(name.clone(), Balance::with_currency(currency));
+            match initial.filter(|initial| !initial.amount.is_zero()) {
+                Some(initial) => storage.credit(name, initial),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Открывает счёт в валюте `currency`, если его ещё нет и это разрешает
+    /// [`AccountPolicy`]. Вызывается перед зачислением; при `Strict` ничего не делает,
+    /// и зачисление на неизвестное имя завершится `UserNotFound`. Новый счёт с именем,
+    /// не прошедшим [`check_name`], не открывается: это [`BankError::BadName`].
+    pub fn ensure_account(&mut self, name: &Name, currency: Currency) -> Result<(), BankError> {
+        if self.account_policy == AccountPolicy::AutoCreate && !self.accounts.contains_key(name) {
+            check_name(name)?;
+            self.touch(name);
+            self.accounts
+                .insert(name.clone(), Balance::with_currency(currency));
         }
+        Ok(())
     }
 
-    pub fn remove_user(&mut self, name: &Name) -> Option<Balance> {
-        self.accounts.remove(name)
+    /// Удаляет пользователя вместе с деньгами и историей, не оставляя следа, и
+    /// возвращает его баланс. Годится только для счетов, открытых по ошибке, — обычный
+    /// путь [`Storage::close_account`]. Счёт с переводами удалить нельзя
+    /// ([`BankError::HasTransfers`]): их пары на других счетах остались бы без второй
+    /// половины, и оборотно-сальдовая ведомость перестала бы сходиться.
+    pub fn remove_user(&mut self, name: &Name) -> Result<Balance, BankError> {
+        let balance = self
+            .accounts
+            .get(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        if balance.has_transfers() {
+            return Err(BankError::HasTransfers(name.clone()));
+        }
+        self.atomic(|storage| {
+            storage.touch(name);
+            Ok(storage
+                .accounts
+                .remove(name)
+                .expect("счёт только что найден"))
+        })
     }
 
     pub fn get_balance(&self, name: &Name) -> Option<Balance> {
         self.accounts.get(name).cloned()
     }
 
-    /// Получает все аккаунты с их балансами
-    pub fn get_all(&self) -> Vec<(Name, u64)> {
+    /// Получает все аккаунты с их балансами (при овердрафте — отрицательными)
+    pub fn get_all(&self) -> Vec<(Name, SignedAmount)> {
         self.accounts
             .iter()
             .map(|(n, b)| (n.clone(), b.result))
             .collect()
     }
 
-    /// Загружает данные из CSV-файла или создаёт хранилище с дефолтными пользователями
-    pub fn load_data(file: &str) -> Storage {
-        let mut storage = Storage::new();
+    /// Зачисляет `amount` на счёт извне банка и записывает операцию в историю.
+    /// Общий путь для `BalanceManager` и транзакций; атомарность и журнал — забота вызывающего.
+    pub fn credit(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.add_money(name, amount, OpKind::Deposit(amount.amount), None)
+    }
 
-        // Проверяем, существует ли файл
-        if Path::new(file).exists() {
-            // Открываем файл
-            let file = File::open(file).unwrap();
+    /// Списывает `amount` со счёта за пределы банка, при нехватке собственных денег —
+    /// в пределах кредитного лимита, и записывает операцию в историю
+    pub fn debit(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.take_money(name, amount, OpKind::Withdraw(amount.amount), None)
+    }
 
-            // Оборачиваем файл в BufReader
-            // BufReader читает данные блоками и хранит их в буфере,
-            // поэтому построчное чтение (lines()) работает быстрее, чем читать по байту
-            let reader = io::BufReader::new(file);
+    /// Зачисление операцией `op`, которая увеличивает баланс на `amount`;
+    /// у переводов `counterparty` — счёт отправителя
+    fn add_money(
+        &mut self,
+        name: &Name,
+        amount: Money,
+        op: OpKind,
+        counterparty: Option<&Name>,
+    ) -> Result<(), BankError> {
+        self.settle_interest(name)?;
+        let meta = self.meta(counterparty);
+        let balance = self.account_in(name, amount.currency)?;
+        if balance.result.checked_add(amount.amount).is_none() {
+            return Err(BankError::Overflow {
+                account: name.clone(),
+                balance: balance.money(),
+                amount,
+            });
+        }
+        balance.process_with(&meta, &[&op]);
+        Ok(())
+    }
 
-            // Читаем файл построчно
-            for line in reader.lines().map_while(Result::ok) {
-                // Разделяем строку по запятой: "Name,Balance"
-                let parts: Vec<&str> = line.trim().split(',').collect();
+    /// Списание операцией `op`, которая уменьшает баланс на `amount`;
+    /// у переводов `counterparty` — счёт получателя
+    fn take_money(
+        &mut self,
+        name: &Name,
+        amount: Money,
+        op: OpKind,
+        counterparty: Option<&Name>,
+    ) -> Result<(), BankError> {
+        self.settle_interest(name)?;
+        let today = self.clock.today();
+        let meta = self.meta(counterparty);
+        let balance = self.account_in(name, amount.currency)?;
+        check_available(name, balance, amount, today)?;
+        balance.process_with(&meta, &[&op]);
+        Ok(())
+    }
 
-                if parts.len() == 2 {
-                    let name = parts[0].to_string();
-                    // Пробуем преобразовать баланс из строки в число
-                    let balance: u64 = parts[1].parse().unwrap_or(0);
+    /// Реквизиты новой записи истории: номер транзакции из [`Storage::tx_id`],
+    /// время по часам хранилища и назначение, если его задал [`Storage::with_memo`]
+    pub(crate) fn meta(&mut self, counterparty: Option<&Name>) -> OpMeta {
+        OpMeta {
+            tx: Some(self.tx_id()),
+            at: Some(self.clock.now()),
+            counterparty: counterparty.cloned(),
+            memo: self.memo.clone(),
+        }
+    }
+
+    /// Выполняет `f`, помечая все новые записи истории назначением `memo`
+    pub(crate) fn with_memo<T>(&mut self, memo: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = self.memo.replace(memo.to_string());
+        let value = f(self);
+        self.memo = outer;
+        value
+    }
+
+    /// Номер транзакции для новых записей истории. Внутри `atomic` все операции
+    /// получают номер самого внешнего вызова, вне его каждая операция — свой.
+    pub(crate) fn tx_id(&mut self) -> TxId {
+        if self.undo.is_empty() {
+            return self.next_tx.fetch_add(1, Ordering::Relaxed);
+        }
+        *self
+            .tx
+            .get_or_insert_with(|| self.next_tx.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Наибольший номер транзакции в истории счетов и ключах идемпотентности
+    fn last_tx(&self) -> TxId {
+        let in_history = self
+            .accounts
+            .values()
+            .flat_map(|balance| &balance.last_ops)
+            .filter_map(|op| op.meta.tx);
+        let in_keys = self.keys.values().map(|record| record.tx);
+        in_history.chain(in_keys).max().unwrap_or(0)
+    }
 
-                    // Добавляем пользователя и выставляем баланс
-                    storage.add_user(name.clone());
-                    let _ = storage.deposit(&name, balance);
+    /// Задаёт условия начисления процентов по счёту; `None` отключает начисление.
+    /// Уже набежавшие по старым условиям проценты нужно начислить до смены условий.
+    pub fn set_interest(
+        &mut self,
+        name: &Name,
+        terms: Option<InterestTerms>,
+    ) -> Result<(), BankError> {
+        self.atomic(|storage| {
+            let balance = storage.open_account(name)?;
+            balance.interest = terms;
+            Ok(())
+        })
+    }
+
+    /// Начисляет проценты по всем счетам с условиями по день `until` (не включая его).
+    /// Атомарно: если хоть один счёт переполнился бы, не начисляется ничего.
+    /// Возвращает ненулевые начисления в порядке имён.
+    pub fn accrue_interest(
+        &mut self,
+        until: Date,
+        rounding: Rounding,
+    ) -> Result<Vec<(Name, Money)>, BankError> {
+        self.atomic(|storage| {
+            let mut names: Vec<Name> = storage.accounts.keys().cloned().collect();
+            names.sort();
+            let mut accrued = Vec::new();
+            for name in names {
+                if storage.accounts[&name].interest.is_none() {
+                    continue;
                 }
+                let interest = storage.accrue_account(&name, until, rounding)?;
+                if !interest.is_zero() {
+                    let currency = storage.accounts[&name].currency;
+                    accrued.push((
+                        name,
+                        Money {
+                            amount: interest,
+                            currency,
+                        },
+                    ));
+                }
+            }
+            Ok(accrued)
+        })
+    }
+
+    /// Начисляет проценты по счёту `name` с условиями по день `until` (не включая его)
+    /// и помечает записи реквизитами текущей транзакции. Возвращает начисленное.
+    fn accrue_account(
+        &mut self,
+        name: &Name,
+        until: Date,
+        rounding: Rounding,
+    ) -> Result<Amount, BankError> {
+        let meta = self.meta(None);
+        let balance = self.account_mut(name).expect("счёт проверен вызывающим");
+        let recorded = balance.last_ops.len();
+        match balance.accrue_interest(until, rounding) {
+            Ok(interest) => {
+                for op in &mut balance.last_ops[recorded..] {
+                    op.meta = meta.clone();
+                }
+                Ok(interest)
+            }
+            Err(interest) => Err(BankError::Overflow {
+                amount: Money {
+                    amount: interest,
+                    currency: balance.currency,
+                },
+                account: name.clone(),
+                balance: balance.money(),
+            }),
+        }
+    }
+
+    /// Доначисляет проценты по сегодняшний день перед движением денег по счёту.
+    /// Начисление берёт за базу остаток на момент расчёта, поэтому без этого деньги,
+    /// внесённые в последний день месяца, получили бы проценты за весь месяц, а снятые —
+    /// не получили бы ничего. Округление, как у [`crate::interest::InterestEngine`], вниз.
+    fn settle_interest(&mut self, name: &Name) -> Result<(), BankError> {
+        let today = self.clock.today();
+        let due = self
+            .accounts
+            .get(name)
+            .and_then(|balance| balance.interest)
+            .is_some_and(|terms| terms.accrued_to < today);
+        if due {
+            self.accrue_account(name, today, Rounding::Down)?;
+        }
+        Ok(())
+    }
+
+    /// Устанавливает кредитный лимит счёта в его основной валюте.
+    /// Лимит нельзя опустить ниже текущего долга: сначала долг нужно погасить.
+    pub fn set_credit_limit(&mut self, name: &Name, limit: Amount) -> Result<(), BankError> {
+        self.atomic(|storage| {
+            let balance = storage.open_account(name)?;
+            let debt = balance.result.debt();
+            if limit < debt {
+                return Err(BankError::LimitBelowDebt {
+                    account: name.clone(),
+                    limit: Money {
+                        amount: limit,
+                        currency: balance.currency,
+                    },
+                    debt: Money {
+                        amount: debt,
+                        currency: balance.currency,
+                    },
+                });
+            }
+            balance.credit_limit = limit;
+            Ok(())
+        })
+    }
+
+    /// Закрывает счёт. Остаток в основной валюте переводится на счёт `payout`;
+    /// без него закрыть можно только пустой счёт. Остатки в других валютах нужно
+    /// заранее обменять, долг — погасить. Платёжные поручения со счёта и на счёт
+    /// отменяются. Закрытый счёт остаётся в хранилище с историей, но любые операции
+    /// с ним завершаются [`BankError::AccountClosed`]. Проценты по сегодняшний день
+    /// начисляются до закрытия и выплачиваются с остатком. Возвращает выплаченную сумму.
+    pub fn close_account(
+        &mut self,
+        name: &Name,
+        payout: Option<&Name>,
+    ) -> Result<Money, BankError> {
+        self.atomic(|storage| {
+            storage.settle_interest(name)?;
+            let balance = storage.open_account(name)?;
+            let mut paid = Money {
+                amount: Amount::ZERO,
+                currency: balance.currency,
+            };
+            if let Some(leftover) = balance.leftover() {
+                match (payout, leftover.amount.to_amount()) {
+                    (Some(to), Some(amount)) if leftover.currency == balance.currency => {
+                        paid.amount = amount;
+                        storage.transfer(name, to, paid)?;
+                    }
+                    _ => {
+                        return Err(BankError::AccountNotEmpty {
+                            account: name.clone(),
+                            balance: leftover,
+                        });
+                    }
+                }
+            }
+
+            let meta = storage.meta(None);
+            let balance = storage.open_account(name)?;
+            if let Some(leftover) = balance.leftover() {
+                return Err(BankError::AccountNotEmpty {
+                    account: name.clone(),
+                    balance: leftover,
+                });
+            }
+            balance.process_with(&meta, &[&OpKind::CloseAccount]);
+            let cancelled: Vec<OrderId> = storage
+                .orders
+                .values()
+                .filter(|order| {
+                    order.status == OrderStatus::Active
+                        && (&order.from == name || &order.to == name)
+                })
+                .map(|order| order.id)
+                .collect();
+            for id in cancelled {
+                storage
+                    .order_mut(id)
+                    .expect("номер взят из поручений")
+                    .status = OrderStatus::Cancelled;
+            }
+            Ok(paid)
+        })
+    }
+
+    /// Переводит `amount` со счёта `from` на счёт `to`. Перевод самому себе запрещён:
+    /// он ничего не меняет, но почти всегда означает опечатку в имени.
+    /// В историю пишутся парные `TransferOut` и `TransferIn`, которые в книге
+    /// гасят друг друга на транзитном счёте.
+    pub fn transfer(&mut self, from: &Name, to: &Name, amount: Money) -> Result<(), BankError> {
+        if from == to {
+            return Err(BankError::SelfTransfer(from.clone()));
+        }
+        self.take_money(from, amount, OpKind::TransferOut(amount.amount), Some(to))?;
+        self.ensure_account(to, amount.currency)?;
+        self.add_money(to, amount, OpKind::TransferIn(amount.amount), Some(from))
+    }
+
+    /// Обменивает `amount` на валюту `to` по таблице курсов и записывает операцию
+    /// вместе с применённым курсом в историю. Возвращает полученную сумму.
+    pub fn exchange(
+        &mut self,
+        name: &Name,
+        amount: Money,
+        to: Currency,
+    ) -> Result<Money, BankError> {
+        let (bought, rate) = self.rates.convert(amount, to).ok_or(BankError::NoRate {
+            from: amount.currency,
+            to,
+        })?;
+        if bought.amount.is_zero() {
+            return Err(BankError::ExchangeTooSmall(amount));
+        }
+
+        self.settle_interest(name)?;
+        let today = self.clock.today();
+        let meta = self.meta(None);
+        let balance = self.open_account(name)?;
+        if balance.amount_in(amount.currency).is_none() {
+            return Err(BankError::CurrencyMismatch {
+                account: name.clone(),
+                expected: balance.currency,
+                found: amount.currency,
+            });
+        }
+        check_available(name, balance, amount, today)?;
+        let held = if to == balance.currency {
+            balance.result
+        } else {
+            balance.amount_in(to).unwrap_or(Amount::ZERO).into()
+        };
+        if held.checked_add(bought.amount).is_none() {
+            return Err(BankError::Overflow {
+                account: name.clone(),
+                balance: SignedMoney {
+                    amount: held,
+                    currency: to,
+                },
+                amount: bought,
+            });
+        }
+
+        balance.process_with(
+            &meta,
+            &[&OpKind::Exchange {
+                sold: amount,
+                bought,
+                rate,
+            }],
+        );
+        Ok(bought)
+    }
+
+    /// Счёт, если он существует и не закрыт
+    pub(crate) fn open_account(&mut self, name: &Name) -> Result<&mut Balance, BankError> {
+        let balance = self
+            .account_mut(name)
+            .ok_or_else(|| BankError::UserNotFound(name.clone()))?;
+        if balance.is_closed() {
+            return Err(BankError::AccountClosed(name.clone()));
+        }
+        Ok(balance)
+    }
+
+    /// Счёт, если он существует, не закрыт и ведётся в валюте `currency`
+    pub(crate) fn account_in(
+        &mut self,
+        name: &Name,
+        currency: Currency,
+    ) -> Result<&mut Balance, BankError> {
+        let balance = self.open_account(name)?;
+        if balance.currency != currency {
+            return Err(BankError::CurrencyMismatch {
+                account: name.clone(),
+                expected: balance.currency,
+                found: currency,
+            });
+        }
+        Ok(balance)
+    }
+
+    /// Запоминает счёт `name` таким, каким он был до первого изменения внутри `atomic`.
+    /// Всё, что меняет `accounts` внутри `atomic`, сначала вызывает этот метод
+    /// (или берёт счёт через [`Storage::account_mut`]): иначе изменение не попадёт
+    /// в журнал и не откатится при ошибке. Вне `atomic` ничего не делает.
+    pub(crate) fn touch(&mut self, name: &Name) {
+        if let Some(undo) = self.undo.last_mut()
+            && !undo.accounts.contains_key(name)
+        {
+            undo.accounts
+                .insert(name.clone(), self.accounts.get(name).cloned());
+        }
+    }
+
+    /// То же, что [`Storage::touch`], для платёжного поручения `id`
+    pub(crate) fn touch_order(&mut self, id: OrderId) {
+        if let Some(undo) = self.undo.last_mut() {
+            undo.orders
+                .entry(id)
+                .or_insert_with(|| self.orders.get(&id).cloned());
+        }
+    }
+
+    /// То же, что [`Storage::touch`], для ключа идемпотентности `key`
+    pub(crate) fn touch_key(&mut self, key: &str) {
+        if let Some(undo) = self.undo.last_mut()
+            && !undo.keys.contains_key(key)
+        {
+            undo.keys
+                .insert(key.to_string(), self.keys.get(key).cloned());
+        }
+    }
+
+    /// Счёт для изменения, уже запомненный через [`Storage::touch`]
+    pub(crate) fn account_mut(&mut self, name: &Name) -> Option<&mut Balance> {
+        self.touch(name);
+        self.accounts.get_mut(name)
+    }
+
+    /// Поручение для изменения, уже запомненное через [`Storage::touch_order`]
+    pub(crate) fn order_mut(&mut self, id: OrderId) -> Option<&mut StandingOrder> {
+        self.touch_order(id);
+        self.orders.get_mut(&id)
+    }
+
+    /// Выполняет `f` атомарно: если `f` вернула ошибку, все изменения счетов,
+    /// платёжных поручений и ключей идемпотентности откатываются.
+    /// Успешный результат самого внешнего вызова подтверждается только после того,
+    /// как изменения записаны в журнал и сброшены на диск.
+    ///
+    /// Запоминаются и сравниваются только счета, поручения и ключи, которые `f`
+    /// меняет через методы `Storage`, поэтому цена операции не зависит от размера банка.
+    /// Прямые изменения полей `accounts`, `orders` и `keys` внутри `f` не отслеживаются.
+    pub fn atomic<T, E: From<io::Error>>(
+        &mut self,
+        f: impl FnOnce(&mut Storage) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.undo.push(Undo::default());
+        let result = f(self);
+        let undo = self.undo.pop().expect("кадр этого вызова");
+
+        let result = result.and_then(|value| {
+            if self.undo.is_empty() {
+                self.write_journal(&undo)?;
             }
+            Ok(value)
+        });
+
+        if self.undo.is_empty() {
+            self.tx = None;
+        }
+        if result.is_err() {
+            self.rollback(undo);
+        } else if let Some(outer) = self.undo.last_mut() {
+            outer.merge(undo);
+        }
+        result
+    }
+
+    /// Возвращает тронутым счетам, поручениям и ключам прежние значения
+    fn rollback(&mut self, undo: Undo) {
+        for (name, balance) in undo.accounts {
+            match balance {
+                Some(balance) => self.accounts.insert(name, balance),
+                None => self.accounts.remove(&name),
+            };
+        }
+        for (id, order) in undo.orders {
+            match order {
+                Some(order) => self.orders.insert(id, order),
+                None => self.orders.remove(&id),
+            };
+        }
+        for (key, record) in undo.keys {
+            match record {
+                Some(record) => self.keys.insert(key, record),
+                None => self.keys.remove(&key),
+            };
+        }
+    }
+
+    fn write_journal(&mut self, undo: &Undo) -> io::Result<()> {
+        let Some(journal) = self.journal.as_mut() else {
+            return Ok(());
+        };
+        let (orders_before, orders_after) = touched(&undo.orders, &self.orders);
+        let (keys_before, keys_after) = touched(&undo.keys, &self.keys);
+        let entry = JournalEntry::diff_touched(&undo.accounts, &self.accounts)
+            .with_orders(&orders_before, &orders_after)
+            .with_keys(&keys_before, &keys_after);
+        if entry.is_empty() {
+            return Ok(());
+        }
+        journal.append(&entry)
+    }
+
+    /// Загружает данные из CSV-файла, затем повторяет поверх снимка операции
+    /// из журнала `<file>.journal`. Если файла нет, хранилище начинается пустым.
+    /// Старая история счетов читается из архива `<file>.archive` по запросу.
+    ///
+    /// В режиме [`LoadMode::Strict`] любая плохая строка — ошибка со списком всех
+    /// отвергнутых строк. В режиме [`LoadMode::Lenient`] плохие строки пропускаются
+    /// и возвращаются вторым элементом кортежа, чтобы вызывающий мог о них сообщить.
+    pub fn load_data(
+        file: &str,
+        mode: LoadMode,
+    ) -> Result<(Storage, Vec<RejectedLine>), LoadError> {
+        let (mut storage, rejected) = if Path::new(file).exists() {
+            // BufReader читает данные блоками и хранит их в буфере,
+            // поэтому построчное чтение (lines()) работает быстрее, чем читать по байту
+            Storage::from_reader(io::BufReader::new(File::open(file)?), mode)?
         } else {
-            // если файла нет, создаём пользователей с нуля
-            for u in ["John", "Alice", "Bob", "Vasya"] {
-                storage.add_user(u.to_string());
+            (Storage::new(), Vec::new())
+        };
+
+        // Восстанавливаем то, что успели подтвердить после последнего снимка
+        let (journal, entries) = Journal::open(Journal::path_for(file))?;
+        for entry in &entries {
+            entry.apply(
+                &mut storage.accounts,
+                &mut storage.orders,
+                &mut storage.keys,
+            );
+        }
+        storage.journal = Some(journal);
+        storage.archive = Some(Archive::open(Archive::path_for(file))?);
+        storage.next_tx = Arc::new(AtomicU64::new(storage.last_tx() + 1));
+
+        Ok((storage, rejected))
+    }
+
+    /// Разбирает снимок. Файлы с заголовком [`SNAPSHOT_HEADER`] хранят строки
+    /// "Name,Currency,Balance,Limit,Interest,Holdings,Holds,Archived,Ops" с валютой счёта,
+    /// балансом со знаком, кредитным лимитом, условиями начисления процентов (пусто,
+    /// если их нет), остатками в других валютах ("USD:100 EUR:5"), блокировками
+    /// ("7:500:2026-02-01"), сводкой архива в формате [`Archived`](crate::Archived)
+    /// и историей операций после архивных, после строки "#orders" —
+    /// платёжные поручения в формате [`StandingOrder`], а после "#keys" — ключи
+    /// идемпотентности в формате [`KeyRecord`]. Старые форматы тоже читаются:
+    /// "#bank-system v10" — то же без сводки архива, с полной историей,
+    /// "#bank-system v9" — ещё и без времени, второго счёта и назначения платежа,
+    /// "#bank-system v8" — то же без номеров транзакций и ключей,
+    /// "#bank-system v7" — "Name,Currency,Balance,Limit,Interest,Holdings,Ops" без блокировок,
+    /// "#bank-system v6" — то же без платёжных поручений,
+    /// "#bank-system v5" — "Name,Currency,Balance,Limit,Holdings,Ops" без процентов,
+    /// "#bank-system v4" — "Name,Currency,Balance,Holdings,Ops" без кредита,
+    /// "#bank-system v3" — "Name,Currency,Balance,Ops",
+    /// счета из более ранних форматов открываются в базовой валюте:
+    /// "#bank-system v2" — "Name,Balance,Ops", файлы без заголовка — "Name,Balance",
+    /// где история восстанавливается одним пополнением. Пустые строки пропускаются.
+    pub fn from_reader(
+        reader: impl BufRead,
+        mode: LoadMode,
+    ) -> Result<(Storage, Vec<RejectedLine>), LoadError> {
+        let mut storage = Storage::new();
+        let mut rejected = Vec::new();
+        let mut format = SnapshotFormat::V1;
+        let mut section = Section::Accounts;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if index == 0 && line.starts_with('#') {
+                format = SnapshotFormat::from_header(line)?;
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if format >= SnapshotFormat::V7 && line == ORDERS_SECTION {
+                section = Section::Orders;
+                continue;
+            }
+            if format >= SnapshotFormat::V9 && line == KEYS_SECTION {
+                section = Section::Keys;
+                continue;
+            }
+
+            let parsed = match section {
+                Section::Accounts => parse_line(line, &storage, format).map(|(name, balance)| {
+                    storage.accounts.insert(name, balance);
+                }),
+                Section::Orders => parse_order(line, &storage).map(|order| {
+                    storage.orders.insert(order.id, order);
+                }),
+                Section::Keys => parse_key(line, &storage).map(|record| {
+                    storage.keys.insert(record.key.clone(), record);
+                }),
+            };
+            if let Err(reason) = parsed {
+                rejected.push(RejectedLine {
+                    line: index + 1,
+                    reason,
+                });
             }
         }
 
-        storage
+        if mode == LoadMode::Strict && !rejected.is_empty() {
+            return Err(LoadError::Rejected(rejected));
+        }
+        Ok((storage, rejected))
     }
 
     /// Сохраняет текущее состояние Storage в CSV-файл
     pub fn save(&self, file: &str) {
-        let mut data = String::new();
+        let mut data = format!("{}\n", SNAPSHOT_HEADER);
 
-        // Собираем все данные в одну строку формата "Name,Balance"
-        for (name, balance) in self.get_all() {
-            data.push_str(&format!("{},{}\n", name, balance));
+        // Собираем все данные в одну строку формата
+        // "Name,Currency,Balance,Limit,Interest,Holdings,Holds,Archived,Ops",
+        // сортируя по имени, чтобы файл не менялся от порядка в HashMap
+        let mut names: Vec<&Name> = self.accounts.keys().collect();
+        names.sort();
+        for name in names {
+            let balance = &self.accounts[name];
+            let ops: Vec<String> = balance.last_ops.iter().map(|op| op.to_string()).collect();
+            data.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                name,
+                balance.currency,
+                balance.result,
+                balance.credit_limit,
+                balance
+                    .interest
+                    .map(|terms| terms.to_string())
+                    .unwrap_or_default(),
+                balance.format_holdings(),
+                balance.format_holds(),
+                balance.archived,
+                ops.join(" ")
+            ));
+        }
+        if !self.orders.is_empty() {
+            data.push_str(ORDERS_SECTION);
+            data.push('\n');
+            for order in self.orders.values() {
+                data.push_str(&format!("{}\n", order));
+            }
+        }
+        if !self.keys.is_empty() {
+            data.push_str(KEYS_SECTION);
+            data.push('\n');
+            for record in self.keys.values() {
+                data.push_str(&format!("{}\n", record));
+            }
         }
 
-        // Записываем в файл
+        // Пишем во временный файл и переименовываем: так после падения на диске
+        // останется либо старый снимок, либо новый, но не обрезанный.
         // Здесь мы не используем BufWriter, потому что сразу пишем всю строку целиком.
-        fs::write(file, data).expect("Не удалось записать файл");
+        let tmp = format!("{}.tmp", file);
+        let mut out = File::create(&tmp).expect("Не удалось записать файл");
+        out.write_all(data.as_bytes())
+            .and_then(|_| out.sync_all())
+            .expect("Не удалось записать файл");
+        fs::rename(&tmp, file).expect("Не удалось записать файл");
+
+        // Всё из журнала теперь есть в снимке
+        if let Some(journal) = &self.journal
+            && journal.path() == Journal::path_for(file)
+        {
+            journal.clear().expect("Не удалось очистить журнал");
+        }
     }
 
     pub fn process_if_deposit(
         &mut self,
-        operations: &[(bool, Name, u64)],
-    ) -> Result<(), BalanceManagerError> {
+        operations: &[(bool, Name, Money)],
+    ) -> Result<(), BankError> {
         for (is_deposit, name, sum) in operations {
             if *is_deposit {
                 self.deposit(name, *sum)?;
@@ -122,40 +871,349 @@ impl Storage {
     }
 }
 
-impl BalanceManager for Storage {
-    fn deposit(&mut self, name: &Name, amount: u64) -> Result<(), BalanceManagerError> {
-        if let Some(balance) = self.accounts.get_mut(name) {
-            let op = OpKind::Deposit(amount as u32);
-            let ops_refs = [&op];
-            balance.process(&ops_refs);
-            Ok(())
-        } else {
-            Err(BalanceManagerError::UserNotFound(name.clone()))
+/// Прежние и текущие значения тронутых записей `undo` из `current`
+fn touched<K: Ord + Clone, V: Clone>(
+    undo: &BTreeMap<K, Option<V>>,
+    current: &BTreeMap<K, V>,
+) -> (BTreeMap<K, V>, BTreeMap<K, V>) {
+    let before = undo
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), value.clone()?)))
+        .collect();
+    let after = undo
+        .keys()
+        .filter_map(|key| Some((key.clone(), current.get(key)?.clone())))
+        .collect();
+    (before, after)
+}
+
+/// Проверяет, что на счёте хватает денег на `amount` с учётом кредитной линии
+/// и действующих в день `today` блокировок
+pub(crate) fn check_available(
+    name: &Name,
+    balance: &Balance,
+    amount: Money,
+    today: Date,
+) -> Result<(), BankError> {
+    let available = balance
+        .available_in(amount.currency, today)
+        .unwrap_or(Amount::ZERO);
+    if available >= amount.amount {
+        return Ok(());
+    }
+    let credit = if amount.currency == balance.currency {
+        balance.available_credit()
+    } else {
+        Amount::ZERO
+    };
+    Err(BankError::NotEnoughMoney {
+        account: name.clone(),
+        required: amount,
+        available: Money {
+            amount: available,
+            currency: amount.currency,
+        },
+        credit: Money {
+            amount: credit,
+            currency: amount.currency,
+        },
+    })
+}
+
+/// Заголовок текущей версии снимка
+pub const SNAPSHOT_HEADER: &str = "#bank-system v11";
+
+/// Строка снимка, после которой идут платёжные поручения
+const ORDERS_SECTION: &str = "#orders";
+
+/// Строка снимка, после которой идут ключи идемпотентности
+const KEYS_SECTION: &str = "#keys";
+
+/// Часть снимка, которую сейчас читаем
+enum Section {
+    Accounts,
+    Orders,
+    Keys,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SnapshotFormat {
+    /// "Name,Balance" без заголовка
+    V1,
+    /// "Name,Balance,Ops"
+    V2,
+    /// "Name,Currency,Balance,Ops"
+    V3,
+    /// "Name,Currency,Balance,Holdings,Ops"
+    V4,
+    /// "Name,Currency,Balance,Limit,Holdings,Ops"
+    V5,
+    /// "Name,Currency,Balance,Limit,Interest,Holdings,Ops"
+    V6,
+    /// Как V6, плюс платёжные поручения после "#orders"
+    V7,
+    /// "Name,Currency,Balance,Limit,Interest,Holdings,Holds,Ops" и платёжные поручения
+    V8,
+    /// Как V8, плюс номера транзакций в истории и ключи идемпотентности после "#keys"
+    V9,
+    /// Как V9, плюс время, второй счёт перевода и назначение платежа в истории
+    V10,
+    /// "Name,Currency,Balance,Limit,Interest,Holdings,Holds,Archived,Ops": как V10,
+    /// плюс сводка операций, перенесённых в архив
+    V11,
+}
+
+impl SnapshotFormat {
+    fn from_header(header: &str) -> Result<Self, LoadError> {
+        match header {
+            "#bank-system v2" => Ok(SnapshotFormat::V2),
+            "#bank-system v3" => Ok(SnapshotFormat::V3),
+            "#bank-system v4" => Ok(SnapshotFormat::V4),
+            "#bank-system v5" => Ok(SnapshotFormat::V5),
+            "#bank-system v6" => Ok(SnapshotFormat::V6),
+            "#bank-system v7" => Ok(SnapshotFormat::V7),
+            "#bank-system v8" => Ok(SnapshotFormat::V8),
+            "#bank-system v9" => Ok(SnapshotFormat::V9),
+            "#bank-system v10" => Ok(SnapshotFormat::V10),
+            SNAPSHOT_HEADER => Ok(SnapshotFormat::V11),
+            other => Err(LoadError::UnsupportedFormat(other.to_string())),
         }
     }
 
-    fn withdraw(&mut self, name: &Name, amount: u64) -> Result<(), BalanceManagerError> {
-        if let Some(balance) = self.accounts.get_mut(name) {
-            if balance.result >= amount {
-                let op = OpKind::Withdraw(amount as u32);
-                let ops_refs = [&op];
-                balance.process(&ops_refs);
-                Ok(())
-            } else {
-                Err(BalanceManagerError::NotEnoughMoney {
-                    required: amount,
-                    available: balance.result,
-                })
-            }
-        } else {
-            Err(BalanceManagerError::UserNotFound(name.clone()))
+    fn fields(self) -> usize {
+        match self {
+            SnapshotFormat::V1 => 2,
+            SnapshotFormat::V2 => 3,
+            SnapshotFormat::V3 => 4,
+            SnapshotFormat::V4 => 5,
+            SnapshotFormat::V5 => 6,
+            SnapshotFormat::V6 | SnapshotFormat::V7 => 7,
+            SnapshotFormat::V8 | SnapshotFormat::V9 | SnapshotFormat::V10 => 8,
+            SnapshotFormat::V11 => 9,
         }
     }
 }
 
+/// Поля строки снимка; в старых форматах части полей нет
+struct LineFields<'a> {
+    currency: Option<&'a str>,
+    value: &'a str,
+    limit: Option<&'a str>,
+    interest: Option<&'a str>,
+    holdings: Option<&'a str>,
+    holds: Option<&'a str>,
+    archived: Option<&'a str>,
+    ops: Option<&'a str>,
+}
+
+impl<'a> LineFields<'a> {
+    fn new(value: &'a str) -> Self {
+        LineFields {
+            currency: None,
+            value,
+            limit: None,
+            interest: None,
+            holdings: None,
+            holds: None,
+            archived: None,
+            ops: None,
+        }
+    }
+}
+
+/// Разбирает одну строку снимка
+fn parse_line(
+    line: &str,
+    storage: &Storage,
+    format: SnapshotFormat,
+) -> Result<(Name, Balance), LineError> {
+    // Разделяем строку по запятой: от "Name,Balance" до
+    // "Name,Currency,Balance,Limit,Interest,Holdings,Ops"
+    let parts: Vec<&str> = line.split(',').collect();
+    if parts.len() != format.fields() {
+        return Err(LineError::FieldCount {
+            expected: format.fields(),
+            found: parts.len(),
+        });
+    }
+
+    let name = parts[0].trim();
+    if name.is_empty() {
+        return Err(LineError::EmptyName);
+    }
+    if storage.accounts.contains_key(name) {
+        return Err(LineError::DuplicateName(name.to_string()));
+    }
+
+    // Поля, которых в старом формате ещё не было, остаются `None`
+    let fields = match format {
+        SnapshotFormat::V1 => LineFields::new(parts[1]),
+        SnapshotFormat::V2 => LineFields {
+            ops: Some(parts[2]),
+            ..LineFields::new(parts[1])
+        },
+        SnapshotFormat::V3 => LineFields {
+            currency: Some(parts[1]),
+            ops: Some(parts[3]),
+            ..LineFields::new(parts[2])
+        },
+        SnapshotFormat::V4 => LineFields {
+            currency: Some(parts[1]),
+            holdings: Some(parts[3]),
+            ops: Some(parts[4]),
+            ..LineFields::new(parts[2])
+        },
+        SnapshotFormat::V5 => LineFields {
+            currency: Some(parts[1]),
+            limit: Some(parts[3]),
+            holdings: Some(parts[4]),
+            ops: Some(parts[5]),
+            ..LineFields::new(parts[2])
+        },
+        SnapshotFormat::V6 | SnapshotFormat::V7 => LineFields {
+            currency: Some(parts[1]),
+            limit: Some(parts[3]),
+            interest: Some(parts[4]),
+            holdings: Some(parts[5]),
+            ops: Some(parts[6]),
+            ..LineFields::new(parts[2])
+        },
+        SnapshotFormat::V8 | SnapshotFormat::V9 | SnapshotFormat::V10 => LineFields {
+            currency: Some(parts[1]),
+            limit: Some(parts[3]),
+            interest: Some(parts[4]),
+            holdings: Some(parts[5]),
+            holds: Some(parts[6]),
+            ops: Some(parts[7]),
+            ..LineFields::new(parts[2])
+        },
+        SnapshotFormat::V11 => LineFields {
+            currency: Some(parts[1]),
+            limit: Some(parts[3]),
+            interest: Some(parts[4]),
+            holdings: Some(parts[5]),
+            holds: Some(parts[6]),
+            archived: Some(parts[7]),
+            ops: Some(parts[8]),
+            ..LineFields::new(parts[2])
+        },
+    };
+    let LineFields {
+        currency,
+        value,
+        limit,
+        interest,
+        holdings,
+        holds,
+        archived,
+        ops,
+    } = fields;
+
+    let currency = match currency.map(str::trim) {
+        Some(code) => code
+            .parse()
+            .map_err(|_| LineError::BadCurrency(code.to_string()))?,
+        None => Currency::default(),
+    };
+
+    let value = value.trim();
+    let result: SignedAmount = value
+        .parse()
+        .map_err(|_| LineError::BadBalance(value.to_string()))?;
+
+    let mut balance = Balance::with_currency(currency);
+    if let Some(limit) = limit.map(str::trim) {
+        balance.credit_limit = limit
+            .parse()
+            .map_err(|_| LineError::BadLimit(limit.to_string()))?;
+    }
+    if let Some(interest) = interest.map(str::trim).filter(|s| !s.is_empty()) {
+        balance.interest = Some(
+            interest
+                .parse()
+                .map_err(|_| LineError::BadInterest(interest.to_string()))?,
+        );
+    }
+    // долг глубже кредитного лимита — признак испорченного файла
+    if result.debt() > balance.credit_limit {
+        return Err(LineError::BadBalance(value.to_string()));
+    }
+    if let Some(holdings) = holdings {
+        balance.holdings = Balance::parse_holdings(holdings)
+            .map_err(|_| LineError::BadHoldings(holdings.trim().to_string()))?;
+    }
+    if let Some(holds) = holds {
+        balance.holds = Balance::parse_holds(holds)
+            .map_err(|_| LineError::BadHolds(holds.trim().to_string()))?;
+    }
+    if let Some(archived) = archived {
+        balance.archived = archived.parse().map_err(LineError::BadArchived)?;
+    }
+    match ops {
+        None => {
+            // в старом формате истории нет — восстанавливаем её одним пополнением
+            let result = result
+                .to_amount()
+                .expect("без кредита баланс неотрицателен");
+            balance.process(&[&OpKind::Deposit(result)]);
+        }
+        Some(ops) => {
+            balance.result = result;
+            balance.last_ops = ops
+                .split_whitespace()
+                .map(|op| op.parse().map_err(|_| LineError::BadOp(op.to_string())))
+                .collect::<Result<_, _>>()?;
+        }
+    }
+
+    Ok((name.to_string(), balance))
+}
+
+/// Разбирает строку ключа идемпотентности
+fn parse_key(line: &str, storage: &Storage) -> Result<KeyRecord, LineError> {
+    let record: KeyRecord = line.parse().map_err(LineError::BadKey)?;
+    if storage.keys.contains_key(&record.key) {
+        return Err(LineError::BadKey(format!(
+            "ключ '{}' встречается повторно",
+            record.key
+        )));
+    }
+    Ok(record)
+}
+
+/// Разбирает строку платёжного поручения
+fn parse_order(line: &str, storage: &Storage) -> Result<StandingOrder, LineError> {
+    let order: StandingOrder = line.parse().map_err(LineError::BadOrder)?;
+    if storage.orders.contains_key(&order.id) {
+        return Err(LineError::BadOrder(format!(
+            "поручение №{} встречается повторно",
+            order.id
+        )));
+    }
+    Ok(order)
+}
+
+impl BalanceManager for Storage {
+    fn deposit(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.atomic(|storage| {
+            storage.ensure_account(name, amount.currency)?;
+            storage.credit(name, amount)
+        })
+    }
+
+    fn withdraw(&mut self, name: &Name, amount: Money) -> Result<(), BankError> {
+        self.atomic(|storage| storage.debit(name, amount))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use crate::operations::Operation;
+    use crate::schedule::{RetryPolicy, Schedule};
+    use crate::testing::rub;
+    use crate::transaction::{Transaction, Transfer};
     use std::fs::{self, File};
     use std::io::{BufReader, BufWriter, Cursor, Write};
 
@@ -170,18 +1228,113 @@ mod tests {
         let mut storage = Storage::new();
         assert_eq!(storage.add_user("Alice".to_string()), Some(0)); // новый пользователь
         assert_eq!(storage.add_user("Alice".to_string()), None); // уже существует
+
+        // имя пишется в строки снимка и журнала как есть
+        for bad in ["", "Evil,Name", "a;b", "line\nbreak", " Bob", "Bob\t"] {
+            assert_eq!(storage.add_user(bad.to_string()), None, "{:?}", bad);
+            assert!(matches!(check_name(bad), Err(BankError::BadName(_))));
+        }
+        assert!(check_name("Анна Петрова").is_ok());
+
+        storage.account_policy = AccountPolicy::AutoCreate;
+        assert!(matches!(
+            storage.deposit(&"Evil,Name".to_string(), rub(100)),
+            Err(BankError::BadName(_))
+        ));
+        assert_eq!(storage.accounts.len(), 1);
+    }
+
+    #[test]
+    fn create_account_with_initial_balance() {
+        let file = "create_account.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        let alice = "Alice".to_string();
+        storage
+            .create_account(&alice, Currency::RUB, Some(rub(100)))
+            .unwrap();
+        assert_eq!(storage.accounts[&alice].result, 100);
+        assert!(matches!(
+            storage.create_account(&alice, Currency::RUB, None),
+            Err(BankError::AccountExists(_))
+        ));
+        let journaled = fs::read_to_string(Journal::path_for(file)).unwrap();
+        assert_eq!(journaled.lines().count(), 1);
+
+        // не удалось зачислить — счёт не открыт, и повтор не упирается в "уже существует"
+        let bob = "Bob".to_string();
+        assert!(matches!(
+            storage.create_account(&bob, Currency::USD, Some(rub(5))),
+            Err(BankError::CurrencyMismatch { .. })
+        ));
+        assert!(!storage.accounts.contains_key(&bob));
+        assert_eq!(
+            fs::read_to_string(Journal::path_for(file)).unwrap(),
+            journaled
+        );
+        storage
+            .create_account(&bob, Currency::RUB, Some(rub(5)))
+            .unwrap();
+
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts[&bob].result, 5);
+        fs::remove_file(Journal::path_for(file)).unwrap();
+    }
+
+    #[test]
+    fn atomic_journals_only_touched_accounts() {
+        let file = "atomic_touched.csv";
+        let (mut storage, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        for name in ["Alice", "Bob", "Carl"] {
+            storage.add_user(name.to_string());
+        }
+        let journal = Journal::path_for(file);
+        let before = fs::read_to_string(&journal).unwrap();
+
+        // неудачный вложенный шаг откатывается сам, остальное — одной записью
+        storage
+            .atomic(|storage| {
+                storage.deposit(&"Alice".to_string(), rub(100))?;
+                assert!(storage.withdraw(&"Bob".to_string(), rub(1)).is_err());
+                storage.deposit(&"Dave".to_string(), rub(5))
+            })
+            .unwrap();
+        let written = fs::read_to_string(&journal).unwrap();
+        let entry = written[before.len()..].trim_end();
+        assert!(
+            entry.starts_with("S,Alice,") && entry.contains(";S,Dave,") && !entry.contains("Bob"),
+            "{}",
+            entry
+        );
+
+        // ошибка снаружи откатывает и новый счёт, и изменения существующих
+        let result = storage.atomic(|storage| {
+            storage.deposit(&"Eve".to_string(), rub(5))?;
+            storage.deposit(&"Carl".to_string(), rub(7))?;
+            storage.withdraw(&"Carl".to_string(), rub(100))
+        });
+        assert!(matches!(result, Err(BankError::NotEnoughMoney { .. })));
+        assert!(!storage.accounts.contains_key("Eve"));
+        assert_eq!(storage.accounts["Carl"].result, 0);
+        assert_eq!(fs::read_to_string(&journal).unwrap(), written);
+
+        let (restored, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(restored.accounts, storage.accounts);
+        fs::remove_file(&journal).unwrap();
     }
 
     #[test]
     fn remove_user() {
         let mut storage = Storage::new();
         storage.add_user("Bob".to_string());
-        storage.deposit(&"Bob".to_string(), 100).unwrap();
+        storage.deposit(&"Bob".to_string(), rub(100)).unwrap();
 
         let removed = storage.remove_user(&"Bob".to_string());
-        assert!(removed.is_some());
         assert_eq!(removed.unwrap().result, 100); // удаляем и получаем баланс
-        assert_eq!(storage.remove_user(&"Bob".to_string()), None); // второй раз — не найден
+        assert!(matches!(
+            storage.remove_user(&"Bob".to_string()),
+            Err(BankError::UserNotFound(_)) // второй раз — не найден
+        ));
     }
 
     #[test]
@@ -189,15 +1342,34 @@ mod tests {
         let mut storage = Storage::new();
 
         // Депозит несуществующему пользователю
-        assert!(storage.deposit(&"Dana".to_string(), 100).is_err());
+        assert!(storage.deposit(&"Dana".to_string(), rub(100)).is_err());
 
         // Снятие у несуществующего пользователя
-        assert!(storage.withdraw(&"Dana".to_string(), 50).is_err());
+        assert!(storage.withdraw(&"Dana".to_string(), rub(50)).is_err());
 
         // Баланс у несуществующего пользователя
         assert_eq!(storage.get_balance(&"Dana".to_string()), None);
     }
 
+    #[test]
+    fn auto_create_policy_opens_account_on_deposit() {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        let dana = "Dana".to_string();
+
+        // снятие счёт не открывает даже при AutoCreate
+        assert!(storage.withdraw(&dana, rub(50)).is_err());
+        assert!(!storage.accounts.contains_key("Dana"));
+
+        storage
+            .deposit(&dana, Money::new(100, Currency::USD))
+            .unwrap();
+        assert_eq!(
+            storage.accounts["Dana"].money(),
+            Money::new(100, Currency::USD)
+        );
+    }
+
     #[test]
     fn load_data_existing_file() {
         let file_path = "load.csv";
@@ -207,7 +1379,8 @@ mod tests {
         writeln!(file, "Alice,200").unwrap();
         writeln!(file, "Bob,50").unwrap();
 
-        let storage = Storage::load_data(file_path);
+        let (storage, rejected) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert!(rejected.is_empty());
 
         assert_eq!(
             storage.get_balance(&"John".to_string()).unwrap().result,
@@ -221,6 +1394,75 @@ mod tests {
         assert_eq!(storage.get_balance(&"Vasya".to_string()), None);
 
         fs::remove_file(file_path).unwrap();
+        fs::remove_file(Journal::path_for(file_path)).unwrap();
+    }
+
+    #[test]
+    fn load_data_missing_file_is_empty() {
+        let file_path = "missing.csv";
+
+        let (storage, rejected) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert!(storage.accounts.is_empty());
+        assert!(rejected.is_empty());
+
+        fs::remove_file(Journal::path_for(file_path)).unwrap();
+    }
+
+    #[test]
+    fn from_reader_strict_rejects_bad_lines() {
+        let data = b"John,100\nAlice,abc\n\nBob\nJohn,5\n,7\nVasya,1,2\n";
+
+        let result = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict);
+        let Err(LoadError::Rejected(rejected)) = result else {
+            panic!("Ожидалась ошибка Rejected");
+        };
+
+        assert_eq!(
+            rejected,
+            vec![
+                RejectedLine {
+                    line: 2,
+                    reason: LineError::BadBalance("abc".to_string()),
+                },
+                RejectedLine {
+                    line: 4,
+                    reason: LineError::FieldCount {
+                        expected: 2,
+                        found: 1,
+                    },
+                },
+                RejectedLine {
+                    line: 5,
+                    reason: LineError::DuplicateName("John".to_string()),
+                },
+                RejectedLine {
+                    line: 6,
+                    reason: LineError::EmptyName,
+                },
+                RejectedLine {
+                    line: 7,
+                    reason: LineError::FieldCount {
+                        expected: 2,
+                        found: 3,
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn from_reader_lenient_skips_bad_lines() {
+        let data = b"John,100\nAlice,-5\nBob,50\n";
+
+        let (storage, rejected) =
+            Storage::from_reader(Cursor::new(&data[..]), LoadMode::Lenient).unwrap();
+
+        assert_eq!(storage.accounts.len(), 2);
+        assert_eq!(storage.get_balance(&"Bob".to_string()).unwrap().result, 50);
+        // плохой баланс не превращается в ноль — строки просто нет
+        assert_eq!(storage.get_balance(&"Alice".to_string()), None);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].line, 2);
     }
 
     #[test]
@@ -228,22 +1470,326 @@ mod tests {
         let file_path = "save.csv";
 
         let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
         storage.add_user("John".to_string());
         storage.add_user("Alice".to_string());
-        storage.deposit(&"John".to_string(), 150).unwrap();
-        storage.deposit(&"Alice".to_string(), 300).unwrap();
+        storage.deposit(&"John".to_string(), rub(150)).unwrap();
+        storage.deposit(&"Alice".to_string(), rub(300)).unwrap();
 
         storage.save(file_path);
 
         let contents = fs::read_to_string(file_path).unwrap();
-        let mut lines: Vec<&str> = contents.lines().collect();
-        lines.sort();
+        let lines: Vec<&str> = contents.lines().collect();
 
-        assert_eq!(lines, vec!["Alice,300", "John,150"]);
+        assert_eq!(
+            lines,
+            vec![
+                SNAPSHOT_HEADER,
+                "Alice,RUB,300,0,,,,,D:300#2^2026-01-31T00:00:00Z",
+                "John,RUB,150,0,,,,,D:150#1^2026-01-31T00:00:00Z"
+            ]
+        );
 
         fs::remove_file(file_path).unwrap();
     }
 
+    #[test]
+    fn save_and_load_roundtrip_history() {
+        let file_path = "history.csv";
+
+        let mut storage = Storage::new();
+        let clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        storage.clock = clock.clone();
+        storage.add_user("Alice".to_string());
+        storage.add_user("Empty".to_string());
+        storage.deposit(&"Alice".to_string(), rub(100)).unwrap();
+        clock.advance_secs(90);
+        storage.withdraw(&"Alice".to_string(), rub(30)).unwrap();
+        storage.deposit(&"Alice".to_string(), rub(5)).unwrap();
+        storage.save(file_path);
+
+        let (loaded, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts, storage.accounts);
+        assert_eq!(
+            loaded.get_balance(&"Alice".to_string()).unwrap().last_ops,
+            vec![
+                Operation {
+                    kind: OpKind::Deposit(Amount::new(100)),
+                    meta: OpMeta {
+                        tx: Some(1),
+                        at: Some("2026-01-31T00:00:00Z".parse().unwrap()),
+                        ..OpMeta::default()
+                    }
+                },
+                Operation {
+                    kind: OpKind::Withdraw(Amount::new(30)),
+                    meta: OpMeta {
+                        tx: Some(2),
+                        at: Some("2026-01-31T00:01:30Z".parse().unwrap()),
+                        ..OpMeta::default()
+                    }
+                },
+                Operation {
+                    kind: OpKind::Deposit(Amount::new(5)),
+                    meta: OpMeta {
+                        tx: Some(3),
+                        at: Some("2026-01-31T00:01:30Z".parse().unwrap()),
+                        ..OpMeta::default()
+                    }
+                }
+            ]
+        );
+
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(Journal::path_for(file_path)).unwrap();
+    }
+
+    #[test]
+    fn currency_is_stored_per_account() {
+        let file_path = "currency.csv";
+
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage.add_user_with_currency("Bob".to_string(), Currency::USD);
+        storage
+            .deposit(&"Bob".to_string(), Money::new(250, Currency::USD))
+            .unwrap();
+        storage.save(file_path);
+
+        let contents = fs::read_to_string(file_path).unwrap();
+        assert!(contents.contains("Bob,USD,250,0,,,,,D:250"));
+
+        let (loaded, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        let bob = loaded.get_balance(&"Bob".to_string()).unwrap();
+        assert_eq!(bob.money(), Money::new(250, Currency::USD));
+        assert_eq!(
+            loaded.get_balance(&"Alice".to_string()).unwrap().currency,
+            Currency::RUB
+        );
+
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(Journal::path_for(file_path)).unwrap();
+    }
+
+    #[test]
+    fn mixed_currency_operations_are_rejected() {
+        let mut storage = Storage::new();
+        storage.add_user_with_currency("Bob".to_string(), Currency::USD);
+
+        let result = storage.deposit(&"Bob".to_string(), rub(100));
+        assert!(matches!(
+            result,
+            Err(BankError::CurrencyMismatch {
+                ref account,
+                expected: Currency::USD,
+                found: Currency::RUB,
+            }) if account == "Bob"
+        ));
+        let result = storage.withdraw(&"Bob".to_string(), rub(0));
+        assert!(matches!(result, Err(BankError::CurrencyMismatch { .. })));
+        assert!(storage.accounts["Bob"].last_ops.is_empty());
+    }
+
+    #[test]
+    fn from_reader_reads_older_formats() {
+        let data = b"#bank-system v2\nAlice,10,D:10\n";
+        let (storage, _) = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict).unwrap();
+        assert_eq!(storage.accounts["Alice"].money(), rub(10));
+
+        let data = b"#bank-system v3\nAlice,XX,10,D:10\n";
+        let result = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict);
+        let Err(LoadError::Rejected(rejected)) = result else {
+            panic!("Ожидалась ошибка Rejected");
+        };
+        assert_eq!(rejected[0].reason, LineError::BadCurrency("XX".to_string()));
+
+        let data = b"#bank-system v3\nAlice,USD,10,D:10\n";
+        let (storage, _) = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict).unwrap();
+        assert!(storage.accounts["Alice"].holdings.is_empty());
+
+        // до v7 поручений не было, и строка "#orders" — просто плохая строка счёта
+        let data = b"#bank-system v6\nAlice,RUB,10,0,,,D:10\n#orders\n";
+        let (storage, rejected) =
+            Storage::from_reader(Cursor::new(&data[..]), LoadMode::Lenient).unwrap();
+        assert_eq!(storage.accounts["Alice"].result, 10);
+        assert_eq!(rejected.len(), 1);
+
+        let data = b"#bank-system v7\nAlice,RUB,10,0,,,D:10\n#orders\n1,Alice\n";
+        let (storage, rejected) =
+            Storage::from_reader(Cursor::new(&data[..]), LoadMode::Lenient).unwrap();
+        assert!(storage.orders.is_empty());
+        assert!(matches!(rejected[0].reason, LineError::BadOrder(_)));
+
+        let data = b"#bank-system v9\nAlice,RUB,10,0,,,,D:10#3\n#keys\nk,3,deposit\n";
+        let (storage, _) = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict).unwrap();
+        let op = &storage.accounts["Alice"].last_ops[0];
+        assert_eq!((op.meta.tx, op.meta.at), (Some(3), None));
+        assert_eq!(storage.keys["k"].tx, 3);
+    }
+
+    #[test]
+    fn exchange_moves_money_between_currencies() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, rub(10000)).unwrap();
+        storage
+            .rates
+            .set(Currency::USD, Currency::RUB, "92.5".parse().unwrap());
+
+        // 100.00 RUB -> 1.08 USD по обратному курсу, остаток копеек отбрасывается
+        let bought = storage.exchange(&alice, rub(10000), Currency::USD).unwrap();
+        assert_eq!(bought, Money::new(108, Currency::USD));
+        let balance = &storage.accounts["Alice"];
+        assert_eq!(balance.result, 0);
+        assert_eq!(balance.amount_in(Currency::USD), Some(Amount::new(108)));
+        assert_eq!(
+            balance.last_ops.last().unwrap().kind.to_string(),
+            "X:10000:RUB>108:USD@0.010811"
+        );
+
+        let result = storage.exchange(&alice, Money::new(200, Currency::USD), Currency::RUB);
+        assert!(matches!(result, Err(BankError::NotEnoughMoney { .. })));
+        let result = storage.exchange(&alice, Money::new(1, Currency::USD), Currency::EUR);
+        assert!(matches!(result, Err(BankError::NoRate { .. })));
+        let result = storage.exchange(&alice, Money::new(1, Currency::EUR), Currency::RUB);
+        assert!(matches!(result, Err(BankError::NoRate { .. })));
+        storage.deposit(&alice, rub(1)).unwrap();
+        let result = storage.exchange(&alice, rub(1), Currency::USD);
+        assert!(matches!(result, Err(BankError::ExchangeTooSmall(_))));
+    }
+
+    #[test]
+    fn save_and_load_roundtrip_holdings() {
+        let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        let alice = "Alice".to_string();
+        storage.add_user(alice.clone());
+        storage.deposit(&alice, rub(92500)).unwrap();
+        storage
+            .rates
+            .set(Currency::RUB, Currency::USD, "0.01".parse().unwrap());
+        storage.exchange(&alice, rub(50000), Currency::USD).unwrap();
+
+        let file = "holdings_roundtrip.csv";
+        storage.save(file);
+        let contents = fs::read_to_string(file).unwrap();
+        assert!(contents.contains(
+            "Alice,RUB,42500,0,,USD:500,,,D:92500#1^2026-01-31T00:00:00Z \
+             X:50000:RUB>500:USD@0.01#2^2026-01-31T00:00:00Z"
+        ));
+
+        let (loaded, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts["Alice"], storage.accounts["Alice"]);
+        fs::remove_file(file).unwrap();
+        let _ = fs::remove_file(Journal::path_for(file));
+
+        let data = b"#bank-system v4\nAlice,RUB,1,USD,D:1\n";
+        let result = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict);
+        let Err(LoadError::Rejected(rejected)) = result else {
+            panic!("Ожидалась ошибка Rejected");
+        };
+        assert_eq!(
+            rejected[0].reason,
+            LineError::BadHoldings("USD".to_string())
+        );
+    }
+
+    #[test]
+    fn credit_limit_allows_overdraft() {
+        let mut storage = Storage::new();
+        let alice = "Alice".to_string();
+        let bob = "Bob".to_string();
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, rub(100)).unwrap();
+        storage.set_credit_limit(&alice, Amount::new(500)).unwrap();
+
+        storage.withdraw(&alice, rub(300)).unwrap();
+        Transfer {
+            from: alice.clone(),
+            to: bob.clone(),
+            amount: rub(200),
+        }
+        .apply(&mut storage)
+        .unwrap();
+        assert_eq!(storage.accounts["Alice"].result, -400);
+        assert_eq!(storage.accounts["Bob"].result, 200);
+
+        let result = storage.withdraw(&alice, rub(150));
+        match result {
+            Err(BankError::NotEnoughMoney {
+                available, credit, ..
+            }) => {
+                assert_eq!(available, rub(100));
+                assert_eq!(credit, rub(100));
+            }
+            other => panic!("Ожидалась NotEnoughMoney, получено {:?}", other),
+        }
+
+        // Обмен тоже может занять деньги в пределах лимита
+        storage
+            .rates
+            .set(Currency::RUB, Currency::USD, "0.01".parse().unwrap());
+        storage.exchange(&alice, rub(100), Currency::USD).unwrap();
+        assert_eq!(storage.accounts["Alice"].result, -500);
+        let result = storage.exchange(&alice, rub(100), Currency::USD);
+        assert!(matches!(result, Err(BankError::NotEnoughMoney { .. })));
+
+        // Лимит нельзя опустить ниже долга
+        let result = storage.set_credit_limit(&alice, Amount::new(499));
+        assert!(matches!(result, Err(BankError::LimitBelowDebt { .. })));
+        assert_eq!(storage.accounts["Alice"].credit_limit, Amount::new(500));
+        let result = storage.set_credit_limit(&"Nobody".to_string(), Amount::ZERO);
+        assert!(matches!(result, Err(BankError::UserNotFound(_))));
+
+        let file = "credit_roundtrip.csv";
+        storage.save(file);
+        let contents = fs::read_to_string(file).unwrap();
+        assert!(contents.contains("Alice,RUB,-500,500,,USD:1,"));
+        let (loaded, _) = Storage::load_data(file, LoadMode::Strict).unwrap();
+        assert_eq!(loaded.accounts, storage.accounts);
+        fs::remove_file(file).unwrap();
+        let _ = fs::remove_file(Journal::path_for(file));
+
+        // Долг глубже лимита — испорченная строка
+        let data = b"#bank-system v5\nAlice,RUB,-10,5,,W:10\n";
+        let result = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Strict);
+        let Err(LoadError::Rejected(rejected)) = result else {
+            panic!("Ожидалась ошибка Rejected");
+        };
+        assert_eq!(rejected[0].reason, LineError::BadBalance("-10".to_string()));
+    }
+
+    #[test]
+    fn from_reader_v2_rejects_bad_ops_and_unknown_version() {
+        let data = b"#bank-system v2\nAlice,10,D:10\nBob,5,D:5 X:1\nCarl,1\n";
+
+        let (storage, rejected) =
+            Storage::from_reader(Cursor::new(&data[..]), LoadMode::Lenient).unwrap();
+        assert_eq!(storage.accounts.len(), 1);
+        assert_eq!(
+            rejected,
+            vec![
+                RejectedLine {
+                    line: 3,
+                    reason: LineError::BadOp("X:1".to_string()),
+                },
+                RejectedLine {
+                    line: 4,
+                    reason: LineError::FieldCount {
+                        expected: 3,
+                        found: 2,
+                    },
+                },
+            ]
+        );
+
+        let data = b"#bank-system v12\nAlice,10,D:10\n";
+        let result = Storage::from_reader(Cursor::new(&data[..]), LoadMode::Lenient);
+        assert!(matches!(result, Err(LoadError::UnsupportedFormat(_))));
+    }
+
     #[test]
     fn load_data_existing_cursor() {
         // Создаём данные в памяти, как будто это CSV-файл
@@ -260,7 +1806,7 @@ mod tests {
                 let name = parts[0].to_string();
                 let balance: u64 = parts[1].parse().unwrap_or(0);
                 storage.add_user(name.clone());
-                storage.deposit(&name, balance).unwrap();
+                storage.deposit(&name, rub(balance)).unwrap();
             }
         }
 
@@ -279,10 +1825,11 @@ mod tests {
     #[test]
     fn save_writes_to_cursor_correctly() {
         let mut storage = Storage::new();
+        storage.clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
         storage.add_user("John".to_string());
         storage.add_user("Alice".to_string());
-        storage.deposit(&"John".to_string(), 150).unwrap();
-        storage.deposit(&"Alice".to_string(), 300).unwrap();
+        storage.deposit(&"John".to_string(), rub(150)).unwrap();
+        storage.deposit(&"Alice".to_string(), rub(300)).unwrap();
 
         let buffer = Vec::new();
         let mut cursor = Cursor::new(buffer);
@@ -308,9 +1855,9 @@ mod tests {
         storage.add_user("Bob".to_string());
 
         let operations = vec![
-            (true, "Alice".to_string(), 100), // deposit
-            (true, "Bob".to_string(), 200),   // deposit
-            (false, "Alice".to_string(), 50), // withdraw
+            (true, "Alice".to_string(), rub(100)), // deposit
+            (true, "Bob".to_string(), rub(200)),   // deposit
+            (false, "Alice".to_string(), rub(50)), // withdraw
         ];
 
         let result = storage.process_if_deposit(&operations);
@@ -328,15 +1875,15 @@ mod tests {
         storage.add_user("Alice".to_string());
 
         let operations = vec![
-            (true, "Alice".to_string(), 100),
-            (true, "Unknown".to_string(), 50), // пользователь не существует
+            (true, "Alice".to_string(), rub(100)),
+            (true, "Unknown".to_string(), rub(50)), // пользователь не существует
         ];
 
         let result = storage.process_if_deposit(&operations);
         assert!(result.is_err());
 
         match result {
-            Err(BalanceManagerError::UserNotFound(name)) => {
+            Err(BankError::UserNotFound(name)) => {
                 assert_eq!(name, "Unknown");
             }
             _ => panic!("Ожидалась ошибка UserNotFound"),
@@ -347,24 +1894,188 @@ mod tests {
     fn process_if_deposit_not_enough_money() {
         let mut storage = Storage::new();
         storage.add_user("Alice".to_string());
-        storage.deposit(&"Alice".to_string(), 50).unwrap();
+        storage.deposit(&"Alice".to_string(), rub(50)).unwrap();
 
         let operations = vec![
-            (false, "Alice".to_string(), 100), // снять больше чем есть
+            (false, "Alice".to_string(), rub(100)), // снять больше чем есть
         ];
 
         let result = storage.process_if_deposit(&operations);
         assert!(result.is_err());
 
         match result {
-            Err(BalanceManagerError::NotEnoughMoney {
+            Err(BankError::NotEnoughMoney {
+                account,
                 required,
                 available,
+                credit,
             }) => {
-                assert_eq!(required, 100);
-                assert_eq!(available, 50);
+                assert_eq!(account, "Alice");
+                assert_eq!(required, rub(100));
+                assert_eq!(available, rub(50));
+                assert_eq!(credit, rub(0));
             }
             _ => panic!("Ожидалась ошибка NotEnoughMoney"),
         }
     }
+
+    #[test]
+    fn deposit_wider_than_u32_is_not_truncated() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        let big = 5_000_000_000;
+
+        storage.deposit(&"Alice".to_string(), rub(big)).unwrap();
+
+        let alice = storage.get_balance(&"Alice".to_string()).unwrap();
+        assert_eq!(alice.result, Amount::new(big));
+        assert_eq!(alice.last_ops[0].kind, OpKind::Deposit(Amount::new(big)));
+    }
+
+    #[test]
+    fn deposit_overflow_is_reported() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage
+            .deposit(&"Alice".to_string(), rub(u64::MAX))
+            .unwrap();
+
+        let result = storage.deposit(&"Alice".to_string(), rub(1));
+        assert!(matches!(
+            result,
+            Err(BankError::Overflow { balance, amount, .. })
+                if balance == rub(u64::MAX) && amount == rub(1)
+        ));
+        assert_eq!(
+            storage
+                .get_balance(&"Alice".to_string())
+                .unwrap()
+                .last_ops
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn load_data_replays_journal() {
+        let file_path = "journal_replay.csv";
+        fs::write(file_path, "Alice,100\nBob,0\n").unwrap();
+
+        {
+            let (mut storage, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+            storage.withdraw(&"Alice".to_string(), rub(30)).unwrap();
+            storage.remove_user(&"Bob".to_string()).unwrap();
+            storage.add_user("Carl".to_string());
+            storage.deposit(&"Carl".to_string(), rub(5)).unwrap();
+            // save не вызываем — как будто процесс упал
+        }
+
+        let (storage, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        let alice = storage.get_balance(&"Alice".to_string()).unwrap();
+        assert_eq!(alice.result, 70);
+        assert_eq!(
+            alice.last_ops.last().map(|op| &op.kind),
+            Some(&OpKind::Withdraw(Amount::new(30)))
+        );
+        assert_eq!(storage.get_balance(&"Bob".to_string()), None);
+        assert_eq!(storage.get_balance(&"Carl".to_string()).unwrap().result, 5);
+
+        // После сохранения журнал пуст, а снимок содержит всё
+        storage.save(file_path);
+        let journal_path = Journal::path_for(file_path);
+        assert_eq!(fs::read_to_string(&journal_path).unwrap(), "");
+        let (mut storage, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert_eq!(
+            storage.get_balance(&"Alice".to_string()).unwrap().result,
+            70
+        );
+        // после очистки журнал снова пишется с начала файла
+        storage.deposit(&"Alice".to_string(), rub(1)).unwrap();
+        assert!(
+            fs::read_to_string(&journal_path)
+                .unwrap()
+                .starts_with("S,Alice,RUB,71,")
+        );
+
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(journal_path).unwrap();
+    }
+
+    #[test]
+    fn close_account_pays_out_and_persists() {
+        let file_path = "close_account.csv";
+        let (mut storage, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        let (alice, bob) = ("Alice".to_string(), "Bob".to_string());
+        storage.add_user(alice.clone());
+        storage.add_user(bob.clone());
+        storage.deposit(&alice, rub(100)).unwrap();
+        storage.set_credit_limit(&alice, Amount::new(50)).unwrap();
+        let start = "2026-01-01".parse().unwrap();
+        let transfer = Transfer {
+            from: alice.clone(),
+            to: bob.clone(),
+            amount: rub(10),
+        };
+        let order = storage
+            .add_order(transfer, Schedule::Once, start, RetryPolicy::default())
+            .unwrap();
+
+        assert!(matches!(
+            storage.close_account(&alice, None),
+            Err(BankError::AccountNotEmpty { .. })
+        ));
+        assert!(matches!(
+            storage.close_account(&alice, Some(&"Carl".to_string())),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert_eq!(storage.accounts[&alice].result, 100);
+
+        assert_eq!(storage.close_account(&alice, Some(&bob)).unwrap(), rub(100));
+        assert_eq!(storage.accounts[&bob].result, 100);
+        let closed = &storage.accounts[&alice];
+        assert!(closed.is_closed());
+        assert_eq!(closed.credit_limit, Amount::ZERO);
+        assert_eq!(storage.orders[&order].status, OrderStatus::Cancelled);
+
+        assert!(matches!(
+            storage.deposit(&alice, rub(1)),
+            Err(BankError::AccountClosed(_))
+        ));
+        assert!(matches!(
+            storage.close_account(&bob, Some(&alice)),
+            Err(BankError::AccountClosed(_))
+        ));
+        assert!(matches!(
+            storage.close_account(&alice, None),
+            Err(BankError::AccountClosed(_))
+        ));
+
+        // закрытие переживает и журнал, и снимок
+        let (restored, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert!(restored.accounts[&alice].is_closed());
+        restored.save(file_path);
+        let (mut restored, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+        assert!(restored.accounts[&alice].is_closed());
+        assert!(restored.withdraw(&alice, rub(1)).is_err());
+
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(Journal::path_for(file_path)).unwrap();
+    }
+
+    #[test]
+    fn failed_operation_is_not_journaled() {
+        let file_path = "journal_failed.csv";
+        fs::write(file_path, "Alice,10\n").unwrap();
+
+        {
+            let (mut storage, _) = Storage::load_data(file_path, LoadMode::Strict).unwrap();
+            assert!(storage.withdraw(&"Alice".to_string(), rub(30)).is_err());
+        }
+
+        let journal_path = Journal::path_for(file_path);
+        assert_eq!(fs::read_to_string(&journal_path).unwrap(), "");
+
+        fs::remove_file(file_path).unwrap();
+        fs::remove_file(journal_path).unwrap();
+    }
 }
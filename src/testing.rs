@@ -0,0 +1,14 @@
+//! Общие заготовки для модульных тестов
+
+use crate::clock::Date;
+use crate::money::{Currency, Money};
+
+/// `amount` копеек
+pub(crate) fn rub(amount: u64) -> Money {
+    Money::new(amount, Currency::RUB)
+}
+
+/// Дата в виде `2026-01-31`
+pub(crate) fn date(s: &str) -> Date {
+    s.parse().unwrap()
+}
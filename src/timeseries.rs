@@ -0,0 +1,617 @@
+use crate::Name;
+use crate::analytics::{Flow, flow};
+use crate::clock::Date;
+use crate::errors::BankError;
+use crate::money::Currency;
+use crate::operations::Operation;
+use crate::storage::Storage;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Длина периода временного ряда
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Day,
+    /// Неделя с понедельника по воскресенье
+    Week,
+    /// Календарный месяц
+    Month,
+}
+
+impl Bucket {
+    /// Первый день периода, в который попадает `date`
+    pub fn start(self, date: Date) -> Date {
+        match self {
+            Bucket::Day => date,
+            Bucket::Week => date.add_days(-i64::from(date.weekday())),
+            Bucket::Month => date.in_month(1),
+        }
+    }
+
+    /// Первый день периода, следующего за периодом с началом `start`
+    pub fn next(self, start: Date) -> Date {
+        match self {
+            Bucket::Day => start.add_days(1),
+            Bucket::Week => start.add_days(7),
+            Bucket::Month => start.next_month(),
+        }
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bucket::Day => write!(f, "day"),
+            Bucket::Week => write!(f, "week"),
+            Bucket::Month => write!(f, "month"),
+        }
+    }
+}
+
+impl FromStr for Bucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Bucket::Day),
+            "week" => Ok(Bucket::Week),
+            "month" => Ok(Bucket::Month),
+            _ => Err(format!("неизвестный период '{}': day, week или month", s)),
+        }
+    }
+}
+
+/// Показатель, по которому считаются скользящее среднее и изменение к прошлому периоду
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Остаток на конец периода
+    Balance,
+    Inflow,
+    Outflow,
+    /// Зачисления минус списания
+    Net,
+    /// Число операций
+    Operations,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Balance => write!(f, "balance"),
+            Metric::Inflow => write!(f, "inflow"),
+            Metric::Outflow => write!(f, "outflow"),
+            Metric::Net => write!(f, "net"),
+            Metric::Operations => write!(f, "operations"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "balance" => Ok(Metric::Balance),
+            "inflow" => Ok(Metric::Inflow),
+            "outflow" => Ok(Metric::Outflow),
+            "net" => Ok(Metric::Net),
+            "operations" => Ok(Metric::Operations),
+            _ => Err(format!(
+                "неизвестный показатель '{}': balance, inflow, outflow, net или operations",
+                s
+            )),
+        }
+    }
+}
+
+/// Один период ряда. Суммы — в минимальных единицах; итоги по всему банку могут
+/// выйти за пределы [`SignedAmount`](crate::SignedAmount), поэтому здесь `i128`,
+/// как в [`TrialBalance`](crate::TrialBalance).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesPoint {
+    /// Первый день периода
+    pub start: Date,
+    pub operations: u64,
+    /// Зачисления: пополнения, входящие переводы и проценты
+    pub inflow: i128,
+    /// Списания: снятия и исходящие переводы
+    pub outflow: i128,
+    /// Остаток на конец периода; меняется и от обмена, который в обороты не входит
+    pub balance: i128,
+}
+
+impl SeriesPoint {
+    fn new(start: Date) -> Self {
+        SeriesPoint {
+            start,
+            operations: 0,
+            inflow: 0,
+            outflow: 0,
+            balance: 0,
+        }
+    }
+
+    pub fn net(&self) -> i128 {
+        self.inflow - self.outflow
+    }
+
+    pub fn value(&self, metric: Metric) -> i128 {
+        match metric {
+            Metric::Balance => self.balance,
+            Metric::Inflow => self.inflow,
+            Metric::Outflow => self.outflow,
+            Metric::Net => self.net(),
+            Metric::Operations => i128::from(self.operations),
+        }
+    }
+}
+
+/// Изменение показателя к предыдущему периоду
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeriodChange {
+    pub delta: i128,
+    /// Изменение в процентах от модуля прошлого значения; `None`, если оно нулевое
+    pub percent: Option<f64>,
+}
+
+/// Временной ряд остатка и оборотов счёта или группы счетов в одной валюте:
+/// по точке на каждый период подряд, включая периоды без операций
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub currency: Currency,
+    pub bucket: Bucket,
+    /// Остаток до начала первого периода
+    pub opening: i128,
+    pub points: Vec<SeriesPoint>,
+}
+
+impl Series {
+    /// Пустой ряд: периоды `bucket` целиком, от того, где лежит `from`,
+    /// до того, где лежит `to`
+    fn empty(
+        currency: Currency,
+        bucket: Bucket,
+        from: Date,
+        to: Date,
+    ) -> Result<Series, BankError> {
+        if from > to {
+            return Err(BankError::BadPeriod { from, to });
+        }
+        let mut points = Vec::new();
+        let mut start = bucket.start(from);
+        while start <= to {
+            points.push(SeriesPoint::new(start));
+            start = bucket.next(start);
+        }
+        Ok(Series {
+            currency,
+            bucket,
+            opening: 0,
+            points,
+        })
+    }
+
+    /// Ряд по полной истории счёта `history` в основной валюте `currency`.
+    /// Периоды берутся целиком: первый начинается в начале периода с `from`,
+    /// последний заканчивается в конце периода с `to`. Операции без времени,
+    /// как в выписке, считаются проведёнными раньше любого периода.
+    pub fn for_history(
+        currency: Currency,
+        history: &[Operation],
+        bucket: Bucket,
+        from: Date,
+        to: Date,
+    ) -> Result<Series, BankError> {
+        let mut series = Series::empty(currency, bucket, from, to)?;
+        let first = series.points[0].start;
+        let end = bucket.next(series.points[series.points.len() - 1].start);
+
+        let mut shifts = vec![0; series.points.len()];
+        for op in history {
+            let delta = op.kind.balance_delta(currency);
+            let day = op.meta.at.map(|at| at.date());
+            let Some(day) = day.filter(|day| *day >= first) else {
+                series.opening += delta;
+                continue;
+            };
+            // история упорядочена по записи, а не по времени: дальше может быть операция
+            // из периода, если часы хранилища переводили назад
+            if day >= end {
+                continue;
+            }
+            let index = series.points.partition_point(|point| point.start <= day) - 1;
+            let point = &mut series.points[index];
+            point.operations += 1;
+            match flow(&op.kind) {
+                Some(Flow::In(value)) => point.inflow += i128::from(value.value()),
+                Some(Flow::Out(value)) => point.outflow += i128::from(value.value()),
+                None => {}
+            }
+            shifts[index] += delta;
+        }
+
+        let mut running = series.opening;
+        for (point, shift) in series.points.iter_mut().zip(shifts) {
+            running += shift;
+            point.balance = running;
+        }
+        Ok(series)
+    }
+
+    /// Складывает с рядом `other` тех же периодов
+    fn add(&mut self, other: &Series) {
+        debug_assert_eq!(
+            self.points.len(),
+            other.points.len(),
+            "ряды разных периодов"
+        );
+        self.opening += other.opening;
+        for (point, other) in self.points.iter_mut().zip(&other.points) {
+            point.operations += other.operations;
+            point.inflow += other.inflow;
+            point.outflow += other.outflow;
+            point.balance += other.balance;
+        }
+    }
+
+    /// Значения показателя `metric` по периодам
+    pub fn values(&self, metric: Metric) -> Vec<i128> {
+        self.points
+            .iter()
+            .map(|point| point.value(metric))
+            .collect()
+    }
+
+    /// Скользящее среднее `metric` за последние `window` периодов, включая текущий,
+    /// с округлением вниз. Пока периодов набралось меньше `window`, — `None`;
+    /// при нулевом окне `None` везде.
+    pub fn moving_average(&self, metric: Metric, window: usize) -> Vec<Option<i128>> {
+        let values = self.values(metric);
+        (0..values.len())
+            .map(|end| {
+                if window == 0 || end + 1 < window {
+                    return None;
+                }
+                let sum: i128 = values[end + 1 - window..=end].iter().sum();
+                Some(sum.div_euclid(window as i128))
+            })
+            .collect()
+    }
+
+    /// Изменение `metric` к предыдущему периоду; у первого периода — `None`
+    pub fn change(&self, metric: Metric) -> Vec<Option<PeriodChange>> {
+        let values = self.values(metric);
+        let mut changes = vec![None; values.len().min(1)];
+        changes.extend(values.windows(2).map(|pair| {
+            let (previous, current) = (pair[0], pair[1]);
+            Some(PeriodChange {
+                delta: current - previous,
+                percent: (previous != 0)
+                    .then(|| (current - previous) as f64 / previous.unsigned_abs() as f64 * 100.0),
+            })
+        }));
+        changes
+    }
+
+    /// Ряд в CSV для дашбордов: "period,operations,inflow,outflow,net,balance", затем
+    /// скользящее среднее `metric` за `window` периодов, его изменение к прошлому
+    /// периоду и изменение в процентах. Суммы — в основных единицах валюты без кода,
+    /// неопределённые значения — пустые поля.
+    pub fn to_csv(&self, metric: Metric, window: usize) -> String {
+        let units = |value: i128| match metric {
+            Metric::Operations => value.to_string(),
+            _ => self.currency.major_units(value),
+        };
+        let mut csv = format!(
+            "period,operations,inflow,outflow,net,balance,{0}_avg{1},{0}_change,{0}_change_pct\n",
+            metric, window
+        );
+        let averages = self.moving_average(metric, window);
+        let changes = self.change(metric);
+        for ((point, average), change) in self.points.iter().zip(averages).zip(changes) {
+            let fields = [
+                point.start.to_string(),
+                point.operations.to_string(),
+                self.currency.major_units(point.inflow),
+                self.currency.major_units(point.outflow),
+                self.currency.major_units(point.net()),
+                self.currency.major_units(point.balance),
+                average.map(units).unwrap_or_default(),
+                change.map(|c| units(c.delta)).unwrap_or_default(),
+                change
+                    .and_then(|c| c.percent)
+                    .map(|percent| format!("{:.2}", percent))
+                    .unwrap_or_default(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl Storage {
+    /// Ряд счёта `name` по всей его истории, вместе с архивной, как [`Series::for_history`]
+    pub fn account_series(
+        &self,
+        name: &Name,
+        bucket: Bucket,
+        from: Date,
+        to: Date,
+    ) -> Result<Series, BankError> {
+        let history = self.history(name, 0, usize::MAX)?;
+        Series::for_history(self.accounts[name].currency, &history, bucket, from, to)
+    }
+
+    /// Ряды всего банка по валютам: сумма рядов счетов с этой основной валютой.
+    /// Перевод между клиентами, как и в [`Storage::bank_stats`], — и списание, и зачисление.
+    pub fn series(
+        &self,
+        bucket: Bucket,
+        from: Date,
+        to: Date,
+    ) -> Result<BTreeMap<Currency, Series>, BankError> {
+        let mut all: BTreeMap<Currency, Series> = BTreeMap::new();
+        for (name, history) in self.histories()? {
+            let currency = self.accounts[name].currency;
+            let series = Series::for_history(currency, &history, bucket, from, to)?;
+            match all.get_mut(&series.currency) {
+                Some(total) => total.add(&series),
+                None => {
+                    all.insert(series.currency, series);
+                }
+            }
+        }
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::Balance;
+    use crate::testing::date;
+    use std::collections::HashMap;
+
+    fn ops(ops: &[&str]) -> Vec<Operation> {
+        ops.iter().map(|op| op.parse().unwrap()).collect()
+    }
+
+    /// Пополнение без времени, январские операции по неделям и одна в феврале
+    fn history() -> Vec<Operation> {
+        ops(&[
+            "D:1000",
+            "D:500^2026-01-05T09:00:00Z",
+            "W:200^2026-01-07T10:00:00Z",
+            "X:100:RUB>1:USD@0.01^2026-01-08T10:00:00Z",
+            "T+:300^2026-01-20T12:00:00Z",
+            "D:50^2026-02-02T08:00:00Z",
+        ])
+    }
+
+    #[test]
+    fn buckets() {
+        let thursday = date("2026-01-01");
+        assert_eq!(Bucket::Day.start(thursday), thursday);
+        assert_eq!(Bucket::Week.start(thursday), date("2025-12-29"));
+        assert_eq!(Bucket::Week.start(date("2026-01-04")), date("2025-12-29"));
+        assert_eq!(Bucket::Week.start(date("2026-01-05")), date("2026-01-05"));
+        assert_eq!(Bucket::Month.start(date("2026-02-28")), date("2026-02-01"));
+        assert_eq!(Bucket::Month.next(date("2026-12-01")), date("2027-01-01"));
+        assert_eq!(Bucket::Week.next(date("2025-12-29")), date("2026-01-05"));
+        for bucket in [Bucket::Day, Bucket::Week, Bucket::Month] {
+            assert_eq!(bucket.to_string().parse(), Ok(bucket));
+        }
+        assert!("year".parse::<Bucket>().is_err());
+        assert_eq!("net".parse(), Ok(Metric::Net));
+        assert!("median".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn weekly_series_fills_gaps() {
+        let series = Series::for_history(
+            Currency::RUB,
+            &history(),
+            Bucket::Week,
+            date("2026-01-06"),
+            date("2026-01-25"),
+        )
+        .unwrap();
+        assert_eq!(series.opening, 1000);
+        let rows: Vec<(String, u64, i128, i128, i128)> = series
+            .points
+            .iter()
+            .map(|p| {
+                (
+                    p.start.to_string(),
+                    p.operations,
+                    p.inflow,
+                    p.outflow,
+                    p.balance,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                // обмен меняет остаток, но не обороты
+                ("2026-01-05".to_string(), 3, 500, 200, 1200),
+                ("2026-01-12".to_string(), 0, 0, 0, 1200),
+                ("2026-01-19".to_string(), 1, 300, 0, 1500),
+            ]
+        );
+        assert_eq!(series.values(Metric::Net), vec![300, 0, 300]);
+
+        let monthly = Series::for_history(
+            Currency::RUB,
+            &history(),
+            Bucket::Month,
+            date("2026-01-31"),
+            date("2026-02-01"),
+        )
+        .unwrap();
+        assert_eq!(monthly.values(Metric::Balance), vec![1500, 1550]);
+        assert_eq!(monthly.values(Metric::Operations), vec![4, 1]);
+
+        assert!(matches!(
+            Series::for_history(
+                Currency::RUB,
+                &[],
+                Bucket::Day,
+                date("2026-02-01"),
+                date("2026-01-01")
+            ),
+            Err(BankError::BadPeriod { .. })
+        ));
+    }
+
+    #[test]
+    fn history_out_of_time_order() {
+        // часы переводили назад: после операции за пределами периода идёт операция из него
+        let series = Series::for_history(
+            Currency::RUB,
+            &ops(&["D:100^2026-01-05T00:00:00Z", "D:50^2026-01-02T00:00:00Z"]),
+            Bucket::Day,
+            date("2026-01-01"),
+            date("2026-01-03"),
+        )
+        .unwrap();
+        assert_eq!(series.values(Metric::Operations), vec![0, 1, 0]);
+        assert_eq!(series.values(Metric::Balance), vec![0, 50, 50]);
+    }
+
+    #[test]
+    fn moving_average_and_change() {
+        let series = Series::for_history(
+            Currency::RUB,
+            &ops(&[
+                "D:100^2026-01-01T00:00:00Z",
+                "W:100^2026-01-02T00:00:00Z",
+                "D:301^2026-01-04T00:00:00Z",
+            ]),
+            Bucket::Day,
+            date("2026-01-01"),
+            date("2026-01-04"),
+        )
+        .unwrap();
+        assert_eq!(series.values(Metric::Balance), vec![100, 0, 0, 301]);
+        assert_eq!(
+            series.moving_average(Metric::Balance, 2),
+            vec![None, Some(50), Some(0), Some(150)]
+        );
+        assert_eq!(
+            series.moving_average(Metric::Net, 3),
+            vec![None, None, Some(0), Some(67)]
+        );
+        assert!(
+            series
+                .moving_average(Metric::Balance, 0)
+                .iter()
+                .all(Option::is_none)
+        );
+
+        let changes = series.change(Metric::Balance);
+        assert_eq!(changes[0], None);
+        assert_eq!(
+            changes[1],
+            Some(PeriodChange {
+                delta: -100,
+                percent: Some(-100.0)
+            })
+        );
+        // с нуля процент не определён
+        assert_eq!(
+            changes[3],
+            Some(PeriodChange {
+                delta: 301,
+                percent: None
+            })
+        );
+        let net = series.change(Metric::Net);
+        assert_eq!(net[1].unwrap().percent, Some(-200.0));
+        assert_eq!(net[2].unwrap().percent, Some(100.0));
+    }
+
+    #[test]
+    fn series_csv() {
+        let series = Series::for_history(
+            Currency::RUB,
+            &history(),
+            Bucket::Month,
+            date("2026-01-01"),
+            date("2026-03-01"),
+        )
+        .unwrap();
+        assert_eq!(
+            series.to_csv(Metric::Balance, 2),
+            "period,operations,inflow,outflow,net,balance,balance_avg2,balance_change,balance_change_pct\n\
+             2026-01-01,4,8.00,2.00,6.00,15.00,,,\n\
+             2026-02-01,1,0.50,0.00,0.50,15.50,15.25,0.50,3.33\n\
+             2026-03-01,0,0.00,0.00,0.00,15.50,15.50,0.00,0.00\n"
+        );
+        let operations = series.to_csv(Metric::Operations, 1);
+        assert!(operations.contains("operations_avg1"));
+        assert!(operations.ends_with(",0,-1,-100.00\n"), "{}", operations);
+    }
+
+    #[test]
+    fn bank_series_by_currency() {
+        let mut storage = Storage::new();
+        let account = |currency, kinds: &[&str]| {
+            let mut balance = Balance::with_currency(currency);
+            balance.last_ops = ops(kinds);
+            balance
+        };
+        storage.accounts = HashMap::from([
+            (
+                "Alice".to_string(),
+                account(
+                    Currency::RUB,
+                    &["D:1000^2026-01-01T00:00:00Z", "T-:300^2026-01-02T00:00:00Z"],
+                ),
+            ),
+            (
+                "Bob".to_string(),
+                account(Currency::RUB, &["T+:300^2026-01-02T00:00:00Z"]),
+            ),
+            (
+                "Dave".to_string(),
+                account(Currency::USD, &["D:70^2026-01-02T00:00:00Z"]),
+            ),
+        ]);
+        let all = storage
+            .series(Bucket::Day, date("2026-01-01"), date("2026-01-02"))
+            .unwrap();
+        let rub = &all[&Currency::RUB];
+        assert_eq!(rub.values(Metric::Balance), vec![1000, 1000]);
+        assert_eq!(rub.values(Metric::Inflow), vec![1000, 300]);
+        assert_eq!(rub.values(Metric::Outflow), vec![0, 300]);
+        assert_eq!(all[&Currency::USD].values(Metric::Balance), vec![0, 70]);
+
+        let bob = storage
+            .account_series(
+                &"Bob".to_string(),
+                Bucket::Month,
+                date("2026-01-01"),
+                date("2026-01-01"),
+            )
+            .unwrap();
+        assert_eq!(bob.values(Metric::Balance), vec![300]);
+        assert!(matches!(
+            storage.account_series(
+                &"Nobody".to_string(),
+                Bucket::Day,
+                date("2026-01-01"),
+                date("2026-01-01")
+            ),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert!(
+            Storage::new()
+                .series(Bucket::Day, date("2026-01-01"), date("2026-01-01"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,15 +1,63 @@
+use crate::Name;
+use crate::clock::Date;
+use crate::errors::BankError;
+use crate::holds::HoldId;
+use crate::money::{Currency, Money};
+use crate::operations::escape;
 use crate::storage::Storage;
 use my_macros::Transaction;
 use std::ops::Add;
 
-#[derive(Debug)]
-pub enum TxError {
-    InsufficientFunds,
-    InvalidAccount,
-}
+/// Номер проведённой транзакции, уникальный в пределах хранилища. Все операции
+/// транзакции, в том числе шаги комбинированной, записываются в историю с одним номером.
+pub type TxId = u64;
 
 pub trait Transaction {
-    fn apply(&self, storage: &mut Storage) -> Result<(), TxError>;
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError>;
+
+    /// Описание транзакции со всеми параметрами, например `transfer Alice Bob 1.00 RUB`.
+    /// По нему повтор с тем же ключом идемпотентности отличают от другой транзакции,
+    /// поэтому имена и другие строки в нём кодируются `%XX`, как реквизиты операций.
+    fn fingerprint(&self) -> String;
+
+    /// Количество элементарных шагов в транзакции
+    fn steps(&self) -> usize {
+        1
+    }
+
+    /// Счета, которые может затронуть транзакция, включая те, что она откроет
+    fn accounts(&self) -> Vec<&Name>;
+
+    /// Та же транзакция, все операции которой записываются в историю
+    /// с назначением платежа `memo`
+    fn with_memo(self, memo: impl Into<String>) -> WithMemo<Self>
+    where
+        Self: Sized,
+    {
+        WithMemo {
+            tx: self,
+            memo: memo.into(),
+        }
+    }
+}
+
+/// Транзакция, собранная во время выполнения, например из запроса к API
+impl<T: Transaction + ?Sized> Transaction for Box<T> {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        (**self).apply(storage)
+    }
+
+    fn fingerprint(&self) -> String {
+        (**self).fingerprint()
+    }
+
+    fn steps(&self) -> usize {
+        (**self).steps()
+    }
+
+    fn accounts(&self) -> Vec<&Name> {
+        (**self).accounts()
+    }
 }
 
 pub struct TxCombinator<T1, T2> {
@@ -18,10 +66,28 @@ pub struct TxCombinator<T1, T2> {
 }
 
 impl<T1: Transaction, T2: Transaction> Transaction for TxCombinator<T1, T2> {
-    fn apply(&self, storage: &mut Storage) -> Result<(), TxError> {
-        self.t1.apply(storage)?;
-        self.t2.apply(storage)?;
-        Ok(())
+    /// Либо применяются все шаги, либо `Storage` остаётся в исходном состоянии
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        storage.atomic(|storage| {
+            self.t1.apply(storage).map_err(|e| e.at_step(0))?;
+            self.t2
+                .apply(storage)
+                .map_err(|e| e.at_step(self.t1.steps()))
+        })
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("{} + {}", self.t1.fingerprint(), self.t2.fingerprint())
+    }
+
+    fn steps(&self) -> usize {
+        self.t1.steps() + self.t2.steps()
+    }
+
+    fn accounts(&self) -> Vec<&Name> {
+        let mut accounts = self.t1.accounts();
+        accounts.extend(self.t2.accounts());
+        accounts
     }
 }
 
@@ -33,10 +99,50 @@ impl<T1, T2, Rhs: Transaction> Add<Rhs> for TxCombinator<T1, T2> {
     }
 }
 
+/// Транзакция `tx` с назначением платежа `memo`
+pub struct WithMemo<T> {
+    pub tx: T,
+    pub memo: String,
+}
+
+impl<T: Transaction> Transaction for WithMemo<T> {
+    fn apply(&self, storage: &mut Storage) -> Result<(), BankError> {
+        storage.with_memo(&self.memo, |storage| self.tx.apply(storage))
+    }
+
+    /// Назначение входит в отпечаток: тот же перевод с другим назначением —
+    /// другая транзакция. Оно кодируется, как в истории, а цепочка берётся в скобки,
+    /// чтобы назначение всей цепочки не совпало с назначением её последнего шага.
+    fn fingerprint(&self) -> String {
+        let memo = escape(&self.memo);
+        if self.tx.steps() > 1 {
+            format!("( {} ) memo {}", self.tx.fingerprint(), memo)
+        } else {
+            format!("{} memo {}", self.tx.fingerprint(), memo)
+        }
+    }
+
+    fn steps(&self) -> usize {
+        self.tx.steps()
+    }
+
+    fn accounts(&self) -> Vec<&Name> {
+        self.tx.accounts()
+    }
+}
+
+impl<T, Rhs: Transaction> Add<Rhs> for WithMemo<T> {
+    type Output = TxCombinator<WithMemo<T>, Rhs>;
+
+    fn add(self, rhs: Rhs) -> Self::Output {
+        TxCombinator { t1: self, t2: rhs }
+    }
+}
+
 #[derive(Transaction)]
 pub struct Deposit {
     pub account: String,
-    pub amount: u64,
+    pub amount: Money,
 }
 
 impl<T: Transaction> Add<T> for Deposit {
@@ -51,7 +157,7 @@ impl<T: Transaction> Add<T> for Deposit {
 #[transaction("withdraw")]
 pub struct Withdraw {
     pub account: String,
-    pub amount: u64,
+    pub amount: Money,
 }
 
 #[derive(Transaction)]
@@ -59,7 +165,7 @@ pub struct Withdraw {
 pub struct Transfer {
     pub from: String,
     pub to: String,
-    pub amount: u64,
+    pub amount: Money,
 }
 
 impl<T: Transaction> Add<T> for Transfer {
@@ -70,17 +176,64 @@ impl<T: Transaction> Add<T> for Transfer {
     }
 }
 
+/// Обмен `amount` на валюту `to` внутри одного счёта по курсам из `Storage::rates`
+#[derive(Transaction)]
+#[transaction("exchange")]
+pub struct Exchange {
+    pub account: String,
+    pub amount: Money,
+    pub to: Currency,
+}
+
+/// Блокировка `amount` на счёте до дня `expires` под номером `hold`:
+/// доступный остаток уменьшается, баланс — нет
+#[derive(Transaction)]
+#[transaction("hold")]
+pub struct Hold {
+    pub account: String,
+    pub hold: HoldId,
+    pub amount: Money,
+    pub expires: Date,
+}
+
+/// Списание `amount` по блокировке `hold`; остаток блокировки снимается
+#[derive(Transaction)]
+#[transaction("capture")]
+pub struct Capture {
+    pub account: String,
+    pub hold: HoldId,
+    pub amount: Money,
+}
+
+/// Снятие блокировки `hold` без списания
+#[derive(Transaction)]
+#[transaction("release")]
+pub struct Release {
+    pub account: String,
+    pub hold: HoldId,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::money::{Amount, Currency};
+    use crate::storage::AccountPolicy;
+    use crate::testing::rub;
+
+    /// Хранилище, которое открывает счета при первом зачислении
+    fn auto_create() -> Storage {
+        let mut storage = Storage::new();
+        storage.account_policy = AccountPolicy::AutoCreate;
+        storage
+    }
 
     #[test]
     fn deposit_creates_account() {
-        let mut storage = Storage::new();
+        let mut storage = auto_create();
 
         let tx = Deposit {
             account: "Alice".to_string(),
-            amount: 100,
+            amount: rub(100),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -94,7 +247,7 @@ mod tests {
 
         let tx = Deposit {
             account: "Bob".to_string(),
-            amount: 50,
+            amount: rub(50),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -102,7 +255,7 @@ mod tests {
 
         let tx2 = Deposit {
             account: "Bob".to_string(),
-            amount: 30,
+            amount: rub(30),
         };
         assert!(tx2.apply(&mut storage).is_ok());
         assert_eq!(storage.accounts.get("Bob").unwrap().result, 80);
@@ -112,13 +265,13 @@ mod tests {
     fn transfer_success() {
         let mut storage = Storage::new();
         storage.add_user("Alice".to_string());
-        storage.accounts.get_mut("Alice").unwrap().result = 100;
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(100).into();
         storage.add_user("Bob".to_string());
 
         let tx = Transfer {
             from: "Alice".to_string(),
             to: "Bob".to_string(),
-            amount: 40,
+            amount: rub(40),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -130,11 +283,11 @@ mod tests {
     fn withdraw_success() {
         let mut storage = Storage::new();
         storage.add_user("Dima".to_string());
-        storage.accounts.get_mut("Dima").unwrap().result = 100;
+        storage.accounts.get_mut("Dima").unwrap().result = Amount::new(100).into();
 
         let tx = Withdraw {
             account: "Dima".to_string(),
-            amount: 70,
+            amount: rub(70),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -142,7 +295,7 @@ mod tests {
 
         let tx2 = Withdraw {
             account: "Dima".to_string(),
-            amount: 30,
+            amount: rub(30),
         };
         assert!(tx2.apply(&mut storage).is_ok());
         assert_eq!(storage.accounts.get("Dima").unwrap().result, 0);
@@ -152,43 +305,56 @@ mod tests {
     fn withdraw_insufficient_funds() {
         let mut storage = Storage::new();
         storage.add_user("Dima".to_string());
-        storage.accounts.get_mut("Dima").unwrap().result = 30;
+        storage.accounts.get_mut("Dima").unwrap().result = Amount::new(30).into();
 
         let tx = Withdraw {
             account: "Dima".to_string(),
-            amount: 70,
+            amount: rub(70),
         };
 
         let result = tx.apply(&mut storage);
-        assert!(matches!(result, Err(TxError::InsufficientFunds)));
+        match result {
+            Err(BankError::NotEnoughMoney {
+                account,
+                required,
+                available,
+                credit,
+            }) => {
+                assert_eq!(account, "Dima");
+                assert_eq!(required, rub(70));
+                assert_eq!(available, rub(30));
+                assert_eq!(credit, rub(0));
+            }
+            other => panic!("Ожидалась NotEnoughMoney, получено {:?}", other),
+        }
     }
 
     #[test]
     fn transfer_insufficient_funds() {
         let mut storage = Storage::new();
         storage.add_user("Alice".to_string());
-        storage.accounts.get_mut("Alice").unwrap().result = 30;
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(30).into();
 
         let tx = Transfer {
             from: "Alice".to_string(),
             to: "Bob".to_string(),
-            amount: 50,
+            amount: rub(50),
         };
 
         let result = tx.apply(&mut storage);
-        assert!(matches!(result, Err(TxError::InsufficientFunds)));
+        assert!(matches!(result, Err(BankError::NotEnoughMoney { .. })));
     }
 
     #[test]
     fn transfer_creates_recipient() {
-        let mut storage = Storage::new();
+        let mut storage = auto_create();
         storage.add_user("Alice".to_string());
-        storage.accounts.get_mut("Alice").unwrap().result = 100;
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(100).into();
 
         let tx = Transfer {
             from: "Alice".to_string(),
             to: "NewUser".to_string(),
-            amount: 25,
+            amount: rub(25),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -196,16 +362,217 @@ mod tests {
     }
 
     #[test]
-    fn combined_deposit_and_transfer() {
+    fn history_records_time_counterparty_and_memo() {
+        use crate::clock::{ManualClock, Timestamp};
+        use std::sync::Arc;
+
+        let mut storage = auto_create();
+        let clock = Arc::new(ManualClock::new("2026-01-31".parse().unwrap()));
+        storage.clock = clock.clone();
+        Deposit {
+            account: "Alice".to_string(),
+            amount: rub(100),
+        }
+        .apply(&mut storage)
+        .unwrap();
+        clock.advance_secs(60);
+        let rent = Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(30),
+        }
+        .with_memo("аренда")
+            + Withdraw {
+                account: "Bob".to_string(),
+                amount: rub(10),
+            };
+        assert_eq!(
+            rent.fingerprint(),
+            "transfer Alice Bob 0.30 RUB memo аренда + withdraw Bob 0.10 RUB"
+        );
+        let chained = (Deposit {
+            account: "Alice".to_string(),
+            amount: rub(30),
+        } + Withdraw {
+            account: "Bob".to_string(),
+            amount: rub(10),
+        })
+        .with_memo("аренда; январь");
+        assert_eq!(
+            chained.fingerprint(),
+            "( deposit Alice 0.30 RUB + withdraw Bob 0.10 RUB ) memo аренда%3B%20январь"
+        );
+        storage.submit(&rent, None).unwrap();
+
+        let at: Timestamp = "2026-01-31T00:01:00Z".parse().unwrap();
+        let alice = &storage.get_balance(&"Alice".to_string()).unwrap().last_ops;
+        assert_eq!(alice[0].meta.memo, None);
+        assert_eq!(alice[1].meta.at, Some(at));
+        assert_eq!(alice[1].meta.counterparty.as_deref(), Some("Bob"));
+        assert_eq!(alice[1].meta.memo.as_deref(), Some("аренда"));
+        let bob = &storage.accounts["Bob"].last_ops;
+        assert_eq!(bob[0].meta.counterparty.as_deref(), Some("Alice"));
+        assert_eq!(bob[0].meta.tx, alice[1].meta.tx);
+        // назначение относится только к переводу, а не ко всей цепочке
+        assert_eq!(bob[1].meta.memo, None);
+        assert_eq!(bob[1].meta.counterparty, None);
+    }
+
+    #[test]
+    fn derived_transactions_record_history() {
+        use crate::operations::OpKind;
+
+        let mut storage = auto_create();
+
+        let tx = Deposit {
+            account: "Alice".to_string(),
+            amount: rub(100),
+        } + Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(30),
+        } + Withdraw {
+            account: "Bob".to_string(),
+            amount: rub(10),
+        };
+        assert!(tx.apply(&mut storage).is_ok());
+
+        assert_eq!(
+            storage.accounts["Alice"]
+                .last_ops
+                .iter()
+                .map(|op| op.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                OpKind::Deposit(Amount::new(100)),
+                OpKind::TransferOut(Amount::new(30))
+            ]
+        );
+        assert_eq!(
+            storage.accounts["Bob"]
+                .last_ops
+                .iter()
+                .map(|op| op.kind.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                OpKind::TransferIn(Amount::new(30)),
+                OpKind::Withdraw(Amount::new(10))
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_policy_rejects_unknown_accounts() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(100).into();
+
+        let tx = Deposit {
+            account: "Alcie".to_string(),
+            amount: rub(10),
+        };
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::UserNotFound(ref name)) if name == "Alcie"
+        ));
+
+        // опечатка в имени получателя не уводит деньги на новый счёт
+        let tx = Transfer {
+            from: "Alice".to_string(),
+            to: "Bbo".to_string(),
+            amount: rub(25),
+        };
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert_eq!(storage.accounts["Alice"].result, 100);
+        assert_eq!(storage.accounts.len(), 1);
+    }
+
+    #[test]
+    fn self_transfer_is_rejected() {
+        let mut storage = auto_create();
+        storage.add_user("Alice".to_string());
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(100).into();
+
+        let tx = Transfer {
+            from: "Alice".to_string(),
+            to: "Alice".to_string(),
+            amount: rub(25),
+        };
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::SelfTransfer(_))
+        ));
+        assert!(storage.accounts["Alice"].last_ops.is_empty());
+    }
+
+    #[test]
+    fn transfer_from_unknown_account() {
         let mut storage = Storage::new();
 
+        let tx = Transfer {
+            from: "Ghost".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(10),
+        };
+
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::UserNotFound(_))
+        ));
+        assert!(storage.accounts.is_empty());
+    }
+
+    #[test]
+    fn transfer_rejects_mixed_currencies() {
+        let mut storage = Storage::new();
+        storage.add_user("Alice".to_string());
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(100).into();
+        storage.add_user_with_currency("Bob".to_string(), Currency::USD);
+
+        let tx = Transfer {
+            from: "Alice".to_string(),
+            to: "Bob".to_string(),
+            amount: rub(40),
+        };
+
+        assert!(matches!(
+            tx.apply(&mut storage),
+            Err(BankError::CurrencyMismatch { .. })
+        ));
+        assert_eq!(storage.accounts["Alice"].result, 100);
+        assert_eq!(storage.accounts["Bob"].result, 0);
+    }
+
+    #[test]
+    fn deposit_creates_account_in_its_currency() {
+        let mut storage = auto_create();
+
+        let tx = Deposit {
+            account: "Bob".to_string(),
+            amount: Money::new(100, Currency::EUR),
+        };
+
+        assert!(tx.apply(&mut storage).is_ok());
+        assert_eq!(
+            storage.accounts["Bob"].money(),
+            Money::new(100, Currency::EUR)
+        );
+    }
+
+    #[test]
+    fn combined_deposit_and_transfer() {
+        let mut storage = auto_create();
+
         let tx = Deposit {
             account: "Alice".to_string(),
-            amount: 100,
+            amount: rub(100),
         } + Transfer {
             from: "Alice".to_string(),
             to: "Bob".to_string(),
-            amount: 30,
+            amount: rub(30),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -215,17 +582,17 @@ mod tests {
 
     #[test]
     fn combined_multiple_deposits() {
-        let mut storage = Storage::new();
+        let mut storage = auto_create();
 
         let tx = Deposit {
             account: "Alice".to_string(),
-            amount: 50,
+            amount: rub(50),
         } + Deposit {
             account: "Bob".to_string(),
-            amount: 100,
+            amount: rub(100),
         } + Deposit {
             account: "Alice".to_string(),
-            amount: 25,
+            amount: rub(25),
         };
 
         assert!(tx.apply(&mut storage).is_ok());
@@ -235,19 +602,92 @@ mod tests {
 
     #[test]
     fn combined_fails_on_insufficient_funds() {
-        let mut storage = Storage::new();
+        let mut storage = auto_create();
 
         let tx = Deposit {
             account: "Alice".to_string(),
-            amount: 50,
+            amount: rub(50),
         } + Transfer {
             from: "Alice".to_string(),
             to: "Bob".to_string(),
-            amount: 100,
+            amount: rub(100),
         };
 
         let result = tx.apply(&mut storage);
-        assert!(matches!(result, Err(TxError::InsufficientFunds)));
-        assert_eq!(storage.accounts.get("Alice").unwrap().result, 50);
+        match result {
+            Err(BankError::StepFailed { step, source }) => {
+                assert_eq!(step, 1);
+                assert!(matches!(*source, BankError::NotEnoughMoney { .. }));
+            }
+            _ => panic!("Ожидалась ошибка StepFailed"),
+        }
+        // депозит откатился вместе с упавшим переводом
+        assert!(!storage.accounts.contains_key("Alice"));
+        assert!(!storage.accounts.contains_key("Bob"));
+    }
+
+    #[test]
+    fn combined_rollback_keeps_existing_balances() {
+        let mut storage = auto_create();
+        storage.add_user("Alice".to_string());
+        storage.accounts.get_mut("Alice").unwrap().result = Amount::new(10).into();
+
+        let tx = crate::tx_chain!(
+            Deposit {
+                account: "Alice".to_string(),
+                amount: rub(40),
+            },
+            Transfer {
+                from: "Alice".to_string(),
+                to: "Bob".to_string(),
+                amount: rub(20),
+            },
+            Withdraw {
+                account: "Alice".to_string(),
+                amount: rub(100),
+            }
+        );
+
+        let result = tx.apply(&mut storage);
+        assert!(matches!(result, Err(BankError::StepFailed { step: 2, .. })));
+        assert_eq!(storage.accounts.get("Alice").unwrap().result, 10);
+        assert!(!storage.accounts.contains_key("Bob"));
+    }
+
+    #[test]
+    fn exchange_then_transfer_rolls_back_together() {
+        let mut storage = auto_create();
+        storage
+            .rates
+            .set(Currency::USD, Currency::RUB, "90".parse().unwrap());
+        let tx = Deposit {
+            account: "Alice".to_string(),
+            amount: rub(9000),
+        } + Exchange {
+            account: "Alice".to_string(),
+            amount: rub(9000),
+            to: Currency::USD,
+        };
+        tx.apply(&mut storage).unwrap();
+        let alice = &storage.accounts["Alice"];
+        assert_eq!(alice.result, 0);
+        assert_eq!(alice.amount_in(Currency::USD), Some(Amount::new(100)));
+
+        // обмен в валюту без курса откатывает всю цепочку
+        let tx = Deposit {
+            account: "Alice".to_string(),
+            amount: rub(100),
+        } + Exchange {
+            account: "Alice".to_string(),
+            amount: Money::new(100, Currency::USD),
+            to: Currency::EUR,
+        };
+        let result = tx.apply(&mut storage);
+        assert!(matches!(
+            result,
+            Err(BankError::StepFailed { step: 1, ref source }) if matches!(**source, BankError::NoRate { .. })
+        ));
+        assert_eq!(storage.accounts["Alice"].result, 0);
+        assert_eq!(storage.accounts["Alice"].last_ops.len(), 2);
     }
 }